@@ -43,7 +43,7 @@ impl eframe::App for App {
                     if ui.button("load").clicked() {
                         if let Ok(mut p) = Player::new(ctx, &self.media_path.replace("\"", "")) {
                             p.enable_keybinds(true);
-                            self.player = Some(p.with_overlay(DefaultOverlay));
+                            self.player = Some(p.with_overlay(DefaultOverlay::default()));
                             ui.data_mut(|d| d.insert_persisted(path_id, self.media_path.clone()));
                         }
                     }
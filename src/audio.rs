@@ -4,27 +4,54 @@ use anyhow::bail;
 use anyhow::{Context, Result};
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use cpal::{SampleFormat, Stream, StreamConfig, StreamInstant};
-use log::{error, info};
+use log::{error, info, warn};
 use scaletempo2::{
-    mp_scaletempo2, mp_scaletempo2_create, mp_scaletempo2_fill_input_buffer,
-    mp_scaletempo2_get_default_opts,
+    mp_scaletempo2, mp_scaletempo2_create, mp_scaletempo2_destroy, mp_scaletempo2_fill_buffer,
+    mp_scaletempo2_fill_input_buffer, mp_scaletempo2_frames_available,
+    mp_scaletempo2_get_default_opts, mp_scaletempo2_reset,
 };
 use std::collections::VecDeque;
 use std::marker::PhantomData;
 use std::ptr::NonNull;
-use std::sync::atomic::Ordering;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::mpsc;
 use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
+/// Highest output sample rate picked when opening a stream
+const MAX_SAMPLE_RATE: u32 = 192_000;
+
+/// Sample rates reported for devices which support a continuous range
+const COMMON_SAMPLE_RATES: [u32; 6] = [44_100, 48_000, 88_200, 96_000, 176_400, 192_000];
+
 /// The playback device. Needs to be initialized (and kept alive!) for use by a [`Player`].
+#[derive(Clone)]
 pub struct AudioDevice(pub(crate) cpal::Device);
 
+/// Output device found by [`AudioDevice::enumerate_devices`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AudioDeviceInfo {
+    /// Device name, pass to [`AudioDevice::open_by_name`] to use it
+    pub name: String,
+    /// Name of the audio host (ALSA, WASAPI, CoreAudio etc.)
+    pub host: String,
+    /// If this is the default output device of its host
+    pub default: bool,
+}
+
+/// Callback receiving the interleaved output samples of each audio device callback
+pub type AudioTap = Arc<dyn Fn(&[f32]) + Send + Sync>;
+
 /// Handle to an actively running audio stream
 pub struct AudioDeviceHandle {
     device: AudioDevice,
     stream: Stream,
     config: StreamConfig,
+    #[allow(unused)]
+    audio_tap: Option<AudioTap>,
+    /// Output latency measured in the last device callback (microseconds)
+    latency: Arc<AtomicU64>,
 }
 
 impl crate::AudioDevice for AudioDeviceHandle {
@@ -35,6 +62,19 @@ impl crate::AudioDevice for AudioDeviceHandle {
     fn sample_rate(&self) -> u32 {
         self.config.sample_rate as _
     }
+
+    fn latency(&self) -> Duration {
+        AudioDeviceHandle::latency(self)
+    }
+}
+
+impl AudioDeviceHandle {
+    /// Output latency of the stream, the time between the device callback and the samples
+    /// being played. cpal has no latency query, so this is measured from the callback
+    /// timestamps and is zero until the first callback
+    pub fn latency(&self) -> Duration {
+        Duration::from_micros(self.latency.load(Ordering::Relaxed))
+    }
 }
 
 impl AudioDevice {
@@ -57,23 +97,140 @@ impl AudioDevice {
         Self::from_subsystem(&host)
     }
 
+    /// List the output devices of all available audio hosts
+    pub fn enumerate_devices() -> Vec<AudioDeviceInfo> {
+        Self::output_devices()
+            .into_iter()
+            .map(|(info, _)| info)
+            .collect()
+    }
+
+    /// Open the first output device with a matching name, see [`AudioDevice::enumerate_devices`]
+    pub fn open_by_name(name: &str) -> Result<AudioDevice> {
+        match Self::output_devices()
+            .into_iter()
+            .find(|(info, _)| info.name == name)
+        {
+            Some((_, dev)) => Ok(AudioDevice(dev)),
+            None => bail!("Audio device not found: {}", name),
+        }
+    }
+
+    fn output_devices() -> Vec<(AudioDeviceInfo, cpal::Device)> {
+        let mut ret = Vec::new();
+        for id in cpal::available_hosts() {
+            let host = match cpal::host_from_id(id) {
+                Ok(h) => h,
+                Err(e) => {
+                    warn!("Audio host {} unavailable: {}", id.name(), e);
+                    continue;
+                }
+            };
+            let default_name = host
+                .default_output_device()
+                .and_then(|d| d.description().ok())
+                .map(|d| d.name().to_string());
+            let devices = match host.output_devices() {
+                Ok(d) => d,
+                Err(e) => {
+                    warn!("Failed to list devices of {}: {}", id.name(), e);
+                    continue;
+                }
+            };
+            for dev in devices {
+                let Ok(desc) = dev.description() else {
+                    continue;
+                };
+                let name = desc.name().to_string();
+                ret.push((
+                    AudioDeviceInfo {
+                        default: default_name.as_ref() == Some(&name),
+                        host: id.name().to_string(),
+                        name,
+                    },
+                    dev,
+                ));
+            }
+        }
+        ret
+    }
+
+    /// List the output sample rates supported by this device, sorted ascending.
+    ///
+    /// Continuous ranges are reported by their bounds and the common rates within them.
+    pub fn supported_sample_rates(&self) -> Vec<u32> {
+        let configs = match self.0.supported_output_configs() {
+            Ok(c) => c,
+            Err(e) => {
+                warn!("Failed to query supported output configs: {}", e);
+                return Vec::new();
+            }
+        };
+        let mut ret = Vec::new();
+        for range in configs {
+            let (min, max) = (range.min_sample_rate(), range.max_sample_rate());
+            ret.push(min);
+            ret.push(max);
+            ret.extend(
+                COMMON_SAMPLE_RATES
+                    .iter()
+                    .filter(|r| (min..=max).contains(*r)),
+            );
+        }
+        ret.sort_unstable();
+        ret.dedup();
+        ret
+    }
+
+    /// Highest supported output rate (up to [`MAX_SAMPLE_RATE`]) for the given channel count
+    fn best_sample_rate(&self, channels: u16) -> Option<u32> {
+        let configs = self.0.supported_output_configs().ok()?;
+        configs
+            .filter(|c| c.channels() == channels)
+            .filter(|c| c.min_sample_rate() <= MAX_SAMPLE_RATE)
+            .map(|c| c.max_sample_rate().min(MAX_SAMPLE_RATE))
+            .max()
+    }
+
+    /// Open an audio stream on this device using its default output config at the
+    /// highest supported sample rate
     pub fn open_default_audio_stream(
+        self,
         p: SharedPlaybackState,
         rx: Receiver<AudioSamples>,
     ) -> Result<AudioDeviceHandle> {
-        let device = AudioDevice::new()?;
+        self.open_shared_audio_stream(p, Arc::new(Mutex::new(rx)), None)
+    }
+
+    /// Open an audio stream reading from a shared receiver, so the samples can be
+    /// handed over to another device when switching outputs
+    pub(crate) fn open_shared_audio_stream(
+        self,
+        p: SharedPlaybackState,
+        rx: Arc<Mutex<Receiver<AudioSamples>>>,
+        audio_tap: Option<AudioTap>,
+    ) -> Result<AudioDeviceHandle> {
+        let device = self;
         let cfg = device.0.default_output_config()?;
         info!(
-            "Default audio device config: {} {}Hz, {}ch, {:?}",
+            "Audio device config: {} {}Hz, {}ch, {:?}",
             device.0.description()?.name(),
             cfg.sample_rate(),
             cfg.channels(),
             cfg.sample_format(),
         );
 
-        let channels = cfg.channels() as u8;
-        let sample_rate = cfg.sample_rate() as u32;
-        let mut first_frame = false;
+        let mut config = cfg.config();
+        if let Some(rate) = device.best_sample_rate(cfg.channels()) {
+            config.sample_rate = rate;
+        }
+        let channels = config.channels as u8;
+        let sample_rate = config.sample_rate;
+        info!("Using output sample rate {}Hz", sample_rate);
+        // audio delay which is currently applied to the queue
+        let mut applied_delay = 0.0;
+        // samples per channel still to be skipped for a negative delay change
+        let mut skip = 0;
 
         // update the playback state with the audio device playback details
         p.sample_rate.store(sample_rate, Ordering::Relaxed);
@@ -84,17 +241,27 @@ impl AudioDevice {
             simple_queue.push(VecDeque::new());
         }
         let mut audio_scale = AudioScale::new(channels, sample_rate).expect("audio scale");
+        let tap = audio_tap.clone();
+        let latency = Arc::new(AtomicU64::new(0));
+        let cb_latency = latency.clone();
         let stream = device.0.build_output_stream_raw(
-            &cfg.config(),
+            &config,
             SampleFormat::F32,
             move |data: &mut cpal::Data, info: &cpal::OutputCallbackInfo| {
                 if data.len() == 0 {
                     return;
                 }
+                let buffer_delay = info
+                    .timestamp()
+                    .playback
+                    .duration_since(&info.timestamp().callback)
+                    .unwrap_or(Duration::ZERO);
+                cb_latency.store(buffer_delay.as_micros() as _, Ordering::Relaxed);
                 let dst: &mut [f32] = data.as_slice_mut().unwrap();
                 dst.fill(0.0);
                 let state = p.state();
                 if state == PlayerState::Stopped || state == PlayerState::Paused {
+                    p.update_audio_level(dst, channels as _);
                     return;
                 }
                 // number of samples per channel to drain
@@ -104,26 +271,58 @@ impl AudioDevice {
                     panic!("Nothing to drain");
                 }
 
+                // apply audio delay changes, pad with silence or skip samples
+                let delay = p.audio_delay();
+                if delay != applied_delay {
+                    let change = delay - applied_delay;
+                    let samples = (change.abs() * sample_rate as f64) as usize;
+                    if change > 0.0 {
+                        let pad = samples.saturating_sub(skip);
+                        skip = skip.saturating_sub(samples);
+                        for q in simple_queue.iter_mut() {
+                            for _ in 0..pad {
+                                q.push_front(0.0);
+                            }
+                        }
+                    } else {
+                        skip += samples;
+                    }
+                    applied_delay = delay;
+                }
+                skip_samples(&mut simple_queue, &mut skip);
+
+                let Ok(rx) = rx.lock() else {
+                    return;
+                };
+                // opened or seeked, the queued samples are from the old position
+                if p.audio_resync_pending() {
+                    for q in simple_queue.iter_mut() {
+                        q.clear();
+                    }
+                    audio_scale.reset();
+                }
+                // samples played per channel, faster playback consumes more input
+                let speed = p.speed();
+                let needed = (stride as f64 * speed.max(1.0) as f64).ceil() as usize;
                 // fill queue until dst is satisfied
-                while simple_queue[0].len() < stride {
+                while simple_queue[0].len() < needed {
                     // take samples from channel
                     match rx.try_recv() {
                         Ok(m) => {
-                            // for the first frame set the queue head pts
-                            if !first_frame {
-                                first_frame = true;
-                                let buffer_delay = info
-                                    .timestamp()
-                                    .playback
-                                    .duration_since(&info.timestamp().callback)
-                                    .unwrap_or(Duration::ZERO)
-                                    .as_secs_f64();
-                                info!("First audio frame pts={}, delay={}", m.pts, buffer_delay);
-                                p.incr_audio_pts(buffer_delay);
+                            // the first samples after opening or seeking set the clock,
+                            // stream PTS don't start at 0 for all containers (MPEG-TS, HLS)
+                            let resync = p.audio_resync_pending();
+                            let latency = buffer_delay.as_secs_f64();
+                            if !p.sync_audio_clock(m.pts, m.duration, latency) {
+                                continue;
+                            }
+                            if resync {
+                                info!("Audio clock set from pts={}, delay={}", m.pts, latency);
                             }
                             for (chan, data) in m.data.into_iter().enumerate() {
                                 simple_queue[chan].extend(data);
                             }
+                            skip_samples(&mut simple_queue, &mut skip);
                         }
                         Err(mpsc::TryRecvError::Empty) => {
                             continue;
@@ -133,42 +332,64 @@ impl AudioDevice {
                         }
                     }
                 }
-                let mut in_samples = simple_queue
-                    .iter_mut()
-                    .map(|r| r.drain(..stride).collect::<Vec<_>>())
-                    .collect::<Vec<_>>();
-
-                // move queue head pts
-                let drain_samples_pts = stride as f64 / sample_rate as f64;
-                p.incr_audio_pts(drain_samples_pts);
-
-                // after draining all the samples, drop them
-                if p.muted() {
-                    return;
-                }
-
-                let speed = p.speed();
                 let volume = p.volume();
                 if speed != 1.0 {
-                    let dst_samples = dst.len() / channels as usize;
-                    // create a buffer to hold the output samples
-                    // device samples are always packed
-                    let mut out_samples = Vec::with_capacity(channels as usize);
-                    out_samples.resize_with(channels as _, || {
-                        let mut v_line = Vec::with_capacity(dst_samples);
-                        v_line.resize(dst_samples, 0.0);
-                        v_line
-                    });
-
-                    todo!();
+                    // time-stretch to keep the pitch, the clock moves by the input consumed
+                    let mut out_samples = vec![vec![0.0; stride]; channels as usize];
+                    let mut written = 0;
+                    while written < stride {
+                        if audio_scale.frames_available(speed) {
+                            let n = audio_scale.fill_output(&mut out_samples, written, speed);
+                            if n == 0 {
+                                break;
+                            }
+                            written += n;
+                        } else {
+                            let consumed = audio_scale.fill_input(&mut simple_queue, speed);
+                            if consumed == 0 {
+                                // ran out of decoded samples, the rest stays silent
+                                break;
+                            }
+                            p.incr_audio_pts(consumed as f64 / sample_rate as f64);
+                        }
+                    }
+                    if !p.muted() {
+                        let chans = out_samples.len();
+                        for (x, chan) in out_samples.iter().enumerate() {
+                            for z in 0..written {
+                                dst[x + (chans * z)] = chan[z] * volume;
+                            }
+                        }
+                    }
                 } else {
+                    // samples buffered in the scaler are from before the speed change
+                    audio_scale.reset();
+                    let mut in_samples = simple_queue
+                        .iter_mut()
+                        .map(|r| r.drain(..stride.min(r.len())).collect::<Vec<_>>())
+                        .collect::<Vec<_>>();
+
+                    // move queue head pts
+                    let drain_samples_pts = stride as f64 / sample_rate as f64;
+                    p.incr_audio_pts(drain_samples_pts);
+
+                    // after draining all the samples, drop them
+                    if p.muted() {
+                        p.update_audio_level(dst, channels as _);
+                        return;
+                    }
+
                     let chans = in_samples.len();
                     for (x, chan) in in_samples.iter_mut().enumerate() {
-                        for z in 0..stride {
-                            dst[x + (chans * z)] = chan[z] * volume;
+                        for (z, v) in chan.iter().enumerate() {
+                            dst[x + (chans * z)] = v * volume;
                         }
                     }
                 }
+                p.update_audio_level(dst, channels as _);
+                if let Some(tap) = &tap {
+                    tap(dst);
+                }
             },
             move |e| {
                 error!("{}", e);
@@ -179,16 +400,34 @@ impl AudioDevice {
         Ok(AudioDeviceHandle {
             device,
             stream,
-            config: cfg.config(),
+            config,
+            audio_tap,
+            latency,
         })
     }
 }
 
+/// Drop up to `skip` samples from the head of each channel queue
+fn skip_samples(queue: &mut [VecDeque<f32>], skip: &mut usize) {
+    if *skip == 0 {
+        return;
+    }
+    let n = (*skip).min(queue.iter().map(|q| q.len()).min().unwrap_or(0));
+    for q in queue.iter_mut() {
+        q.drain(..n);
+    }
+    *skip -= n;
+}
+
+/// Time-stretching for playback at other speeds, keeps the pitch of the audio
 struct AudioScale {
     ctx: NonNull<mp_scaletempo2>,
     _m: PhantomData<mp_scaletempo2>,
 }
 
+// the scaler is only used from the audio callback thread
+unsafe impl Send for AudioScale {}
+
 impl AudioScale {
     pub fn new(channels: u8, sample_rate: u32) -> Result<AudioScale> {
         unsafe {
@@ -204,21 +443,67 @@ impl AudioScale {
         }
     }
 
-    pub fn process(
-        &mut self,
-        in_samples: Vec<Vec<f32>>,
-        in_size: usize,
-        out_samples: Vec<Vec<f32>>,
-        speed: f64,
-    ) {
-        let mut in_ptrs = in_samples.iter().map(|s| s.as_ptr()).collect::<Vec<_>>();
+    /// Drop all buffered audio, e.g. after seeking
+    pub fn reset(&mut self) {
+        unsafe { mp_scaletempo2_reset(self.ctx.as_ptr()) }
+    }
+
+    /// If output can be produced without more input
+    pub fn frames_available(&mut self, speed: f32) -> bool {
+        unsafe { mp_scaletempo2_frames_available(self.ctx.as_ptr(), speed as _) }
+    }
+
+    /// Move samples from the channel queues into the scaler, returns the number of samples
+    /// per channel which were consumed
+    pub fn fill_input(&mut self, queue: &mut [VecDeque<f32>], speed: f32) -> usize {
+        let len = queue.iter().map(|q| q.len()).min().unwrap_or(0);
+        if len == 0 {
+            return 0;
+        }
+        let mut planes = queue
+            .iter_mut()
+            .map(|q| q.make_contiguous().as_ptr() as *mut u8)
+            .collect::<Vec<_>>();
+        let consumed = unsafe {
+            mp_scaletempo2_fill_input_buffer(
+                self.ctx.as_ptr(),
+                planes.as_mut_ptr(),
+                len as _,
+                speed as _,
+            )
+        }
+        .max(0) as usize;
+        for q in queue.iter_mut() {
+            q.drain(..consumed);
+        }
+        consumed
+    }
+
+    /// Write time-stretched samples into `out` from `offset`, returns the number of samples
+    /// per channel which were written
+    pub fn fill_output(&mut self, out: &mut [Vec<f32>], offset: usize, speed: f32) -> usize {
+        let len = out.iter().map(|c| c.len()).min().unwrap_or(0);
+        if offset >= len {
+            return 0;
+        }
+        let mut planes = out
+            .iter_mut()
+            .map(|c| c[offset..].as_mut_ptr())
+            .collect::<Vec<_>>();
         unsafe {
-            let proc_samples = mp_scaletempo2_fill_input_buffer(
-                self.ctx.as_mut(),
-                in_ptrs.as_mut_ptr() as _,
-                in_size as _,
-                speed,
-            );
+            mp_scaletempo2_fill_buffer(
+                self.ctx.as_ptr(),
+                planes.as_mut_ptr(),
+                (len - offset) as _,
+                speed as _,
+            )
         }
+        .max(0) as usize
+    }
+}
+
+impl Drop for AudioScale {
+    fn drop(&mut self) {
+        unsafe { mp_scaletempo2_destroy(self.ctx.as_ptr()) }
     }
 }
@@ -0,0 +1,901 @@
+use crate::http::{Headers, http_get};
+use anyhow::{Result, bail};
+use itertools::Itertools;
+use log::{info, warn};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::{Reader, XmlVersion};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::Read;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use url::Url;
+
+/// Media type of a DASH representation
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DashContentType {
+    Video,
+    Audio,
+    #[default]
+    Other,
+}
+
+impl DashContentType {
+    fn from_mime(mime: &str) -> Self {
+        if mime.starts_with("video/") {
+            Self::Video
+        } else if mime.starts_with("audio/") {
+            Self::Audio
+        } else {
+            Self::Other
+        }
+    }
+}
+
+/// `SegmentTemplate` of a representation, missing values are inherited from the adaptation set
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SegmentTemplate {
+    pub initialization: Option<String>,
+    pub media: Option<String>,
+    pub start_number: Option<u64>,
+    pub timescale: Option<u64>,
+    /// Segment duration in timescale units, used when there is no timeline
+    pub duration: Option<u64>,
+    /// `SegmentTimeline` entries (t, d, r)
+    pub timeline: Vec<(Option<u64>, u64, i64)>,
+}
+
+impl SegmentTemplate {
+    fn from_element(e: &BytesStart) -> Result<Self> {
+        let attrs = attributes(e)?;
+        Ok(Self {
+            initialization: attrs.get("initialization").cloned(),
+            media: attrs.get("media").cloned(),
+            start_number: attrs.get("startNumber").and_then(|v| v.parse().ok()),
+            timescale: attrs.get("timescale").and_then(|v| v.parse().ok()),
+            duration: attrs.get("duration").and_then(|v| v.parse().ok()),
+            timeline: Vec::new(),
+        })
+    }
+
+    fn inherit(&mut self, parent: &SegmentTemplate) {
+        if self.initialization.is_none() {
+            self.initialization = parent.initialization.clone();
+        }
+        if self.media.is_none() {
+            self.media = parent.media.clone();
+        }
+        if self.start_number.is_none() {
+            self.start_number = parent.start_number;
+        }
+        if self.timescale.is_none() {
+            self.timescale = parent.timescale;
+        }
+        if self.duration.is_none() {
+            self.duration = parent.duration;
+        }
+        if self.timeline.is_empty() {
+            self.timeline = parent.timeline.clone();
+        }
+    }
+}
+
+/// A single encoding of a DASH adaptation set
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Representation {
+    pub id: String,
+    pub content_type: DashContentType,
+    pub mime_type: String,
+    pub codecs: String,
+    pub bandwidth: u64,
+    pub width: u32,
+    pub height: u32,
+    /// Absolute URL which segment URLs are relative to
+    pub base_url: String,
+    pub template: SegmentTemplate,
+}
+
+impl Representation {
+    /// Number of segments behind the live edge where live playback starts
+    const LIVE_EDGE_SEGMENTS: u64 = 3;
+
+    /// Substitute `$..$` identifiers in a segment template
+    fn expand(&self, template: &str, number: u64, time: u64) -> String {
+        let mut out = String::new();
+        for (i, part) in template.split('$').enumerate() {
+            if i % 2 == 0 {
+                out.push_str(part);
+                continue;
+            }
+            let (id, fmt) = part.split_once('%').unwrap_or((part, ""));
+            let value = match id {
+                "" => {
+                    out.push('$');
+                    continue;
+                }
+                "RepresentationID" => {
+                    out.push_str(&self.id);
+                    continue;
+                }
+                "Number" => number,
+                "Time" => time,
+                "Bandwidth" => self.bandwidth,
+                _ => {
+                    out.push('$');
+                    out.push_str(part);
+                    out.push('$');
+                    continue;
+                }
+            };
+            // printf style width, e.g. %05d
+            let width = fmt
+                .trim_start_matches('0')
+                .trim_end_matches('d')
+                .parse::<usize>()
+                .unwrap_or(0);
+            out.push_str(&format!("{:0width$}", value, width = width));
+        }
+        out
+    }
+
+    fn resolve(&self, template: &str, number: u64, time: u64) -> Result<String> {
+        let u: Url = self.base_url.parse()?;
+        Ok(u.join(&self.expand(template, number, time))?.to_string())
+    }
+
+    /// URL of the initialization segment
+    pub fn init_url(&self) -> Result<Option<String>> {
+        match &self.template.initialization {
+            Some(i) => Ok(Some(self.resolve(i, 0, 0)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Media segment URLs and their durations (seconds) for a presentation of `duration` seconds
+    pub fn segments(&self, duration: f64) -> Result<Vec<(String, f64)>> {
+        let t = &self.template;
+        let Some(media) = &t.media else {
+            bail!("Representation {} has no SegmentTemplate", self.id);
+        };
+        let timescale = t.timescale.unwrap_or(1).max(1);
+        let start_number = t.start_number.unwrap_or(1);
+        let mut ret = Vec::new();
+        if !t.timeline.is_empty() {
+            let mut number = start_number;
+            let mut time = 0;
+            for (i, &(start, d, r)) in t.timeline.iter().enumerate() {
+                if d == 0 {
+                    continue;
+                }
+                if let Some(s) = start {
+                    time = s;
+                }
+                let repeat = if r < 0 {
+                    // repeat until the next entry or the end of the presentation
+                    let end = t
+                        .timeline
+                        .get(i + 1)
+                        .and_then(|n| n.0)
+                        .unwrap_or((duration * timescale as f64) as u64);
+                    end.saturating_sub(time).div_ceil(d).saturating_sub(1)
+                } else {
+                    r as u64
+                };
+                for _ in 0..=repeat {
+                    ret.push((
+                        self.resolve(media, number, time)?,
+                        d as f64 / timescale as f64,
+                    ));
+                    time += d;
+                    number += 1;
+                }
+            }
+        } else if let Some(d) = t.duration.filter(|d| *d > 0) {
+            let count = (duration * timescale as f64 / d as f64).ceil() as u64;
+            for n in 0..count {
+                ret.push((
+                    self.resolve(media, start_number + n, n * d)?,
+                    d as f64 / timescale as f64,
+                ));
+            }
+        } else {
+            bail!("Representation {} has no segment duration", self.id);
+        }
+        Ok(ret)
+    }
+
+    /// Media segment URLs and their durations (seconds) of a live presentation using
+    /// `SegmentTemplate@duration`, for the segments available `elapsed` seconds after the
+    /// period started. Only the last [Self::LIVE_EDGE_SEGMENTS] are returned
+    pub fn live_segments(&self, elapsed: f64) -> Result<Vec<(String, f64)>> {
+        let t = &self.template;
+        let (Some(media), Some(d)) = (&t.media, t.duration.filter(|d| *d > 0)) else {
+            bail!("Representation {} has no segment duration", self.id);
+        };
+        let timescale = t.timescale.unwrap_or(1).max(1);
+        let start_number = t.start_number.unwrap_or(1);
+        // a segment is available once it has ended
+        let available = (elapsed.max(0.0) * timescale as f64 / d as f64).floor() as u64;
+        let mut ret = Vec::new();
+        for n in available.saturating_sub(Self::LIVE_EDGE_SEGMENTS)..available {
+            ret.push((
+                self.resolve(media, start_number + n, n * d)?,
+                d as f64 / timescale as f64,
+            ));
+        }
+        Ok(ret)
+    }
+}
+
+/// Parsed MPD manifest
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Mpd {
+    /// Live presentation which must be reloaded for new segments
+    pub dynamic: bool,
+    /// Presentation duration (seconds), 0 when unknown
+    pub duration: f64,
+    /// How often a live manifest should be reloaded (seconds)
+    pub update_period: f64,
+    /// Wall clock time the live presentation started (unix seconds)
+    pub availability_start_time: Option<f64>,
+    /// Start of the period relative to [Self::availability_start_time] (seconds)
+    pub period_start: f64,
+    pub representations: Vec<Representation>,
+}
+
+impl Mpd {
+    /// Parse an MPD manifest loaded from `url`
+    pub fn parse(xml: &str, url: &str) -> Result<Self> {
+        let mut reader = Reader::from_str(xml);
+        reader.config_mut().trim_text(true);
+
+        let mut mpd = Mpd::default();
+        // base URL of each open element, BaseURL children replace the last entry
+        let mut base = vec![url.to_string()];
+        let mut in_base_url = false;
+        let mut base_text = String::new();
+        let mut adaptation_type = DashContentType::Other;
+        let mut adaptation_mime = String::new();
+        let mut adaptation_template = SegmentTemplate::default();
+        let mut rep: Option<Representation> = None;
+
+        loop {
+            let (e, empty) = match reader.read_event()? {
+                Event::Eof => break,
+                Event::Start(e) => (e, false),
+                Event::Empty(e) => (e, true),
+                Event::Text(t) if in_base_url => {
+                    base_text.push_str(&t.decode()?);
+                    continue;
+                }
+                Event::GeneralRef(r) if in_base_url => {
+                    match r.decode()?.as_ref() {
+                        "amp" => base_text.push('&'),
+                        "lt" => base_text.push('<'),
+                        "gt" => base_text.push('>'),
+                        "quot" => base_text.push('"'),
+                        "apos" => base_text.push('\''),
+                        _ => {
+                            if let Some(c) = r.resolve_char_ref()? {
+                                base_text.push(c);
+                            }
+                        }
+                    }
+                    continue;
+                }
+                Event::End(e) => {
+                    match e.local_name().as_ref() {
+                        b"BaseURL" => {
+                            in_base_url = false;
+                            if let Some(last) = base.last_mut() {
+                                let u: Url = last.parse()?;
+                                *last = u.join(base_text.trim())?.to_string();
+                            }
+                        }
+                        b"Representation" => {
+                            if let Some(r) = rep.take() {
+                                mpd.push_representation(
+                                    r,
+                                    base.last().cloned().unwrap_or_default(),
+                                    adaptation_type,
+                                    &adaptation_mime,
+                                    &adaptation_template,
+                                );
+                            }
+                            base.pop();
+                        }
+                        b"MPD" | b"Period" | b"AdaptationSet" => {
+                            base.pop();
+                        }
+                        _ => {}
+                    }
+                    continue;
+                }
+                _ => continue,
+            };
+
+            let attrs = attributes(&e)?;
+            match e.local_name().as_ref() {
+                b"MPD" => {
+                    mpd.dynamic = attrs.get("type").is_some_and(|t| t == "dynamic");
+                    mpd.duration = attrs
+                        .get("mediaPresentationDuration")
+                        .and_then(|d| parse_duration(d))
+                        .unwrap_or(0.0);
+                    mpd.update_period = attrs
+                        .get("minimumUpdatePeriod")
+                        .and_then(|d| parse_duration(d))
+                        .unwrap_or(0.0);
+                    mpd.availability_start_time = attrs
+                        .get("availabilityStartTime")
+                        .and_then(|t| parse_date_time(t));
+                }
+                b"Period" => {
+                    mpd.period_start = attrs
+                        .get("start")
+                        .and_then(|d| parse_duration(d))
+                        .unwrap_or(0.0);
+                    if mpd.duration == 0.0
+                        && let Some(d) = attrs.get("duration").and_then(|d| parse_duration(d))
+                    {
+                        mpd.duration = d;
+                    }
+                }
+                b"AdaptationSet" => {
+                    adaptation_mime = attrs.get("mimeType").cloned().unwrap_or_default();
+                    adaptation_type = match attrs.get("contentType").map(String::as_str) {
+                        Some("video") => DashContentType::Video,
+                        Some("audio") => DashContentType::Audio,
+                        _ => DashContentType::from_mime(&adaptation_mime),
+                    };
+                    adaptation_template = SegmentTemplate::default();
+                }
+                b"Representation" => {
+                    let r = Representation {
+                        id: attrs.get("id").cloned().unwrap_or_default(),
+                        mime_type: attrs.get("mimeType").cloned().unwrap_or_default(),
+                        codecs: attrs.get("codecs").cloned().unwrap_or_default(),
+                        bandwidth: attrs
+                            .get("bandwidth")
+                            .and_then(|v| v.parse().ok())
+                            .unwrap_or(0),
+                        width: attrs.get("width").and_then(|v| v.parse().ok()).unwrap_or(0),
+                        height: attrs
+                            .get("height")
+                            .and_then(|v| v.parse().ok())
+                            .unwrap_or(0),
+                        ..Default::default()
+                    };
+                    if empty {
+                        mpd.push_representation(
+                            r,
+                            base.last().cloned().unwrap_or_default(),
+                            adaptation_type,
+                            &adaptation_mime,
+                            &adaptation_template,
+                        );
+                    } else {
+                        rep = Some(r);
+                    }
+                }
+                b"SegmentTemplate" => {
+                    let t = SegmentTemplate::from_element(&e)?;
+                    match rep.as_mut() {
+                        Some(r) => r.template = t,
+                        None => adaptation_template = t,
+                    }
+                }
+                b"S" => {
+                    let entry = (
+                        attrs.get("t").and_then(|v| v.parse().ok()),
+                        attrs.get("d").and_then(|v| v.parse().ok()).unwrap_or(0),
+                        attrs.get("r").and_then(|v| v.parse().ok()).unwrap_or(0),
+                    );
+                    match rep.as_mut() {
+                        Some(r) => r.template.timeline.push(entry),
+                        None => adaptation_template.timeline.push(entry),
+                    }
+                }
+                b"BaseURL" if !empty => {
+                    in_base_url = true;
+                    base_text.clear();
+                }
+                _ => {}
+            }
+            // elements which can have a BaseURL child start with the parent base URL
+            if !empty
+                && matches!(
+                    e.local_name().as_ref(),
+                    b"MPD" | b"Period" | b"AdaptationSet" | b"Representation"
+                )
+            {
+                base.push(base.last().cloned().unwrap_or_default());
+            }
+        }
+        Ok(mpd)
+    }
+
+    fn push_representation(
+        &mut self,
+        mut rep: Representation,
+        base_url: String,
+        content_type: DashContentType,
+        mime_type: &str,
+        template: &SegmentTemplate,
+    ) {
+        if rep.mime_type.is_empty() {
+            rep.mime_type = mime_type.to_string();
+        }
+        rep.content_type = match content_type {
+            DashContentType::Other => DashContentType::from_mime(&rep.mime_type),
+            t => t,
+        };
+        rep.base_url = base_url;
+        rep.template.inherit(template);
+        self.representations.push(rep);
+    }
+}
+
+/// Unescaped attributes of an element
+fn attributes(e: &BytesStart) -> Result<HashMap<String, String>> {
+    let mut ret = HashMap::new();
+    for a in e.attributes() {
+        let a = a?;
+        ret.insert(
+            String::from_utf8_lossy(a.key.local_name().as_ref()).to_string(),
+            a.normalized_value(XmlVersion::Implicit1_0)?.to_string(),
+        );
+    }
+    Ok(ret)
+}
+
+/// Parse an ISO 8601 duration (`PT1H2M3.5S`) into seconds
+fn parse_duration(s: &str) -> Option<f64> {
+    let s = s.strip_prefix('P')?;
+    let (date, time) = s.split_once('T').unwrap_or((s, ""));
+    let mut secs = 0.0;
+    let mut num = String::new();
+    for (part, is_time) in [(date, false), (time, true)] {
+        for c in part.chars() {
+            if c.is_ascii_digit() || c == '.' {
+                num.push(c);
+                continue;
+            }
+            let v: f64 = num.parse().ok()?;
+            num.clear();
+            secs += v * match (c, is_time) {
+                ('Y', false) => 365.0 * 86400.0,
+                ('M', false) => 30.0 * 86400.0,
+                ('W', false) => 7.0 * 86400.0,
+                ('D', false) => 86400.0,
+                ('H', true) => 3600.0,
+                ('M', true) => 60.0,
+                ('S', true) => 1.0,
+                _ => return None,
+            };
+        }
+    }
+    num.is_empty().then_some(secs)
+}
+
+/// Parse an ISO 8601 date time (`2024-01-02T03:04:05.5Z`) into unix seconds,
+/// UTC when there is no offset
+fn parse_date_time(s: &str) -> Option<f64> {
+    let (date, time) = s.split_once('T')?;
+    let mut date = date.splitn(3, '-').map(|v| v.parse::<i64>().ok());
+    let (y, m, d) = (date.next()??, date.next()??, date.next()??);
+    let (time, offset) = match time.find(['Z', '+', '-']) {
+        Some(i) => {
+            let (time, zone) = time.split_at(i);
+            let offset = match zone.strip_prefix('Z') {
+                Some(_) => 0,
+                None => {
+                    let hm = zone[1..].replace(':', "");
+                    let h = hm.get(..2)?.parse::<i64>().ok()?;
+                    let m = match hm.get(2..)? {
+                        "" => 0,
+                        m => m.parse::<i64>().ok()?,
+                    };
+                    let secs = h * 3600 + m * 60;
+                    if zone.starts_with('-') { -secs } else { secs }
+                }
+            };
+            (time, offset)
+        }
+        None => (time, 0),
+    };
+    let mut time = time.splitn(3, ':');
+    let (hh, mm, ss) = (
+        time.next()?.parse::<i64>().ok()?,
+        time.next()?.parse::<i64>().ok()?,
+        time.next()?.parse::<f64>().ok()?,
+    );
+    // days since 1970-01-01 of a proleptic gregorian date
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * ((m + 9) % 12) + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+    Some((days * 86400 + hh * 3600 + mm * 60 - offset) as f64 + ss)
+}
+
+/// If `url` points to an MPD manifest, by its `.mpd` extension or a `format=mpd` manifest
+/// path (e.g. `/video.ism/manifest(format=mpd-time-csf)`). This runs when the input is opened
+/// so it never makes a request, other inputs are left to ffmpeg to probe
+pub(crate) fn is_dash(url: &str) -> bool {
+    let path = match Url::parse(url) {
+        Ok(u) => u.path().to_lowercase(),
+        Err(_) => url.to_lowercase(),
+    };
+    path.ends_with(".mpd") || path.contains("format=mpd")
+}
+
+pub struct DashStream {
+    url: String,
+    mpd: Option<Mpd>,
+    current_representation: Option<Representation>,
+    headers: Headers,
+}
+
+impl DashStream {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            mpd: None,
+            current_representation: None,
+            headers: Default::default(),
+        }
+    }
+
+    /// Replace the HTTP headers sent with every manifest and segment request
+    pub fn set_headers(&mut self, headers: HashMap<String, String>) {
+        if let Ok(mut h) = self.headers.lock() {
+            *h = headers;
+        }
+    }
+
+    /// Add a HTTP header sent with every manifest and segment request
+    pub fn set_header(&mut self, key: &str, value: &str) {
+        if let Ok(mut h) = self.headers.lock() {
+            h.insert(key.to_string(), value.to_string());
+        }
+    }
+
+    pub fn load(&mut self) -> Result<()> {
+        let xml = http_get(&self.url, &self.headers)?
+            .body_mut()
+            .read_to_string()?;
+        self.mpd = Some(Mpd::parse(&xml, &self.url)?);
+        Ok(())
+    }
+
+    /// Return all representations from the manifest
+    pub fn representations(&self) -> Vec<Representation> {
+        self.mpd
+            .as_ref()
+            .map(|m| m.representations.clone())
+            .unwrap_or_default()
+    }
+
+    /// Select the highest bandwidth representation of a content type
+    pub fn auto_representation(&self, kind: DashContentType) -> Option<Representation> {
+        self.representations()
+            .into_iter()
+            .filter(|r| r.content_type == kind)
+            .sorted_by(|a, b| b.bandwidth.cmp(&a.bandwidth))
+            .next()
+    }
+
+    pub fn set_representation(&mut self, rep: Representation) {
+        self.current_representation = Some(rep);
+    }
+
+    pub fn current_representation(&self) -> Option<Representation> {
+        self.current_representation
+            .clone()
+            .or_else(|| self.auto_representation(DashContentType::Video))
+            .or_else(|| self.representations().into_iter().next())
+    }
+
+    /// Create a reader for the current representation
+    pub fn reader(&self) -> Result<DashVariantReader> {
+        let Some(mpd) = &self.mpd else {
+            bail!("Manifest not loaded");
+        };
+        let Some(rep) = self.current_representation() else {
+            bail!("No representations found");
+        };
+        if rep.content_type == DashContentType::Video
+            && self
+                .representations()
+                .iter()
+                .any(|r| r.content_type == DashContentType::Audio)
+        {
+            warn!("Separate DASH audio adaptation sets are not supported, playing video only");
+        }
+        info!(
+            "Using DASH representation {} ({}, {}bps)",
+            rep.id, rep.codecs, rep.bandwidth
+        );
+        DashVariantReader::new(&self.url, mpd, rep, self.headers.clone())
+    }
+}
+
+/// [Read] implementation which loads the init segment then each media segment of a representation
+pub struct DashVariantReader {
+    url: String,
+    representation: Representation,
+    dynamic: bool,
+    update_period: f64,
+    /// Segments which are not loaded yet
+    queue: VecDeque<String>,
+    /// Segments which were already loaded (live only)
+    prev: HashSet<String>,
+    /// Internal buffer of stream data
+    buffer: Vec<u8>,
+    /// HTTP headers shared with [DashStream]
+    headers: Headers,
+}
+
+impl DashVariantReader {
+    fn new(url: &str, mpd: &Mpd, representation: Representation, headers: Headers) -> Result<Self> {
+        let mut ret = Self {
+            url: url.to_string(),
+            representation,
+            dynamic: mpd.dynamic,
+            update_period: mpd.update_period,
+            queue: VecDeque::new(),
+            prev: HashSet::new(),
+            buffer: Vec::new(),
+            headers,
+        };
+        if let Some(init) = ret.representation.init_url()? {
+            ret.queue.push_back(init);
+        }
+        ret.queue_segments(mpd)?;
+        Ok(ret)
+    }
+
+    fn queue_segments(&mut self, mpd: &Mpd) -> Result<()> {
+        let t = &self.representation.template;
+        let segments = match mpd.availability_start_time {
+            // live numbered segments, the manifest doesn't list them
+            Some(start) if self.dynamic && t.timeline.is_empty() && mpd.duration == 0.0 => {
+                let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs_f64();
+                self.representation
+                    .live_segments(now - start - mpd.period_start)?
+            }
+            _ => self.representation.segments(mpd.duration)?,
+        };
+        for (u, _) in segments {
+            if self.dynamic && !self.prev.insert(u.clone()) {
+                continue;
+            }
+            self.queue.push_back(u);
+        }
+        Ok(())
+    }
+
+    /// Reload a live manifest and queue any new segments
+    fn reload(&mut self) -> Result<()> {
+        let xml = http_get(&self.url, &self.headers)?
+            .body_mut()
+            .read_to_string()?;
+        let mpd = Mpd::parse(&xml, &self.url)?;
+        if let Some(rep) = mpd
+            .representations
+            .iter()
+            .find(|r| r.id == self.representation.id)
+        {
+            self.representation = rep.clone();
+        }
+        self.queue_segments(&mpd)
+    }
+
+    pub fn read_next_segment(&mut self) -> Result<Option<Vec<u8>>> {
+        if self.queue.is_empty() && self.dynamic {
+            self.reload()?;
+        }
+        if let Some(u) = self.queue.pop_front() {
+            info!("Loading segment: {}", &u);
+            let mut data = Vec::new();
+            http_get(&u, &self.headers)?
+                .into_body()
+                .into_reader()
+                .read_to_end(&mut data)?;
+            Ok(Some(data))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl Read for DashVariantReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.buffer.is_empty() {
+            match self.read_next_segment().map_err(std::io::Error::other)? {
+                Some(data) => self.buffer.extend(data),
+                None if self.dynamic => {
+                    std::thread::sleep(Duration::from_secs_f64(self.update_period.clamp(0.1, 2.0)))
+                }
+                // end of a static presentation
+                None => return Ok(0),
+            }
+        }
+        let cpy = buf.len().min(self.buffer.len());
+        buf[..cpy].copy_from_slice(&self.buffer[..cpy]);
+        self.buffer.drain(..cpy);
+        Ok(cpy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MPD: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" type="static" mediaPresentationDuration="PT10.5S">
+  <BaseURL>media/</BaseURL>
+  <Period>
+    <AdaptationSet contentType="video" mimeType="video/mp4">
+      <SegmentTemplate initialization="$RepresentationID$/init.mp4" media="$RepresentationID$/seg-$Number%05d$.m4s" startNumber="1" timescale="1000" duration="4000"/>
+      <Representation id="720p" codecs="avc1.64001f" bandwidth="3000000" width="1280" height="720"/>
+      <Representation id="1080p" codecs="avc1.640028" bandwidth="6000000" width="1920" height="1080"/>
+    </AdaptationSet>
+    <AdaptationSet mimeType="audio/mp4">
+      <Representation id="aac" codecs="mp4a.40.2" bandwidth="128000">
+        <BaseURL>audio/</BaseURL>
+        <SegmentTemplate initialization="init.mp4" media="$Time$.m4s?a=1&amp;b=$Bandwidth$" timescale="48000">
+          <SegmentTimeline>
+            <S t="0" d="96000" r="1"/>
+            <S d="48000" r="-1"/>
+          </SegmentTimeline>
+        </SegmentTemplate>
+      </Representation>
+    </AdaptationSet>
+  </Period>
+</MPD>"#;
+
+    #[test]
+    fn duration() {
+        assert_eq!(parse_duration("PT10.5S"), Some(10.5));
+        assert_eq!(parse_duration("PT1H2M3S"), Some(3723.0));
+        assert_eq!(parse_duration("P1DT1M"), Some(86460.0));
+        assert_eq!(parse_duration("PT1X"), None);
+        assert_eq!(parse_duration("10"), None);
+    }
+
+    #[test]
+    fn parse_mpd() -> Result<()> {
+        let mpd = Mpd::parse(MPD, "https://example.com/live/stream.mpd")?;
+        assert!(!mpd.dynamic);
+        assert_eq!(mpd.duration, 10.5);
+        assert_eq!(mpd.representations.len(), 3);
+
+        let v = &mpd.representations[1];
+        assert_eq!(v.content_type, DashContentType::Video);
+        assert_eq!(v.mime_type, "video/mp4");
+        assert_eq!((v.width, v.height), (1920, 1080));
+        assert_eq!(v.base_url, "https://example.com/live/media/");
+        assert_eq!(
+            v.init_url()?.as_deref(),
+            Some("https://example.com/live/media/1080p/init.mp4")
+        );
+        let segs = v.segments(mpd.duration)?;
+        assert_eq!(segs.len(), 3);
+        assert_eq!(
+            segs[2],
+            (
+                "https://example.com/live/media/1080p/seg-00003.m4s".to_string(),
+                4.0
+            )
+        );
+
+        let a = &mpd.representations[2];
+        assert_eq!(a.content_type, DashContentType::Audio);
+        assert_eq!(a.base_url, "https://example.com/live/media/audio/");
+        let segs = a.segments(mpd.duration)?;
+        assert_eq!(
+            segs.iter().map(|s| s.0.as_str()).collect::<Vec<_>>(),
+            vec![
+                "https://example.com/live/media/audio/0.m4s?a=1&b=128000",
+                "https://example.com/live/media/audio/96000.m4s?a=1&b=128000",
+                "https://example.com/live/media/audio/192000.m4s?a=1&b=128000",
+                "https://example.com/live/media/audio/240000.m4s?a=1&b=128000",
+                "https://example.com/live/media/audio/288000.m4s?a=1&b=128000",
+                "https://example.com/live/media/audio/336000.m4s?a=1&b=128000",
+                "https://example.com/live/media/audio/384000.m4s?a=1&b=128000",
+                "https://example.com/live/media/audio/432000.m4s?a=1&b=128000",
+                "https://example.com/live/media/audio/480000.m4s?a=1&b=128000",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn date_time() {
+        assert_eq!(parse_date_time("1970-01-01T00:00:00Z"), Some(0.0));
+        assert_eq!(
+            parse_date_time("2024-01-02T03:04:05.5Z"),
+            Some(1_704_164_645.5)
+        );
+        assert_eq!(
+            parse_date_time("2024-01-02T05:04:05.5+02:00"),
+            Some(1_704_164_645.5)
+        );
+        assert_eq!(
+            parse_date_time("2024-01-01T22:04:05.5-0500"),
+            Some(1_704_164_645.5)
+        );
+        assert_eq!(
+            parse_date_time("2024-01-02T03:04:05"),
+            Some(1_704_164_645.0)
+        );
+        assert_eq!(parse_date_time("2024-01-02"), None);
+    }
+
+    #[test]
+    fn live_numbered_segments() -> Result<()> {
+        let mpd = Mpd::parse(
+            r#"<MPD type="dynamic" availabilityStartTime="2024-01-02T03:04:05Z" minimumUpdatePeriod="PT2S">
+  <Period start="PT10S">
+    <AdaptationSet mimeType="video/mp4">
+      <SegmentTemplate media="seg-$Number$-$Time$.m4s" startNumber="100" timescale="1000" duration="2000"/>
+      <Representation id="v" bandwidth="1000000"/>
+    </AdaptationSet>
+  </Period>
+</MPD>"#,
+            "https://example.com/live.mpd",
+        )?;
+        assert!(mpd.dynamic);
+        assert_eq!(mpd.duration, 0.0);
+        assert_eq!(mpd.availability_start_time, Some(1_704_164_645.0));
+        assert_eq!(mpd.period_start, 10.0);
+
+        let v = &mpd.representations[0];
+        // no segment has ended yet
+        assert!(v.live_segments(1.0)?.is_empty());
+        // 21s in, segments 0..10 ended, the last 3 are returned
+        let segs = v.live_segments(21.0)?;
+        assert_eq!(
+            segs.iter().map(|s| s.0.as_str()).collect::<Vec<_>>(),
+            vec![
+                "https://example.com/seg-107-14000.m4s",
+                "https://example.com/seg-108-16000.m4s",
+                "https://example.com/seg-109-18000.m4s",
+            ]
+        );
+        assert_eq!(segs[0].1, 2.0);
+        Ok(())
+    }
+
+    #[test]
+    fn detect() {
+        assert!(is_dash("https://example.com/live/stream.mpd"));
+        assert!(is_dash("https://example.com/live/STREAM.MPD?token=abc"));
+        assert!(is_dash(
+            "https://example.com/video.ism/manifest(format=mpd-time-csf)"
+        ));
+        assert!(is_dash("/tmp/stream.mpd"));
+        assert!(!is_dash("https://example.com/live/stream.m3u8"));
+        assert!(!is_dash("https://example.com/watch?v=stream.mpd"));
+        assert!(!is_dash("https://example.com/live/stream"));
+    }
+
+    #[test]
+    fn template() {
+        let rep = Representation {
+            id: "v1".to_string(),
+            bandwidth: 500,
+            ..Default::default()
+        };
+        assert_eq!(
+            rep.expand(
+                "$RepresentationID$/$Number%03d$-$Time$-$Bandwidth$$$.mp4",
+                7,
+                90
+            ),
+            "v1/007-90-500$.mp4"
+        );
+        assert_eq!(rep.expand("$Unknown$", 1, 0), "$Unknown$");
+    }
+}
@@ -0,0 +1,57 @@
+use crate::stream::{DecoderInfo, StreamType};
+use std::sync::mpsc::{Receiver, Sender, channel};
+
+/// Notifications sent to [`crate::Player::subscribe`] receivers
+#[derive(Clone, Debug)]
+pub enum PlayerEvent {
+    /// The media was probed, stream info is available
+    MetadataReady(DecoderInfo),
+    /// A different stream was selected
+    TrackChanged { kind: StreamType, index: i32 },
+    /// The first frame at the seek target (seconds) was shown
+    SeekCompleted(f64),
+    /// An error which stopped playback
+    PlaybackError(String),
+    /// Playback reached the end of the stream
+    EndOfStream,
+}
+
+/// Sends each [PlayerEvent] to every subscriber
+#[derive(Default)]
+pub(crate) struct EventBus {
+    subscribers: Vec<Sender<PlayerEvent>>,
+}
+
+impl EventBus {
+    /// Add a new subscriber, events are queued until the receiver is dropped
+    pub fn subscribe(&mut self) -> Receiver<PlayerEvent> {
+        let (tx, rx) = channel();
+        self.subscribers.push(tx);
+        rx
+    }
+
+    /// Send an event to all subscribers, dropping any which are disconnected
+    pub fn emit(&mut self, event: PlayerEvent) {
+        self.subscribers.retain(|s| s.send(event.clone()).is_ok());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn broadcast() {
+        let mut bus = EventBus::default();
+        let a = bus.subscribe();
+        let b = bus.subscribe();
+        bus.emit(PlayerEvent::SeekCompleted(1.5));
+        assert!(matches!(a.try_recv(), Ok(PlayerEvent::SeekCompleted(1.5))));
+        assert!(matches!(b.try_recv(), Ok(PlayerEvent::SeekCompleted(1.5))));
+
+        drop(b);
+        bus.emit(PlayerEvent::EndOfStream);
+        assert_eq!(bus.subscribers.len(), 1);
+        assert!(matches!(a.try_recv(), Ok(PlayerEvent::EndOfStream)));
+    }
+}
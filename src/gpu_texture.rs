@@ -0,0 +1,108 @@
+use egui::{ColorImage, TextureId};
+use egui_wgpu::RenderState;
+use egui_wgpu::wgpu;
+
+/// Video frame texture owned by the player and written with [wgpu::Queue::write_texture],
+/// skipping the egui texture manager which copies every frame on the CPU
+pub(crate) struct GpuFrameTexture {
+    render_state: RenderState,
+    texture: Option<wgpu::Texture>,
+    id: Option<TextureId>,
+}
+
+impl GpuFrameTexture {
+    pub fn new(render_state: RenderState) -> Self {
+        Self {
+            render_state,
+            texture: None,
+            id: None,
+        }
+    }
+
+    /// Texture of the last uploaded frame
+    pub fn id(&self) -> Option<TextureId> {
+        self.id
+    }
+
+    /// Size of the last uploaded frame
+    pub fn size(&self) -> [usize; 2] {
+        self.texture
+            .as_ref()
+            .map(|t| [t.width() as _, t.height() as _])
+            .unwrap_or([0, 0])
+    }
+
+    /// Write a frame to the texture, it is re-created when the frame size changes
+    pub fn upload(&mut self, image: &ColorImage) {
+        let [width, height] = image.size.map(|v| v as u32);
+        if width == 0 || height == 0 {
+            return;
+        }
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        if self.texture.as_ref().is_none_or(|t| t.size() != size) {
+            self.create_texture(size);
+        }
+        let Some(texture) = &self.texture else {
+            return;
+        };
+        self.render_state.queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            image.as_raw(),
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * width),
+                rows_per_image: Some(height),
+            },
+            size,
+        );
+    }
+
+    fn create_texture(&mut self, size: wgpu::Extent3d) {
+        let device = &self.render_state.device;
+        // same format as egui's own textures, which hold Color32 pixels
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("rvp_frame"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[wgpu::TextureFormat::Rgba8Unorm],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let mut renderer = self.render_state.renderer.write();
+        match self.id {
+            Some(id) => renderer.update_egui_texture_from_wgpu_texture(
+                device,
+                &view,
+                wgpu::FilterMode::Linear,
+                id,
+            ),
+            None => {
+                self.id =
+                    Some(renderer.register_native_texture(device, &view, wgpu::FilterMode::Linear));
+            }
+        }
+        if let Some(old) = self.texture.replace(texture) {
+            old.destroy();
+        }
+    }
+}
+
+impl Drop for GpuFrameTexture {
+    fn drop(&mut self) {
+        if let Some(id) = self.id.take() {
+            self.render_state.renderer.write().free_texture(&id);
+        }
+    }
+}
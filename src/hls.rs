@@ -1,32 +1,229 @@
+use crate::http::{Headers, http_get};
 use anyhow::Result;
 use ffmpeg_rs_raw::{AvPacketRef, Demuxer, DemuxerInfo};
 use itertools::Itertools;
 use log::info;
 use m3u8_rs::{MediaPlaylist, MediaPlaylistType, MediaSegment, Playlist, VariantStream};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::io::Read;
-use std::time::Duration;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{Receiver, RecvError, sync_channel};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use url::Url;
 
+/// Download statistics of loaded segments, used for adaptive variant switching
+#[derive(Debug, Default, Clone)]
+pub struct SegmentDownloadMetrics {
+    /// Moving average of download throughput (bits/s)
+    throughput: f64,
+    /// If the last segment took longer to download than its playback duration
+    draining: bool,
+    /// Number of segments measured
+    segments: u64,
+}
+
+impl SegmentDownloadMetrics {
+    /// Weight of the latest sample in the throughput average
+    const EMA_ALPHA: f64 = 0.3;
+
+    /// Record a downloaded segment
+    pub fn record(&mut self, bytes: usize, download_time: Duration, segment_duration: f32) {
+        let secs = download_time.as_secs_f64().max(0.001);
+        let bps = bytes as f64 * 8.0 / secs;
+        self.throughput = if self.segments == 0 {
+            bps
+        } else {
+            Self::EMA_ALPHA * bps + (1.0 - Self::EMA_ALPHA) * self.throughput
+        };
+        self.draining = secs > segment_duration as f64;
+        self.segments += 1;
+    }
+
+    /// Average download throughput (bits/s)
+    pub fn throughput(&self) -> f64 {
+        self.throughput
+    }
+
+    /// If segments are downloading slower than they play back
+    pub fn draining(&self) -> bool {
+        self.draining
+    }
+}
+
+/// Pick the variant to switch to from the download metrics, [None] keeps the current one.
+/// `variants` must be sorted by bandwidth, lowest first
+fn select_variant<'a>(
+    variants: &'a [VariantStream],
+    current: &VariantStream,
+    metrics: &SegmentDownloadMetrics,
+) -> Option<&'a VariantStream> {
+    /// Required throughput headroom before switching up
+    const UP_HEADROOM: f64 = 1.5;
+    /// Fraction of throughput usable when switching down
+    const DOWN_MARGIN: f64 = 0.8;
+
+    if metrics.segments == 0 {
+        return None;
+    }
+    let next = if metrics.draining || metrics.throughput < current.bandwidth as f64 {
+        // highest variant which fits, or the lowest available. Never up, a draining buffer
+        // can still have a high average throughput
+        variants
+            .iter()
+            .rev()
+            .find(|v| {
+                v.bandwidth <= current.bandwidth
+                    && (v.bandwidth as f64) <= metrics.throughput * DOWN_MARGIN
+            })
+            .or(variants.first())
+    } else {
+        variants.iter().rev().find(|v| {
+            v.bandwidth > current.bandwidth
+                && (v.bandwidth as f64) * UP_HEADROOM <= metrics.throughput
+        })
+    };
+    next.filter(|v| v.uri != current.uri)
+}
+
+/// A switched variant restarts at most one segment back, a packet further behind the
+/// previous variant than this (90kHz ticks) follows a discontinuity or timestamp wrap
+const MAX_REPLAY_DTS: i64 = 60 * 90_000;
+
+/// If a packet was already returned by the previous variant. `skip_until` holds the last
+/// DTS per stream from before the switch, a stream is removed once it has caught up
+fn is_replayed(skip_until: &mut HashMap<i32, i64>, stream_index: i32, dts: i64) -> bool {
+    let Some(until) = skip_until.get(&stream_index).copied() else {
+        return false;
+    };
+    if dts <= until && until.saturating_sub(dts) < MAX_REPLAY_DTS {
+        return true;
+    }
+    skip_until.remove(&stream_index);
+    false
+}
+
+/// Load a variant media playlist
+fn load_media_playlist(url: &str, headers: &Headers) -> Result<MediaPlaylist> {
+    let bytes = http_get(url, headers)?.body_mut().read_to_vec()?;
+    let parsed = m3u8_rs::parse_playlist(&bytes);
+    match parsed {
+        Ok((_, playlist)) => match playlist {
+            Playlist::MasterPlaylist(_) => {
+                anyhow::bail!("Unexpected MasterPlaylist response");
+            }
+            Playlist::MediaPlaylist(mp) => Ok(mp),
+        },
+        Err(e) => {
+            anyhow::bail!("{}", e);
+        }
+    }
+}
+
+/// Total duration of the segments in a playlist, [None] for live playlists
+/// which have no fixed start (no `EVENT` / `VOD` type and no `#EXT-X-ENDLIST`)
+fn playlist_duration(playlist: &MediaPlaylist) -> Option<Duration> {
+    if playlist.playlist_type.is_none() && !playlist.end_list {
+        return None;
+    }
+    let secs: f64 = playlist.segments.iter().map(|s| s.duration as f64).sum();
+    Some(Duration::from_secs_f64(secs))
+}
+
+/// Summary of a variant from the master playlist, e.g. for a quality picker
+#[derive(Debug, Clone, PartialEq)]
+pub struct HlsStreamInfo {
+    /// Variant playlist url, select it with [HlsStream::set_variant_uri]
+    pub uri: String,
+    /// Peak bitrate (bits/s)
+    pub bandwidth: u64,
+    /// Video resolution (width, height)
+    pub resolution: Option<(u32, u32)>,
+    /// RFC 6381 codec list, e.g. `avc1.64001f,mp4a.40.2`
+    pub codecs: Option<String>,
+}
+
+impl From<&VariantStream> for HlsStreamInfo {
+    fn from(v: &VariantStream) -> Self {
+        Self {
+            uri: v.uri.clone(),
+            bandwidth: v.bandwidth,
+            resolution: v.resolution.as_ref().map(|r| (r.width as _, r.height as _)),
+            codecs: v.codecs.clone(),
+        }
+    }
+}
+
 pub struct HlsStream {
     url: String,
     playlist: Option<Playlist>,
     current_variant: Option<VariantStream>,
     demuxer_map: HashMap<String, Demuxer>,
+    metrics: Arc<Mutex<SegmentDownloadMetrics>>,
+    /// Media sequence number of the next segment the demuxer reads, a new variant
+    /// continues from here
+    position: Arc<AtomicU64>,
+    /// [Self::position] when switching was last checked
+    checked_position: u64,
+    /// Last DTS returned per stream
+    last_dts: HashMap<i32, i64>,
+    /// [Self::last_dts] at the last variant switch, packets up to here were already
+    /// returned and are skipped until the new variant catches up
+    skip_until: HashMap<i32, i64>,
+    headers: Headers,
+    /// Number of segments downloaded ahead of playback
+    prefetch_segments: usize,
+    /// Duration of the current variant playlist when it was last loaded,
+    /// see [Self::live_stream_duration]
+    variant_duration: Arc<Mutex<Option<Duration>>>,
 }
 
 impl HlsStream {
+    /// Default number of segments downloaded ahead of playback
+    const DEFAULT_PREFETCH_SEGMENTS: usize = 2;
+
     pub fn new(url: &str) -> Self {
         Self {
             url: url.to_string(),
             playlist: None,
             current_variant: None,
             demuxer_map: HashMap::new(),
+            metrics: Arc::new(Mutex::new(SegmentDownloadMetrics::default())),
+            position: Arc::new(AtomicU64::new(0)),
+            checked_position: 0,
+            last_dts: HashMap::new(),
+            skip_until: HashMap::new(),
+            headers: Default::default(),
+            prefetch_segments: Self::DEFAULT_PREFETCH_SEGMENTS,
+            variant_duration: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Set how many segments are downloaded ahead of playback (default 2),
+    /// applies to variants which are opened afterwards
+    pub(crate) fn set_prefetch_segments(&mut self, segments: usize) {
+        self.prefetch_segments = segments.max(1);
+    }
+
+    /// Replace the HTTP headers sent with every playlist and segment request,
+    /// e.g. for token authenticated streams
+    pub(crate) fn set_headers(&mut self, headers: HashMap<String, String>) {
+        if let Ok(mut h) = self.headers.lock() {
+            *h = headers;
+        }
+    }
+
+    /// Add a HTTP header sent with every playlist and segment request
+    pub(crate) fn set_header(&mut self, key: &str, value: &str) {
+        if let Ok(mut h) = self.headers.lock() {
+            h.insert(key.to_string(), value.to_string());
         }
     }
 
     pub fn load(&mut self) -> Result<()> {
-        let bytes = ureq::get(&self.url).call()?.body_mut().read_to_vec()?;
+        let bytes = http_get(&self.url, &self.headers)?
+            .body_mut()
+            .read_to_vec()?;
 
         let parsed = m3u8_rs::parse_playlist(&bytes);
         match parsed {
@@ -40,6 +237,18 @@ impl HlsStream {
         }
     }
 
+    /// Seekable duration of an `EVENT` (DVR) or `VOD` stream, the sum of all segments
+    /// currently in the playlist. Returns [None] for sliding window live streams
+    /// which have an unbounded duration, or if the playlist was not loaded yet.
+    /// For a master playlist this is the variant playlist from its last reload by the
+    /// segment reader, no request is made here.
+    pub(crate) fn live_stream_duration(&self) -> Option<Duration> {
+        match self.playlist.as_ref()? {
+            Playlist::MediaPlaylist(mp) => playlist_duration(mp),
+            Playlist::MasterPlaylist(_) => *self.variant_duration.lock().ok()?,
+        }
+    }
+
     /// Return variants from master playlist
     pub fn variants(&self) -> Vec<VariantStream> {
         if let Some(Playlist::MasterPlaylist(ref pl)) = self.playlist {
@@ -64,9 +273,37 @@ impl HlsStream {
     }
 
     pub fn set_variant(&mut self, var: VariantStream) {
+        // dropping a demuxer stops the prefetch thread of its reader
+        self.demuxer_map.retain(|uri, _| *uri == var.uri);
         self.current_variant = Some(var);
     }
 
+    /// Select the variant with this url, see [HlsStreamInfo::uri].
+    /// Returns false if no variant matches
+    pub fn set_variant_uri(&mut self, uri: &str) -> bool {
+        match self.variants().into_iter().find(|v| v.uri == uri) {
+            Some(v) => {
+                self.set_variant(v);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// List the variants of the master playlist, highest bandwidth first.
+    /// Loads the playlist if it was not loaded yet
+    pub fn probe(&mut self) -> Result<Vec<HlsStreamInfo>> {
+        if self.playlist.is_none() {
+            self.load()?;
+        }
+        Ok(self
+            .variants()
+            .iter()
+            .sorted_by(|a, b| a.bandwidth.cmp(&b.bandwidth).reverse())
+            .map(HlsStreamInfo::from)
+            .collect())
+    }
+
     /// Pick a variant automatically
     pub fn auto_variant(&self) -> Option<VariantStream> {
         self.variants()
@@ -83,10 +320,90 @@ impl HlsStream {
         }
     }
 
+    /// Switch to a lower variant if downloads can't keep up, or to a higher one when
+    /// throughput leaves enough headroom. Returns true if the variant changed.
+    pub fn check_and_switch_variant(&mut self) -> bool {
+        let metrics = match self.metrics.lock() {
+            Ok(m) => m.clone(),
+            Err(_) => return false,
+        };
+        let Some(current) = self.current_variant() else {
+            return false;
+        };
+        let variants = self
+            .variants()
+            .into_iter()
+            .sorted_by_key(|v| v.bandwidth)
+            .collect::<Vec<_>>();
+
+        match select_variant(&variants, &current, &metrics) {
+            Some(v) => {
+                info!(
+                    "Switching variant {} -> {} (throughput={:.0}bps)",
+                    current.bandwidth, v.bandwidth, metrics.throughput
+                );
+                self.set_variant(v.clone());
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Probe the streams of the current variant
+    ///
+    /// # Safety
+    /// Same as [Demuxer::probe_input]
+    pub unsafe fn probe_input(&mut self) -> Result<DemuxerInfo> {
+        let demuxer = self.current_demuxer()?;
+        Ok(unsafe { demuxer.probe_input()? })
+    }
+
+    /// Read the next packet of the current variant.
+    ///
+    /// Each time a new segment is started the download metrics are checked and the variant
+    /// may be switched, the new variant continues from the same segment. When that happens
+    /// the [DemuxerInfo] of the new variant is returned and decoders must be set up again.
+    ///
+    /// # Safety
+    /// Same as [Demuxer::get_packet]
+    pub unsafe fn get_packet(&mut self) -> Result<(Option<AvPacketRef>, Option<DemuxerInfo>)> {
+        let mut info = None;
+        let position = self.position.load(Ordering::Relaxed);
+        if position != self.checked_position {
+            self.checked_position = position;
+            if self.check_and_switch_variant() {
+                self.skip_until = std::mem::take(&mut self.last_dts);
+                info = Some(unsafe { self.probe_input()? });
+            }
+        }
+        loop {
+            let (pkt, _) = unsafe { self.current_demuxer()?.get_packet()? };
+            let Some(pkt) = pkt else {
+                return Ok((None, info));
+            };
+            // segments are reloaded from their start after a switch, skip what was already
+            // returned (variants share the same 90kHz MPEG-TS clock)
+            if is_replayed(&mut self.skip_until, pkt.stream_index, pkt.dts) {
+                continue;
+            }
+            self.last_dts.insert(pkt.stream_index, pkt.dts);
+            return Ok((Some(pkt), info));
+        }
+    }
+
     fn variant_demuxer(&mut self, var: &VariantStream) -> Result<&mut Demuxer> {
         if !self.demuxer_map.contains_key(&var.uri) {
-            let demux =
-                Demuxer::new_custom_io(VariantReader::new(var.clone()), Some(var.uri.clone()))?;
+            let demux = Demuxer::new_custom_io(
+                VariantReader::new(
+                    var.clone(),
+                    self.metrics.clone(),
+                    self.position.clone(),
+                    self.headers.clone(),
+                    self.prefetch_segments,
+                    self.variant_duration.clone(),
+                ),
+                Some(var.uri.clone()),
+            )?;
             self.demuxer_map.insert(var.uri.clone(), demux);
         }
         Ok(self
@@ -105,97 +422,313 @@ impl HlsStream {
     }
 }
 
+/// Reads the segments of a variant as one continuous stream, segments are downloaded
+/// ahead of playback on a background thread so reads don't stall at segment boundaries
 struct VariantReader {
-    /// The type of stream (Live/VOD)
-    kind: MediaPlaylistType,
-    /// The current variant stream
-    variant: VariantStream,
-    /// List of already loaded segments
-    prev: HashMap<String, MediaSegment>,
+    /// Segment fetcher, moved to the prefetch thread on the first read
+    fetcher: Option<SegmentFetcher>,
+    /// Number of downloaded segments which are queued ahead of the reader
+    prefetch_segments: usize,
+    /// Segments downloaded by the prefetch thread, with their media sequence number
+    rx: Option<Receiver<Result<(u64, Vec<u8>)>>>,
+    /// Media sequence number of the next segment, shared with [HlsStream]
+    position: Arc<AtomicU64>,
+    /// Stops the prefetch thread, set when the reader is dropped (e.g. on a variant switch)
+    stop: Arc<AtomicBool>,
     /// Internal buffer of stream data
-    buffer: Vec<u8>,
+    buffer: VecDeque<u8>,
+    /// Media sequence number and unread length of the segments in [Self::buffer]
+    buffered_segments: VecDeque<(u64, usize)>,
 }
 
 impl VariantReader {
-    fn new(variant: VariantStream) -> Self {
+    fn new(
+        variant: VariantStream,
+        metrics: Arc<Mutex<SegmentDownloadMetrics>>,
+        position: Arc<AtomicU64>,
+        headers: Headers,
+        prefetch_segments: usize,
+        duration: Arc<Mutex<Option<Duration>>>,
+    ) -> Self {
+        // a switched variant starts at the segment the previous one was reading
+        let start = position.load(Ordering::Relaxed).saturating_sub(1);
         Self {
-            kind: Default::default(),
-            variant,
-            prev: HashMap::new(),
-            buffer: Vec::new(),
+            fetcher: Some(SegmentFetcher {
+                kind: Default::default(),
+                variant,
+                prev: HashMap::new(),
+                end_list: false,
+                start,
+                metrics,
+                headers,
+                duration,
+            }),
+            prefetch_segments,
+            rx: None,
+            position,
+            stop: Arc::new(AtomicBool::new(false)),
+            buffer: VecDeque::new(),
+            buffered_segments: VecDeque::new(),
         }
     }
 
-    fn load_playlist(&self) -> Result<MediaPlaylist> {
-        let bytes = ureq::get(&self.variant.uri)
-            .call()?
-            .body_mut()
-            .read_to_vec()?;
-        let parsed = m3u8_rs::parse_playlist(&bytes);
-        match parsed {
-            Ok((_, playlist)) => match playlist {
-                Playlist::MasterPlaylist(_) => {
-                    anyhow::bail!("Unexpected MasterPlaylist response");
-                }
-                Playlist::MediaPlaylist(mp) => Ok(mp),
-            },
-            Err(e) => {
-                anyhow::bail!("{}", e);
-            }
+    /// Start the prefetch thread if it is not running yet
+    fn start_prefetch(&mut self) -> Result<()> {
+        if let Some(mut fetcher) = self.fetcher.take() {
+            // the bounded channel blocks the thread once enough segments are queued
+            let (tx, rx) = sync_channel(self.prefetch_segments.max(1));
+            let stop = self.stop.clone();
+            std::thread::Builder::new()
+                .name("hls-prefetch".to_string())
+                .spawn(move || {
+                    while !stop.load(Ordering::Relaxed) {
+                        let next = match fetcher.read_next_segment() {
+                            Ok(Some(data)) => Ok(data),
+                            // every segment of a VOD / ended playlist was loaded
+                            Ok(None) if fetcher.end_list => break,
+                            Ok(None) => {
+                                // wait for a live playlist to get new segments
+                                std::thread::sleep(Duration::from_millis(100));
+                                continue;
+                            }
+                            Err(e) => Err(e),
+                        };
+                        let failed = next.is_err();
+                        // stop when the reader was dropped or loading failed
+                        if tx.send(next).is_err() || failed {
+                            break;
+                        }
+                    }
+                })?;
+            self.rx = Some(rx);
         }
+        Ok(())
+    }
+}
+
+/// Downloads the segments of a variant in playlist order
+struct SegmentFetcher {
+    /// The type of stream (Live/VOD)
+    kind: MediaPlaylistType,
+    /// The current variant stream
+    variant: VariantStream,
+    /// List of already loaded segments
+    prev: HashMap<String, MediaSegment>,
+    /// The playlist has `#EXT-X-ENDLIST`, no segments will be added
+    end_list: bool,
+    /// Media sequence number of the first segment to load
+    start: u64,
+    /// Download statistics shared with [HlsStream]
+    metrics: Arc<Mutex<SegmentDownloadMetrics>>,
+    /// HTTP headers shared with [HlsStream]
+    headers: Headers,
+    /// Playlist duration shared with [HlsStream]
+    duration: Arc<Mutex<Option<Duration>>>,
+}
+
+impl SegmentFetcher {
+    fn load_playlist(&self) -> Result<MediaPlaylist> {
+        load_media_playlist(&self.variant.uri, &self.headers)
     }
 
-    /// Return the next segment which should be loaded
-    fn get_next_segment<'a>(&self, playlist: &'a MediaPlaylist) -> Option<&'a MediaSegment> {
-        for seg in &playlist.segments {
-            if !self.prev.contains_key(&seg.uri) {
-                return Some(seg);
+    /// Return the next segment which should be loaded, with its media sequence number
+    fn get_next_segment<'a>(&self, playlist: &'a MediaPlaylist) -> Option<(u64, &'a MediaSegment)> {
+        for (seq, seg) in (playlist.media_sequence..).zip(&playlist.segments) {
+            if seq >= self.start && !self.prev.contains_key(&seg.uri) {
+                return Some((seq, seg));
             }
         }
         None
     }
 
-    pub fn read_next_segment(&mut self) -> Result<Option<Box<dyn Read>>> {
+    pub fn read_next_segment(&mut self) -> Result<Option<(u64, Vec<u8>)>> {
         let playlist = self.load_playlist()?;
         if let Some(pk) = &playlist.playlist_type {
             self.kind = pk.clone();
         }
+        self.end_list = playlist.end_list;
+        if let Ok(mut d) = self.duration.lock() {
+            *d = playlist_duration(&playlist);
+        }
 
-        if let Some(next_seg) = self.get_next_segment(&playlist) {
+        if let Some((seq, next_seg)) = self.get_next_segment(&playlist) {
             let u: Url = self.variant.uri.parse()?;
 
             let u = u.join(&next_seg.uri)?;
             info!("Loading segment: {}", &u);
-            let req = ureq::get(u.as_ref()).call()?;
+            let start = Instant::now();
+            let req = http_get(u.as_ref(), &self.headers)?;
+            let mut data = Vec::new();
+            req.into_body().into_reader().read_to_end(&mut data)?;
+            if let Ok(mut m) = self.metrics.lock() {
+                m.record(data.len(), start.elapsed(), next_seg.duration);
+            }
 
             self.prev.insert(next_seg.uri.clone(), next_seg.clone());
-            Ok(Some(Box::new(req.into_body().into_reader())))
+            Ok(Some((seq, data)))
         } else {
             Ok(None)
         }
     }
 }
 
+impl Drop for VariantReader {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
 impl Read for VariantReader {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.start_prefetch().map_err(std::io::Error::other)?;
+        let Some(rx) = &self.rx else {
+            return Err(std::io::Error::other("prefetch thread not started"));
+        };
+        // take every segment which is ready, only block when there is nothing to return
         while self.buffer.len() < buf.len() {
-            if let Some(mut s) = self
-                .read_next_segment()
-                .map_err(|e| std::io::Error::other(e))?
-            {
-                let mut buf = Vec::new();
-                let len = s.read_to_end(&mut buf)?;
-                self.buffer.extend(buf[..len].iter().as_slice());
+            let next = if self.buffer.is_empty() {
+                match rx.recv() {
+                    Ok(next) => next,
+                    // the prefetch thread loaded the last segment
+                    Err(RecvError) => break,
+                }
             } else {
-                std::thread::sleep(Duration::from_millis(100));
-            }
+                match rx.try_recv() {
+                    Ok(next) => next,
+                    Err(_) => break,
+                }
+            };
+            let (seq, data) = next.map_err(std::io::Error::other)?;
+            self.buffered_segments.push_back((seq, data.len()));
+            self.buffer.extend(data);
         }
         let cpy = buf.len().min(self.buffer.len());
-        let mut z = 0;
-        for x in self.buffer.drain(..cpy) {
-            buf[z] = x;
-            z += 1;
+        for (dst, src) in buf.iter_mut().zip(self.buffer.drain(..cpy)) {
+            *dst = src;
+        }
+        let mut consumed = cpy;
+        while let Some((seq, len)) = self.buffered_segments.front_mut() {
+            // the segment which is being read now
+            self.position.store(*seq + 1, Ordering::Relaxed);
+            if consumed < *len {
+                *len -= consumed;
+                break;
+            }
+            consumed -= *len;
+            self.buffered_segments.pop_front();
         }
         Ok(cpy)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variant(bandwidth: u64) -> VariantStream {
+        VariantStream {
+            uri: format!("https://example.com/{}.m3u8", bandwidth),
+            bandwidth,
+            ..Default::default()
+        }
+    }
+
+    fn metrics(throughput: f64, draining: bool) -> SegmentDownloadMetrics {
+        SegmentDownloadMetrics {
+            throughput,
+            draining,
+            segments: 1,
+        }
+    }
+
+    #[test]
+    fn throughput_average() {
+        let mut m = SegmentDownloadMetrics::default();
+        // 1MB in 1s = 8Mbit/s, the first sample is taken as is
+        m.record(1_000_000, Duration::from_secs(1), 2.0);
+        assert_eq!(m.throughput(), 8_000_000.0);
+        assert!(!m.draining());
+
+        // 4Mbit/s, 0.3 * 4M + 0.7 * 8M
+        m.record(1_000_000, Duration::from_secs(2), 4.0);
+        assert!((m.throughput() - 6_800_000.0).abs() < 1.0);
+        assert!(!m.draining());
+
+        // downloading took longer than the segment plays
+        m.record(1_000_000, Duration::from_secs(3), 2.0);
+        assert!(m.draining());
+    }
+
+    #[test]
+    fn switch_thresholds() {
+        let variants = [variant(1_000_000), variant(2_000_000), variant(4_000_000)];
+        let mid = &variants[1];
+
+        // nothing measured yet
+        let none = SegmentDownloadMetrics::default();
+        assert_eq!(select_variant(&variants, mid, &none), None);
+
+        // up needs 1.5x headroom: 6Mbit/s for the 4Mbit/s variant
+        assert_eq!(
+            select_variant(&variants, mid, &metrics(5_900_000.0, false)),
+            None
+        );
+        assert_eq!(
+            select_variant(&variants, mid, &metrics(6_000_000.0, false)),
+            Some(&variants[2])
+        );
+
+        // throughput above the current variant but without headroom, stay
+        assert_eq!(
+            select_variant(&variants, mid, &metrics(2_500_000.0, false)),
+            None
+        );
+
+        // down picks the highest variant within 80% of throughput
+        assert_eq!(
+            select_variant(&variants, mid, &metrics(1_900_000.0, false)),
+            Some(&variants[0])
+        );
+        assert_eq!(
+            select_variant(&variants, &variants[2], &metrics(3_000_000.0, false)),
+            Some(&variants[1])
+        );
+
+        // draining switches down even when the average looks fine
+        assert_eq!(
+            select_variant(&variants, mid, &metrics(2_200_000.0, true)),
+            Some(&variants[0])
+        );
+        // but never up, however high the average is
+        assert_eq!(
+            select_variant(&variants, mid, &metrics(10_000_000.0, true)),
+            None
+        );
+        assert_eq!(
+            select_variant(&variants, &variants[2], &metrics(10_000_000.0, true)),
+            None
+        );
+
+        // already on the lowest variant
+        assert_eq!(
+            select_variant(&variants, &variants[0], &metrics(100_000.0, true)),
+            None
+        );
+    }
+
+    #[test]
+    fn replayed_packets() {
+        let mut skip_until = HashMap::from([(0, 900_000), (1, (1 << 33) - 90_000)]);
+        assert!(is_replayed(&mut skip_until, 0, 450_000));
+        assert!(is_replayed(&mut skip_until, 0, 900_000));
+        // caught up, later packets are never skipped
+        assert!(!is_replayed(&mut skip_until, 0, 903_000));
+        assert!(!is_replayed(&mut skip_until, 0, 450_000));
+        // streams without a DTS from before the switch
+        assert!(!is_replayed(&mut skip_until, 2, 0));
+
+        // a discontinuity or wrap restarts far behind, the stream doesn't freeze
+        assert!(!is_replayed(&mut skip_until, 1, 0));
+        assert!(skip_until.is_empty());
+    }
+}
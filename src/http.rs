@@ -0,0 +1,17 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Custom HTTP headers sent with every playlist and segment request
+pub(crate) type Headers = Arc<Mutex<HashMap<String, String>>>;
+
+/// GET request with custom headers
+pub(crate) fn http_get(url: &str, headers: &Headers) -> Result<ureq::http::Response<ureq::Body>> {
+    let mut req = ureq::get(url);
+    if let Ok(headers) = headers.lock() {
+        for (k, v) in headers.iter() {
+            req = req.header(k, v);
+        }
+    }
+    Ok(req.call()?)
+}
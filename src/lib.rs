@@ -17,19 +17,45 @@ use std::sync::atomic::{
     Ordering,
 };
 
+#[cfg(feature = "dash")]
+mod dash;
+mod event;
+pub use event::*;
+#[cfg(feature = "gpu_texture")]
+mod gpu_texture;
 #[cfg(feature = "hls")]
 mod hls;
+#[cfg(any(feature = "hls", feature = "dash"))]
+mod http;
 #[cfg(feature = "default-overlay")]
 mod overlay;
 #[cfg(feature = "default-overlay")]
 pub use overlay::*;
+#[cfg(feature = "default-overlay")]
+mod overlay_chapter;
+#[cfg(feature = "default-overlay")]
+pub use overlay_chapter::*;
+#[cfg(feature = "default-overlay")]
+mod overlay_playlist;
+#[cfg(feature = "default-overlay")]
+pub use overlay_playlist::*;
 mod player;
 pub use player::*;
+mod playlist;
+pub use playlist::*;
 mod state;
 mod stream;
+pub use stream::{
+    ChapterInfo, DecoderInfo, HwDecoderPref, MediaDecoder, MediaMetadata, StreamInfo, StreamType,
+    StreamingOptions, VideoFilters,
+};
 #[cfg(feature = "subtitles")]
 mod subtitle;
+#[cfg(feature = "subtitles")]
+pub use subtitle::SubtitleStyle;
+mod thumbnail;
 pub use state::*;
+pub use thumbnail::*;
 
 /// Simple audio device handle
 pub trait AudioDevice: Send {
@@ -38,12 +64,19 @@ pub trait AudioDevice: Send {
 
     /// Get the sample rate of the device
     fn sample_rate(&self) -> u32;
+
+    /// Time between samples being handed to the device and being heard
+    fn latency(&self) -> std::time::Duration {
+        std::time::Duration::ZERO
+    }
 }
 
 /// A fallback device which just drains the audio stream
 pub(crate) struct NoAudioDevice {
     #[allow(unused)]
     handle: std::thread::JoinHandle<()>,
+    /// Stops the drain thread of a shared receiver, see [NoAudioDevice::new_shared]
+    stop: Arc<AtomicBool>,
 }
 
 impl NoAudioDevice {
@@ -63,7 +96,45 @@ impl NoAudioDevice {
                 }
             })
             .unwrap();
-        Self { handle: h }
+        Self {
+            handle: h,
+            stop: Default::default(),
+        }
+    }
+
+    /// Drain a receiver which is shared with other audio devices, the receiver is
+    /// released when this device is dropped so another device can take over
+    #[cfg(feature = "audio")]
+    pub fn new_shared(
+        rx: Arc<std::sync::Mutex<std::sync::mpsc::Receiver<stream::AudioSamples>>>,
+    ) -> Self {
+        use std::sync::mpsc::RecvTimeoutError;
+        use std::time::Duration;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        let h = std::thread::Builder::new()
+            .name("empty-audio-device".to_owned())
+            .spawn(move || {
+                while !thread_stop.load(Ordering::Relaxed) {
+                    let Ok(rx) = rx.lock() else {
+                        break;
+                    };
+                    if let Err(RecvTimeoutError::Disconnected) =
+                        rx.recv_timeout(Duration::from_millis(100))
+                    {
+                        break;
+                    }
+                }
+            })
+            .unwrap();
+        Self { handle: h, stop }
+    }
+}
+
+impl Drop for NoAudioDevice {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
     }
 }
 
@@ -77,6 +148,18 @@ impl AudioDevice for NoAudioDevice {
     }
 }
 
+/// Format a timestamp as `HH:MM:SS.mmm`
+pub(crate) fn format_time_ms(secs: f64) -> String {
+    let ms = (secs.max(0.0) * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        ms / 3_600_000,
+        (ms / 60_000) % 60,
+        (ms / 1000) % 60,
+        ms % 1000
+    )
+}
+
 pub(crate) fn format_time(secs: f32) -> String {
     const MIN: f32 = 60.0;
     const HR: f32 = MIN * 60.0;
@@ -98,3 +181,16 @@ pub(crate) fn format_time(secs: f32) -> String {
         format!("{:0>2.2}s", secs)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn time_ms() {
+        assert_eq!(format_time_ms(0.0), "00:00:00.000");
+        assert_eq!(format_time_ms(1.234), "00:00:01.234");
+        assert_eq!(format_time_ms(3723.5), "01:02:03.500");
+        assert_eq!(format_time_ms(-1.0), "00:00:00.000");
+    }
+}
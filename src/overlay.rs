@@ -1,19 +1,97 @@
+use crate::overlay_chapter::paint_chapters;
 use crate::stream::StreamType;
-use crate::{PlayerOverlay, PlayerState, SharedPlaybackState, format_time};
+use crate::{PlayerOverlay, PlayerState, SharedPlaybackState, format_time, format_time_ms};
+use egui::load::SizedTexture;
 use egui::{
-    Align2, Color32, CornerRadius, FontId, Rect, Response, Sense, Shadow, Spinner, Ui, Vec2, vec2,
+    Align2, Color32, CornerRadius, EventFilter, FontId, Key, Modifiers, Rect, Response, Sense,
+    Shadow, Spinner, Stroke, Ui, Vec2, WidgetInfo, pos2, vec2,
 };
+use std::sync::atomic::Ordering;
+
+/// Horizontal space left around the seekbar
+pub(crate) const SEEKBAR_WIDTH_OFFSET: f32 = 20.;
+/// Distance between the seekbar and the bottom of the frame
+pub(crate) const SEEKBAR_OFFSET: f32 = 20.;
+/// Height of the seekbar
+pub(crate) const SEEKBAR_HEIGHT: f32 = 3.;
+/// Volume change per arrow key press while the volume slider has focus
+const VOLUME_KEY_STEP: f32 = 0.05;
+
+/// Area of the full seekbar inside a video frame
+pub(crate) fn seekbar_rect(frame: Rect) -> Rect {
+    Rect::from_min_size(
+        frame.left_bottom() + vec2(SEEKBAR_WIDTH_OFFSET / 2., -SEEKBAR_OFFSET),
+        vec2(frame.width() - SEEKBAR_WIDTH_OFFSET, SEEKBAR_HEIGHT),
+    )
+}
+
+/// Width of the played portion of a seekbar
+fn seekbar_progress_width(full_width: f32, elapsed: f64, duration: f64) -> f32 {
+    if duration > 0.0 {
+        full_width * (elapsed / duration).clamp(0.0, 1.0) as f32
+    } else {
+        0.0
+    }
+}
+
+/// Colors and sizes used by [DefaultOverlay]
+#[derive(Debug, Clone, PartialEq)]
+pub struct OverlayTheme {
+    /// Played portion of the seekbar
+    pub seekbar_color: Color32,
+    /// Full length of the seekbar, behind the played portion
+    pub seekbar_bg_color: Color32,
+    /// Icons and text
+    pub text_color: Color32,
+    /// Font size of the icons
+    pub icon_size: f32,
+    /// Height of the seekbar
+    pub seekbar_height: f32,
+}
+
+impl Default for OverlayTheme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+impl OverlayTheme {
+    /// Light controls for dark video, the default theme
+    pub fn dark() -> Self {
+        Self {
+            seekbar_color: Color32::WHITE,
+            seekbar_bg_color: Color32::GRAY,
+            text_color: Color32::WHITE,
+            icon_size: 16.,
+            seekbar_height: SEEKBAR_HEIGHT,
+        }
+    }
+
+    /// Dark controls for bright video
+    pub fn light() -> Self {
+        Self {
+            seekbar_color: Color32::from_gray(30),
+            seekbar_bg_color: Color32::LIGHT_GRAY,
+            text_color: Color32::BLACK,
+            icon_size: 16.,
+            seekbar_height: SEEKBAR_HEIGHT,
+        }
+    }
+}
 
 /// Basic player overlay impl
-pub struct DefaultOverlay;
+#[derive(Default)]
+pub struct DefaultOverlay(pub OverlayTheme);
 
 impl PlayerOverlay for DefaultOverlay {
     fn show(&self, ui: &mut Ui, frame_response: &Response, p: &SharedPlaybackState) {
+        let theme = &self.0;
         let hovered = ui.rect_contains_pointer(frame_response.rect);
         let state = p.state();
         let currently_seeking = matches!(state, PlayerState::Seeking);
         let is_stopped = matches!(state, PlayerState::Stopped);
         let is_paused = matches!(state, PlayerState::Paused);
+        let is_buffering = matches!(state, PlayerState::Buffering);
         let animation_time = 0.2;
         let seekbar_anim_frac = ui.ctx().animate_bool_with_time(
             frame_response.id.with("seekbar_anim"),
@@ -21,24 +99,41 @@ impl PlayerOverlay for DefaultOverlay {
             animation_time,
         );
 
+        if !p.ready() || is_buffering {
+            let spinner_size = 20.;
+            let center = frame_response.rect.center();
+            ui.put(
+                Rect::from_center_size(center, Vec2::splat(spinner_size)),
+                Spinner::new().size(spinner_size),
+            );
+            ui.painter().text(
+                center + vec2(0., spinner_size),
+                Align2::CENTER_TOP,
+                if p.ready() {
+                    "Buffering…"
+                } else {
+                    "Loading…"
+                },
+                FontId::proportional(14.),
+                theme.text_color,
+            );
+        }
+
+        self.show_context_menu(frame_response, p);
+
         if seekbar_anim_frac <= 0. {
             return;
         }
 
-        let seekbar_width_offset = 20.;
+        let seekbar_width_offset = SEEKBAR_WIDTH_OFFSET;
         let fullseekbar_width = frame_response.rect.width() - seekbar_width_offset;
 
-        let seekbar_width = fullseekbar_width
-            * if p.duration() != 0.0 {
-                (p.video_pts() / p.duration()).max(1.0) as _
-            } else {
-                0.0
-            };
+        let seekbar_width = seekbar_progress_width(fullseekbar_width, p.elapsed(), p.duration());
 
-        let seekbar_offset = 20.;
+        let seekbar_offset = SEEKBAR_OFFSET;
         let seekbar_pos =
             frame_response.rect.left_bottom() + vec2(seekbar_width_offset / 2., -seekbar_offset);
-        let seekbar_height = 3.;
+        let seekbar_height = theme.seekbar_height;
         let mut fullseekbar_rect =
             Rect::from_min_size(seekbar_pos, vec2(fullseekbar_width, seekbar_height));
 
@@ -71,7 +166,8 @@ impl PlayerOverlay for DefaultOverlay {
             animation_time,
         );
 
-        if currently_seeking {
+        // the loading spinner is already shown while the media is probed
+        if currently_seeking && p.ready() {
             let seek_indicator_shadow = Shadow {
                 offset: [10, 20],
                 blur: 15,
@@ -104,11 +200,13 @@ impl PlayerOverlay for DefaultOverlay {
                     if is_stopped {
                         p.set_state(PlayerState::Playing);
                     }
-                    //p_ret.set_seek.replace(seek_frac);
+                    p.request_seek_fraction(seek_frac as _);
                 }
             }
         }
-        let text_color = Color32::WHITE.linear_multiply(seekbar_anim_frac);
+        // global overlay opacity set on the player, on top of the show/hide animation
+        let opacity = p.overlay_opacity() * seekbar_anim_frac;
+        let text_color = theme.text_color.linear_multiply(opacity);
         let volume = p.volume();
         let pause_icon = if is_paused {
             "▶"
@@ -130,7 +228,7 @@ impl PlayerOverlay for DefaultOverlay {
         };
 
         let mut icon_font_id = FontId::default();
-        icon_font_id.size = 16.;
+        icon_font_id.size = theme.icon_size;
 
         let subtitle_icon = "💬";
         let stream_icon = "🔁";
@@ -140,6 +238,7 @@ impl PlayerOverlay for DefaultOverlay {
         let sound_icon_pos = fullseekbar_rect.right_top() + sound_icon_offset;
 
         let stream_index_icon_offset = vec2(-30., text_y_offset + 1.);
+        let aspect_icon_offset = vec2(-55., text_y_offset);
         let stream_icon_pos = fullseekbar_rect.right_top() + stream_index_icon_offset;
 
         let contraster_alpha: u8 = 100;
@@ -161,8 +260,8 @@ impl PlayerOverlay for DefaultOverlay {
         let mut shadow_rect = frame_response.rect;
         shadow_rect.set_top(shadow_rect.bottom() - seekbar_offset - 10.);
 
-        let fullseekbar_color = Color32::GRAY.linear_multiply(seekbar_anim_frac);
-        let seekbar_color = Color32::WHITE.linear_multiply(seekbar_anim_frac);
+        let fullseekbar_color = theme.seekbar_bg_color.linear_multiply(opacity);
+        let seekbar_color = theme.seekbar_color.linear_multiply(opacity);
 
         ui.painter()
             .add(shadow.as_shape(shadow_rect, CornerRadius::ZERO));
@@ -172,8 +271,48 @@ impl PlayerOverlay for DefaultOverlay {
             CornerRadius::ZERO,
             fullseekbar_color.linear_multiply(0.5),
         );
+        // buffered range ahead of the playback position
+        let buffered = p.buffered_duration();
+        if buffered > 0.0 {
+            let mut buffered_rect = fullseekbar_rect;
+            buffered_rect.set_right(
+                fullseekbar_rect.left()
+                    + seekbar_progress_width(
+                        fullseekbar_width,
+                        p.elapsed() + buffered,
+                        p.duration(),
+                    ),
+            );
+            ui.painter().rect_filled(
+                buffered_rect,
+                CornerRadius::ZERO,
+                Color32::LIGHT_GRAY.linear_multiply(seekbar_anim_frac * 0.6),
+            );
+        }
         ui.painter()
             .rect_filled(seekbar_rect, CornerRadius::ZERO, seekbar_color);
+
+        // chapter ticks, hovering one shows its title
+        if p.duration() > 0.0 {
+            paint_chapters(ui, fullseekbar_rect, &p.chapters(), p.duration(), opacity);
+        }
+
+        // A/B loop markers
+        if p.duration() > 0.0 {
+            let (loop_a, loop_b) = p.ab_loop();
+            for (pts, color) in [(loop_a, Color32::GREEN), (loop_b, Color32::RED)] {
+                let Some(pts) = pts else {
+                    continue;
+                };
+                let x = fullseekbar_rect.left()
+                    + seekbar_progress_width(fullseekbar_width, pts, p.duration());
+                ui.painter().vline(
+                    x,
+                    fullseekbar_rect.top() - 4.0..=fullseekbar_rect.bottom() + 4.0,
+                    Stroke::new(2.0, color.linear_multiply(seekbar_anim_frac)),
+                );
+            }
+        }
         ui.painter().text(
             pause_icon_pos,
             Align2::LEFT_BOTTOM,
@@ -182,20 +321,21 @@ impl PlayerOverlay for DefaultOverlay {
             text_color,
         );
 
-        if p.video_pts().is_finite() {
+        // the duration is unknown until the media is probed
+        if p.ready() && p.elapsed().is_finite() {
             ui.painter().text(
                 duration_text_pos,
                 Align2::LEFT_BOTTOM,
                 if p.duration() > 0.0 {
                     format!(
                         "{} / {}",
-                        format_time(p.video_pts() as _),
+                        format_time(p.elapsed() as _),
                         format_time(p.duration() as _)
                     )
                 } else {
-                    format_time(p.video_pts() as _)
+                    format_time(p.elapsed() as _)
                 },
-                duration_text_font_id,
+                duration_text_font_id.clone(),
                 text_color,
             );
         }
@@ -208,6 +348,63 @@ impl PlayerOverlay for DefaultOverlay {
             );
         }
 
+        if seekbar_hovered
+            && p.duration() > 0.0
+            && let Some(hover_pos) = seekbar_response.hover_pos()
+        {
+            let hover_frac =
+                ((hover_pos.x - fullseekbar_rect.left()) / fullseekbar_width).clamp(0.0, 1.0);
+            let hover_pts = hover_frac as f64 * p.duration();
+            p.request_preview(hover_pts);
+            // the exact preview frame lags the pointer, use whichever is closer
+            let thumbnail = p
+                .thumbnail_at(hover_pts)
+                .map(|(pts, t)| (pts, SizedTexture::from_handle(&t)));
+            let preview = match (p.preview_frame(), thumbnail) {
+                (Some(f), Some(t)) => Some(if (f.0 - hover_pts).abs() <= (t.0 - hover_pts).abs() {
+                    f
+                } else {
+                    t
+                }),
+                (f, t) => f.or(t),
+            };
+            if let Some((pts, thumbnail)) = preview {
+                let thumbnail_size = thumbnail.size;
+                let label_height = 20.;
+                let thumbnail_rect = Rect::from_center_size(
+                    pos2(
+                        hover_pos.x,
+                        fullseekbar_rect.top() - 10. - label_height - thumbnail_size.y / 2.,
+                    ),
+                    thumbnail_size,
+                );
+                // keep the preview inside the frame
+                let thumbnail_rect = thumbnail_rect.translate(vec2(
+                    (frame_response.rect.left() - thumbnail_rect.left()).max(0.)
+                        + (frame_response.rect.right() - thumbnail_rect.right()).min(0.),
+                    0.,
+                ));
+                ui.painter().rect_filled(
+                    thumbnail_rect.expand(2.),
+                    CornerRadius::same(3),
+                    Color32::from_black_alpha(contraster_alpha),
+                );
+                ui.painter().image(
+                    thumbnail.id,
+                    thumbnail_rect,
+                    Rect::from_min_max(pos2(0., 0.), pos2(1., 1.)),
+                    Color32::WHITE,
+                );
+                ui.painter().text(
+                    thumbnail_rect.center_bottom() + vec2(0., 4.),
+                    Align2::CENTER_TOP,
+                    format_time(pts as _),
+                    duration_text_font_id.clone(),
+                    text_color,
+                );
+            }
+        }
+
         if frame_response.clicked() {
             match p.state() {
                 PlayerState::Stopped | PlayerState::Paused => {
@@ -220,8 +417,10 @@ impl PlayerOverlay for DefaultOverlay {
             }
         }
 
-        let is_subtitle_cyclable = false;
-        let is_audio_cyclable = false;
+        let audio_streams = p.available_audio_streams();
+        let subtitle_streams = p.available_subtitle_streams();
+        let is_audio_cyclable = audio_streams.len() > 1;
+        let is_subtitle_cyclable = !subtitle_streams.is_empty();
 
         if is_audio_cyclable || is_subtitle_cyclable {
             let stream_icon_rect = ui.painter().text(
@@ -241,11 +440,20 @@ impl PlayerOverlay for DefaultOverlay {
                 .memory_mut(|m| *m.data.get_temp_mut_or_default(stream_anim_id));
 
             let mut draw_row = |stream_type: StreamType| {
-                let text = match stream_type {
-                    StreamType::Audio => format!("{} {}/{}", sound_icon, 1, 1),
-                    StreamType::Subtitle => format!("{} {}/{}", subtitle_icon, 1, 1),
+                let (icon, streams, selected) = match stream_type {
+                    StreamType::Audio => (sound_icon, &audio_streams, &p.selected_audio),
+                    StreamType::Subtitle => {
+                        (subtitle_icon, &subtitle_streams, &p.selected_subtitle)
+                    }
                     _ => unreachable!(),
                 };
+                let selected = selected.load(Ordering::Relaxed);
+                let position = streams
+                    .iter()
+                    .position(|s| s.index as isize == selected)
+                    .map(|i| i + 1)
+                    .unwrap_or(0);
+                let text = format!("{} {}/{}", icon, position, streams.len());
 
                 let text_position = cursor - cursor_offset;
                 let text_galley =
@@ -329,6 +537,26 @@ impl PlayerOverlay for DefaultOverlay {
             }
         }
 
+        // fit / stretch toggle
+        let aspect_icon = if p.maintain_aspect() { "▣" } else { "⬌" };
+        let aspect_icon_rect = ui.painter().text(
+            fullseekbar_rect.right_top() + aspect_icon_offset,
+            Align2::RIGHT_BOTTOM,
+            aspect_icon,
+            icon_font_id.clone(),
+            text_color,
+        );
+        if ui
+            .interact(
+                aspect_icon_rect,
+                frame_response.id.with("aspect_icon_sense"),
+                Sense::click(),
+            )
+            .clicked()
+        {
+            p.set_maintain_aspect(!p.maintain_aspect());
+        }
+
         let sound_slider_outer_height = 75.;
 
         let mut sound_slider_rect = sound_icon_rect;
@@ -338,13 +566,15 @@ impl PlayerOverlay for DefaultOverlay {
         let sound_slider_interact_rect = sound_slider_rect.expand(icon_margin);
         let sound_hovered = ui.rect_contains_pointer(sound_icon_rect);
         let sound_slider_hovered = ui.rect_contains_pointer(sound_slider_interact_rect);
+        let sound_slider_id = frame_response.id.with("sound_slider_sense");
+        let sound_slider_focused = ui.memory(|m| m.has_focus(sound_slider_id));
         let sound_anim_id = frame_response.id.with("sound_anim");
         let mut sound_anim_frac: f32 = ui
             .ctx()
             .memory_mut(|m| *m.data.get_temp_mut_or_default(sound_anim_id));
         sound_anim_frac = ui.ctx().animate_bool_with_time(
             sound_anim_id,
-            sound_hovered || (sound_slider_hovered && sound_anim_frac > 0.),
+            sound_hovered || sound_slider_focused || (sound_slider_hovered && sound_anim_frac > 0.),
             0.2,
         );
         ui.ctx()
@@ -364,11 +594,60 @@ impl PlayerOverlay for DefaultOverlay {
 
         ui.painter()
             .rect_filled(sound_bar_rect, CornerRadius::same(5), sound_bar_color);
-        let sound_slider_resp = ui.interact(
-            sound_slider_rect,
-            frame_response.id.with("sound_slider_sense"),
-            Sense::click_and_drag(),
-        );
+
+        // level meters next to the volume slider
+        let (level_l, level_r) = p.audio_level();
+        for (i, level) in [level_l, level_r].into_iter().enumerate() {
+            let meter_rect = Rect::from_min_size(
+                sound_slider_rect.right_top() + vec2(2. + i as f32 * 4., 0.),
+                vec2(3., sound_slider_rect.height()),
+            );
+            let mut level_rect = meter_rect;
+            level_rect.set_top(meter_rect.bottom() - meter_rect.height() * level);
+            let level_color = if level > 0.9 {
+                Color32::RED
+            } else {
+                Color32::GREEN
+            };
+            ui.painter()
+                .rect_filled(meter_rect, CornerRadius::ZERO, sound_slider_bg_color);
+            ui.painter().rect_filled(
+                level_rect,
+                CornerRadius::ZERO,
+                level_color.linear_multiply(sound_anim_frac),
+            );
+        }
+        let sound_slider_resp =
+            ui.interact(sound_slider_rect, sound_slider_id, Sense::click_and_drag());
+        sound_slider_resp
+            .widget_info(|| WidgetInfo::slider(true, (volume * 100.).round() as f64, "Volume"));
+        if sound_slider_resp.clicked() {
+            sound_slider_resp.request_focus();
+        }
+        if sound_slider_resp.has_focus() {
+            // keep the arrow keys for the slider instead of moving focus or seeking
+            ui.memory_mut(|m| {
+                m.set_focus_lock_filter(
+                    sound_slider_id,
+                    EventFilter {
+                        vertical_arrows: true,
+                        ..Default::default()
+                    },
+                )
+            });
+            let (up, down) = ui.input_mut(|i| {
+                (
+                    i.consume_key(Modifiers::NONE, Key::ArrowUp),
+                    i.consume_key(Modifiers::NONE, Key::ArrowDown),
+                )
+            });
+            if up {
+                p.incr_volume(VOLUME_KEY_STEP);
+            }
+            if down {
+                p.decr_volume(VOLUME_KEY_STEP);
+            }
+        }
         if sound_anim_frac > 0. && sound_slider_resp.clicked() || sound_slider_resp.dragged() {
             if let Some(hover_pos) = ui.ctx().input(|i| i.pointer.hover_pos()) {
                 let sound_frac = 1.
@@ -379,3 +658,96 @@ impl PlayerOverlay for DefaultOverlay {
         }
     }
 }
+
+impl DefaultOverlay {
+    /// Right-click menu with common playback actions, unless the player has its own
+    fn show_context_menu(&self, frame_response: &Response, p: &SharedPlaybackState) {
+        if p.custom_context_menu() {
+            return;
+        }
+        frame_response.context_menu(|ui| {
+            let playing = matches!(p.state(), PlayerState::Playing | PlayerState::Buffering);
+            if ui.button(if playing { "Pause" } else { "Play" }).clicked() {
+                p.set_state(if playing {
+                    PlayerState::Paused
+                } else {
+                    PlayerState::Playing
+                });
+                ui.close();
+            }
+            if ui.button("Stop").clicked() {
+                p.set_state(PlayerState::Stopped);
+                ui.close();
+            }
+            if ui.button("Seek to Start").clicked() {
+                p.request_seek(0.0);
+                ui.close();
+            }
+            if ui.button("Copy Timestamp").clicked() {
+                ui.ctx().copy_text(format_time_ms(p.elapsed()));
+                ui.close();
+            }
+            if ui.selectable_label(p.looping(), "Loop").clicked() {
+                p.set_looping(!p.looping());
+                ui.close();
+            }
+            ui.separator();
+            for (label, streams, selected, can_disable) in [
+                (
+                    "Audio Tracks",
+                    p.available_audio_streams(),
+                    &p.selected_audio,
+                    false,
+                ),
+                (
+                    "Subtitle Tracks",
+                    p.available_subtitle_streams(),
+                    &p.selected_subtitle,
+                    true,
+                ),
+            ] {
+                ui.add_enabled_ui(!streams.is_empty(), |ui| {
+                    ui.menu_button(label, |ui| {
+                        let current = selected.load(Ordering::Relaxed);
+                        if can_disable && ui.selectable_label(current < 0, "Off").clicked() {
+                            selected.store(-1, Ordering::Relaxed);
+                            ui.close();
+                        }
+                        for s in &streams {
+                            let name = format!(
+                                "#{} {}",
+                                s.index,
+                                s.language.as_deref().unwrap_or(&s.codec)
+                            );
+                            if ui
+                                .selectable_label(current == s.index as isize, name)
+                                .clicked()
+                            {
+                                selected.store(s.index as _, Ordering::Relaxed);
+                                ui.close();
+                            }
+                        }
+                    });
+                });
+            }
+            ui.separator();
+            if ui.button("Toggle Debug").clicked() {
+                p.request_debug_toggle();
+                ui.close();
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seekbar_progress() {
+        assert_eq!(seekbar_progress_width(100.0, 0.5, 2.0), 25.0);
+        assert_eq!(seekbar_progress_width(100.0, 3.0, 2.0), 100.0);
+        assert_eq!(seekbar_progress_width(100.0, -1.0, 2.0), 0.0);
+        assert_eq!(seekbar_progress_width(100.0, 1.0, 0.0), 0.0);
+    }
+}
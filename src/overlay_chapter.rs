@@ -0,0 +1,93 @@
+use crate::overlay::seekbar_rect;
+use crate::{Chapter, PlayerOverlay, PlayerState, SharedPlaybackState};
+use egui::{Align2, Color32, CornerRadius, FontId, Rect, Response, Ui, pos2, vec2};
+
+/// Overlay which marks chapters on the seekbar, hovering a marker shows the chapter title.
+///
+/// [`crate::DefaultOverlay`] already marks the chapters of the media, use this with custom
+/// overlays or to show a fixed list of chapters.
+#[derive(Default)]
+pub struct ChapterOverlay {
+    chapters: Vec<Chapter>,
+}
+
+impl ChapterOverlay {
+    /// Create a chapter overlay with a fixed list of chapters,
+    /// when empty the chapters set with [`crate::Player::set_chapters`] or embedded in the
+    /// container are used
+    pub fn new(chapters: Vec<Chapter>) -> Self {
+        Self { chapters }
+    }
+}
+
+impl PlayerOverlay for ChapterOverlay {
+    fn show(&self, ui: &mut Ui, frame_response: &Response, p: &SharedPlaybackState) {
+        let duration = p.duration();
+        if duration <= 0.0 {
+            return;
+        }
+
+        let hovered = ui.rect_contains_pointer(frame_response.rect);
+        let anim_frac = ui.ctx().animate_bool_with_time(
+            frame_response.id.with("chapter_anim"),
+            hovered || p.state() != PlayerState::Playing,
+            0.2,
+        );
+        if anim_frac <= 0. {
+            return;
+        }
+
+        let chapters = if self.chapters.is_empty() {
+            p.chapters()
+        } else {
+            self.chapters.clone()
+        };
+        paint_chapters(
+            ui,
+            seekbar_rect(frame_response.rect),
+            &chapters,
+            duration,
+            anim_frac,
+        );
+    }
+}
+
+/// Draw a tick on the seekbar at the start of each chapter, hovering a tick shows the
+/// chapter title above it
+pub(crate) fn paint_chapters(
+    ui: &Ui,
+    seekbar: Rect,
+    chapters: &[Chapter],
+    duration: f64,
+    anim_frac: f32,
+) {
+    let tick_color = Color32::YELLOW.linear_multiply(anim_frac);
+    for chapter in chapters.iter() {
+        let frac = (chapter.start_seconds / duration).clamp(0.0, 1.0) as f32;
+        let x = seekbar.left() + seekbar.width() * frac;
+        let tick_rect = Rect::from_min_max(
+            pos2(x - 1., seekbar.top() - 3.),
+            pos2(x + 1., seekbar.bottom()),
+        );
+        ui.painter()
+            .rect_filled(tick_rect, CornerRadius::ZERO, tick_color);
+
+        if ui.rect_contains_pointer(tick_rect.expand2(vec2(3., 6.))) {
+            let galley = ui.painter().layout_no_wrap(
+                chapter.title.clone(),
+                FontId::proportional(14.),
+                Color32::WHITE,
+            );
+            let label_rect = Align2::CENTER_BOTTOM
+                .anchor_size(pos2(x, tick_rect.top() - 10.), galley.size())
+                .expand(4.);
+            ui.painter().rect_filled(
+                label_rect,
+                CornerRadius::same(3),
+                Color32::from_black_alpha(150),
+            );
+            ui.painter()
+                .galley(label_rect.min + vec2(4., 4.), galley, Color32::WHITE);
+        }
+    }
+}
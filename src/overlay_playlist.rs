@@ -0,0 +1,151 @@
+use crate::{PlayerOverlay, PlayerState, SharedPlaybackState, format_time};
+use egui::{Align2, Color32, CornerRadius, FontId, Rect, Response, Sense, Ui, pos2, vec2};
+
+/// Item shown in a [PlaylistOverlay]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PlaylistEntry {
+    /// Media path, the file name is shown when there is no title
+    pub path: String,
+    /// Display title
+    pub title: Option<String>,
+    /// Duration in seconds
+    pub duration: Option<f32>,
+}
+
+impl PlaylistEntry {
+    fn label(&self) -> String {
+        self.title.clone().unwrap_or_else(|| {
+            self.path
+                .rsplit(['/', '\\'])
+                .next()
+                .unwrap_or(&self.path)
+                .to_string()
+        })
+    }
+}
+
+/// Overlay which lists the playlist items in a panel on the right edge of the player.
+/// Clicking an entry opens that item of the playlist set with [`crate::Player::set_playlist`].
+///
+/// Combine with [`crate::DefaultOverlay`] to show both in one pass:
+/// ```ignore
+/// player.with_overlay((DefaultOverlay::default(), PlaylistOverlay::new(entries)))
+/// ```
+pub struct PlaylistOverlay {
+    entries: Vec<PlaylistEntry>,
+}
+
+impl PlaylistOverlay {
+    /// Width of the panel as a fraction of the frame
+    const WIDTH_FRAC: f32 = 0.3;
+    const ROW_HEIGHT: f32 = 24.0;
+    const PADDING: f32 = 8.0;
+
+    /// Create a playlist overlay, entries should be in the same order as the playlist items
+    pub fn new(entries: Vec<PlaylistEntry>) -> Self {
+        Self { entries }
+    }
+}
+
+impl PlayerOverlay for PlaylistOverlay {
+    fn show(&self, ui: &mut Ui, frame_response: &Response, p: &SharedPlaybackState) {
+        if self.entries.is_empty() {
+            return;
+        }
+
+        let hovered = ui.rect_contains_pointer(frame_response.rect);
+        let anim_frac = ui.ctx().animate_bool_with_time(
+            frame_response.id.with("playlist_anim"),
+            hovered || p.state() != PlayerState::Playing,
+            0.2,
+        );
+        if anim_frac <= 0. {
+            return;
+        }
+
+        let frame = frame_response.rect;
+        let panel_width = (frame.width() * Self::WIDTH_FRAC).clamp(150., 350.);
+        let panel_height = (self.entries.len() as f32 * Self::ROW_HEIGHT + Self::PADDING * 2.)
+            .min(frame.height() - Self::PADDING * 2.);
+        let panel = Rect::from_min_size(
+            pos2(
+                frame.right() - panel_width - Self::PADDING,
+                frame.top() + Self::PADDING,
+            ),
+            vec2(panel_width, panel_height),
+        );
+        ui.painter().rect_filled(
+            panel,
+            CornerRadius::same(4),
+            Color32::from_black_alpha((180. * anim_frac) as u8),
+        );
+
+        let current = p.playlist_index();
+        let text_color = Color32::WHITE.linear_multiply(anim_frac);
+        let font = FontId::proportional(14.);
+        let painter = ui.painter_at(panel.shrink(Self::PADDING / 2.));
+        for (i, entry) in self.entries.iter().enumerate() {
+            let row = Rect::from_min_size(
+                panel.min + vec2(0., Self::PADDING + i as f32 * Self::ROW_HEIGHT),
+                vec2(panel_width, Self::ROW_HEIGHT),
+            );
+            if row.bottom() > panel.bottom() {
+                break;
+            }
+            let response = ui.interact(
+                row,
+                frame_response.id.with("playlist_entry").with(i),
+                Sense::click(),
+            );
+            if current == Some(i) {
+                painter.rect_filled(
+                    row,
+                    CornerRadius::ZERO,
+                    Color32::from_white_alpha((40. * anim_frac) as u8),
+                );
+            } else if response.hovered() {
+                painter.rect_filled(
+                    row,
+                    CornerRadius::ZERO,
+                    Color32::from_white_alpha((20. * anim_frac) as u8),
+                );
+            }
+            if response.clicked() {
+                p.request_playlist_index(i);
+            }
+
+            painter.text(
+                row.left_center() + vec2(Self::PADDING, 0.),
+                Align2::LEFT_CENTER,
+                entry.label(),
+                font.clone(),
+                text_color,
+            );
+            if let Some(d) = entry.duration {
+                painter.text(
+                    row.right_center() - vec2(Self::PADDING, 0.),
+                    Align2::RIGHT_CENTER,
+                    format_time(d),
+                    font.clone(),
+                    text_color,
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entry_label() {
+        let mut entry = PlaylistEntry {
+            path: "/videos/clip.mp4".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(entry.label(), "clip.mp4");
+        entry.title = Some("Clip".to_string());
+        assert_eq!(entry.label(), "Clip");
+    }
+}
@@ -1,26 +1,34 @@
 use crate::stream::{
-    AudioSamples, DecoderInfo, MediaDecoder, StreamInfo, SubtitlePacket, VideoFrame,
+    AudioSamples, DecoderInfo, HwDecoderPref, MediaDecoder, MediaStreams, StreamInfo, StreamType,
+    StreamingOptions, SubtitlePacket, VideoFilters, VideoFrame, VideoFrameBuffer,
 };
 #[cfg(feature = "subtitles")]
 use crate::subtitle::Subtitle;
-use crate::{AudioDevice, NoAudioDevice, SharedPlaybackState, format_time};
-use anyhow::Result;
+use crate::{
+    AudioDevice, Chapter, EventBus, NoAudioDevice, PlayerEvent, Playlist, SeekPreviewDecoder,
+    SharedPlaybackState, ThumbnailCache, format_time, format_time_ms,
+};
+use anyhow::{Result, bail};
 use egui::load::SizedTexture;
 use egui::text::LayoutJob;
 use egui::{
-    Align2, Color32, ColorImage, Event, FontId, Image, Key, Rect, Response, Sense, Stroke,
-    StrokeKind, TextFormat, TextureHandle, TextureOptions, Ui, Vec2, Widget, pos2, vec2,
+    Align2, Color32, ColorImage, Event, FontId, Image, ImageData, Key, Pos2, Rect, Response, Sense,
+    Stroke, StrokeKind, TextFormat, TextureHandle, TextureId, TextureOptions, Ui, UiBuilder, Vec2,
+    Widget, pos2, vec2,
 };
-use log::{info, trace};
+#[cfg(feature = "subtitles")]
+use ffmpeg_rs_raw::ffmpeg_sys_the_third::AVCodecID;
+use log::{error, info, trace, warn};
 use std::fmt::Display;
 use std::ops::Add;
+use std::str::FromStr;
 use std::sync::Arc;
 use std::sync::atomic::{
     AtomicBool, AtomicI8, AtomicI64, AtomicIsize, AtomicU8, AtomicU16, AtomicU32, AtomicU64,
     Ordering,
 };
 use std::sync::mpsc::Receiver;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 #[cfg(not(feature = "subtitles"))]
 struct Subtitle;
@@ -31,6 +39,14 @@ pub trait PlayerOverlay: Send {
     fn show(&self, ui: &mut Ui, frame_response: &Response, p: &SharedPlaybackState);
 }
 
+/// Show two overlays in one pass, the first is drawn below the second
+impl<A: PlayerOverlay, B: PlayerOverlay> PlayerOverlay for (A, B) {
+    fn show(&self, ui: &mut Ui, frame_response: &Response, p: &SharedPlaybackState) {
+        self.0.show(ui, frame_response, p);
+        self.1.show(ui, frame_response, p);
+    }
+}
+
 struct NoOverlay;
 impl PlayerOverlay for NoOverlay {
     fn show(&self, _ui: &mut Ui, _frame_response: &Response, _p: &SharedPlaybackState) {
@@ -38,6 +54,29 @@ impl PlayerOverlay for NoOverlay {
     }
 }
 
+/// Entry of the right-click menu set with [`Player::with_context_menu`]
+pub struct MenuItem {
+    /// Text shown in the menu
+    pub label: String,
+    /// Called when the item is clicked
+    pub action: Box<dyn Fn(&SharedPlaybackState) + Send + Sync>,
+}
+
+impl MenuItem {
+    /// Create a menu item which calls `action` when clicked
+    pub fn new(
+        label: impl Into<String>,
+        action: impl Fn(&SharedPlaybackState) + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            label: label.into(),
+            action: Box::new(action),
+        }
+    }
+}
+
+type ContextMenuBuilder = Box<dyn Fn(&SharedPlaybackState) -> Vec<MenuItem> + Send + 'static>;
+
 /// The [`Player`] processes and controls streams of video/audio.
 /// This is what you use to show a video file.
 /// Initialize once, and use the [`Player::ui`] or [`Player::ui_at()`] functions to show the playback.
@@ -52,42 +91,140 @@ pub struct Player {
 
     /// The video frame to display
     frame: TextureHandle,
+    /// Pixel data of the video frame currently displayed
+    frame_image: Option<Arc<ColorImage>>,
     /// Start presentation time for the current frame
     frame_pts: f64,
     /// Length to show this frame in seconds
     frame_duration: f64,
     /// Clock time when the frame began
     frame_instant: Instant,
+    /// Embedded cover art, shown when the media has no video frames
+    cover_art: Option<TextureHandle>,
+    /// Clock time when playback started, cover art is shown if no frame arrives soon after
+    playing_since: Instant,
 
     /// How many frames have been rendered so far
     frame_counter: u64,
-    /// Maintain video aspect ratio
-    maintain_aspect: bool,
+    /// Position of the pending seek, frames before this are dropped
+    seek_target: Option<f64>,
+    /// Return to [`PlayerState::Paused`] once the pending seek completes
+    pause_after_seek: bool,
+    /// Position (seconds) to seek to once the media has been probed
+    start_time: Option<f64>,
+    /// Clock time since no video frames were available
+    video_starved: Option<Instant>,
+    /// How long the video can be starved before entering [`PlayerState::Buffering`]
+    buffering_threshold: Duration,
+    /// Difference between the audio and video clocks (seconds) allowed before video
+    /// frames are dropped or held to get back in sync
+    sync_tolerance: f64,
+    /// Upper bound on the rate repaints are scheduled for new frames
+    max_fps: Option<f32>,
+    /// When the current frame was put on screen, frames are not flipped faster than
+    /// [`Player::max_fps`]
+    frame_shown: Instant,
+    /// Display aspect ratio to use instead of the video stream's
+    aspect_ratio_override: Option<f32>,
     /// If player should fullscreen
     fullscreen: bool,
+    /// Window size before entering fullscreen, restored when leaving fullscreen
+    pre_fullscreen_size: Option<Vec2>,
     /// If key presses should be handled
     key_binds: bool,
+    /// Keys used by the built-in keybind controls
+    key_map: KeyBindMap,
 
     /// Stream info
     stream_info: Option<DecoderInfo>,
+    /// Interval between seekbar preview thumbnails in seconds, [None] to disable
+    thumbnail_interval: Option<f64>,
+    /// Decode exact preview frames while hovering the seekbar
+    seek_preview: bool,
+    /// Second decoder serving seekbar preview frames
+    preview_decoder: Option<SeekPreviewDecoder>,
 
     ctx: egui::Context,
     input_path: String,
-    audio: Box<dyn AudioDevice>,
-    subtitle: Option<Subtitle>,
+    /// Active audio output, [None] while switching devices
+    audio: Option<Box<dyn AudioDevice>>,
+    /// Output device used when (re)opening the audio stream
+    #[cfg(feature = "audio")]
+    audio_device: Option<crate::audio::AudioDevice>,
+    /// Decoded audio, shared so a new output device can take over the stream
+    #[cfg(feature = "audio")]
+    rx_audio: Arc<std::sync::Mutex<Receiver<AudioSamples>>>,
+    /// Receives the output samples of the audio device
+    #[cfg(feature = "audio")]
+    audio_tap: Option<crate::audio::AudioTap>,
+    /// Discard decoded audio instead of opening an output device
+    #[cfg(feature = "audio")]
+    no_audio: bool,
+    /// Current cue of the primary (bottom) and secondary (top) subtitle stream
+    subtitles: [Option<Subtitle>; 2],
+    /// Subtitles loaded from an external file, preferred over the embedded stream
+    external_subtitles: Vec<Subtitle>,
+    /// Overrides applied to every subtitle before it is shown
+    #[cfg(feature = "subtitles")]
+    subtitle_style: crate::subtitle::SubtitleStyle,
+    /// Character encoding of subtitle streams, UTF-8 when [None]
+    #[cfg(feature = "subtitles")]
+    subtitle_encoding: Option<String>,
+    /// Offset of subtitles from their default position, changed by dragging them
+    #[cfg(feature = "subtitles")]
+    subtitle_offset: Vec2,
 
     /// Media stream decoder thread
     media_player: MediaDecoder,
     rx_metadata: Receiver<DecoderInfo>,
-    rx_video: Receiver<VideoFrame>,
+    /// Decoded frames waiting to be shown
+    video_buffer: VideoFrameBuffer,
+    /// Seconds of video decoded ahead of playback
+    preload: f32,
+    /// In-memory media, opened again by [`Player::reload`] instead of [`Player::input_path`]
+    bytes: Option<Arc<[u8]>>,
     rx_subtitle: Receiver<SubtitlePacket>,
 
     /// An error which prevented playback
     error: Option<String>,
+    /// Shows errors as toast notifications instead of text on the video
+    #[cfg(feature = "notifications")]
+    toasts: Option<egui_notify::Toasts>,
 
     /// Message to show on scree for a short time (usually from keyboard input)
     osd: Option<String>,
     osd_end: Instant,
+
+    /// Subscribers to [PlayerEvent] notifications
+    events: EventBus,
+    /// Called when playback reaches the end of the stream
+    end_callback: Option<Box<dyn Fn() + Send + 'static>>,
+    /// Called with the target position (seconds) of each seek
+    seek_callback: Option<Box<dyn Fn(f64) + Send + 'static>>,
+    /// Builds the items of the right-click menu
+    context_menu: Option<ContextMenuBuilder>,
+    /// Media queue, advanced at the end of each stream
+    playlist: Option<Playlist>,
+    /// Hardware decoder to use for video
+    hw_pref: HwDecoderPref,
+    /// A/B loop region start (seconds)
+    loop_a: Option<f64>,
+    /// A/B loop region end (seconds)
+    loop_b: Option<f64>,
+    /// Fraction of the native resolution the video is decoded at
+    render_scale: f32,
+    /// Opacity of the overlay controls (0.0-1.0)
+    overlay_opacity: f32,
+    /// Upload frames to [`Player::gpu_frame`] instead of the egui texture
+    #[cfg(feature = "gpu_texture")]
+    gpu_upload: bool,
+    /// Frame texture written directly on the GPU, needs a wgpu render state
+    #[cfg(feature = "gpu_texture")]
+    gpu_frame: Option<crate::gpu_texture::GpuFrameTexture>,
+    /// Video zoom factor, 1.0 shows the whole frame
+    zoom: f32,
+    /// Offset of the zoomed view from the frame center, as a fraction of the frame size
+    pan: Vec2,
 }
 
 /// The possible states of a [`Player`].
@@ -103,6 +240,8 @@ pub enum PlayerState {
     Paused,
     /// Playback is ongoing.
     Playing,
+    /// Playback is stalled waiting for data.
+    Buffering,
 }
 
 impl From<u8> for PlayerState {
@@ -112,6 +251,7 @@ impl From<u8> for PlayerState {
             1 => PlayerState::Seeking,
             2 => PlayerState::Paused,
             3 => PlayerState::Playing,
+            4 => PlayerState::Buffering,
             _ => PlayerState::Stopped,
         }
     }
@@ -124,7 +264,166 @@ impl Display for PlayerState {
             PlayerState::Seeking => write!(f, "Seeking"),
             PlayerState::Paused => write!(f, "Paused"),
             PlayerState::Playing => write!(f, "Playing"),
+            PlayerState::Buffering => write!(f, "Buffering"),
+        }
+    }
+}
+
+/// Keys used by the built-in keybind controls, [None] disables an action.
+/// Mapped keys only trigger when no modifier is held. Keys which are not part of the map
+/// (A-B loop, delays, frame stepping etc.) are fixed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyBindMap {
+    /// Toggle between playing and paused
+    pub play_pause: Option<Key>,
+    /// Seek 5 seconds forward
+    pub seek_forward: Option<Key>,
+    /// Seek 5 seconds back
+    pub seek_backward: Option<Key>,
+    /// Increase the volume
+    pub volume_up: Option<Key>,
+    /// Decrease the volume
+    pub volume_down: Option<Key>,
+    /// Toggle fullscreen
+    pub fullscreen: Option<Key>,
+    /// Toggle the debug info
+    pub debug: Option<Key>,
+    /// Increase the playback speed
+    pub speed_up: Option<Key>,
+    /// Decrease the playback speed
+    pub speed_down: Option<Key>,
+    /// Save a snapshot of the current frame
+    pub snapshot: Option<Key>,
+    /// Export the current frame as PNG, same as [Self::snapshot]
+    pub export_frame: Option<Key>,
+    /// Open the next playlist item
+    pub playlist_next: Option<Key>,
+    /// Open the previous playlist item
+    pub playlist_prev: Option<Key>,
+}
+
+impl Default for KeyBindMap {
+    fn default() -> Self {
+        Self {
+            play_pause: Some(Key::Space),
+            seek_forward: Some(Key::ArrowRight),
+            seek_backward: Some(Key::ArrowLeft),
+            volume_up: Some(Key::ArrowUp),
+            volume_down: Some(Key::ArrowDown),
+            fullscreen: Some(Key::F),
+            debug: Some(Key::F1),
+            speed_up: Some(Key::CloseBracket),
+            speed_down: Some(Key::OpenBracket),
+            snapshot: Some(Key::S),
+            export_frame: Some(Key::E),
+            playlist_next: Some(Key::N),
+            playlist_prev: Some(Key::P),
+        }
+    }
+}
+
+/// [egui::Memory] key of the subtitle offset, shared by all players
+#[cfg(feature = "subtitles")]
+fn subtitle_offset_id() -> egui::Id {
+    egui::Id::new("rvp_subtitle_offset")
+}
+
+/// Largest pan offset which keeps the zoomed view inside the frame
+fn clamp_pan(zoom: f32, pan: Vec2) -> Vec2 {
+    let max = (0.5 - 0.5 / zoom.max(1.0)).max(0.0);
+    vec2(pan.x.clamp(-max, max), pan.y.clamp(-max, max))
+}
+
+/// Decode subtitle text with the encoding `label`, invalid or missing labels decode
+/// as UTF-8 with invalid sequences replaced
+#[cfg(feature = "subtitles")]
+fn decode_subtitle_text(data: &[u8], label: Option<&str>) -> String {
+    match label.and_then(|l| encoding_rs::Encoding::for_label(l.as_bytes())) {
+        Some(enc) => enc.decode(data).0.into_owned(),
+        None => String::from_utf8_lossy(data).into_owned(),
+    }
+}
+
+/// Frame rate over the measurement window, [None] until the window is at least 1s long
+/// and a frame was shown, so low frame rate content still updates every second
+fn average_fps(n_frames: u64, elapsed: Duration) -> Option<f32> {
+    if elapsed >= Duration::from_secs(1) && n_frames > 0 {
+        Some(n_frames as f32 / elapsed.as_secs_f32())
+    } else {
+        None
+    }
+}
+
+/// Texture coordinates of the visible part of the frame
+fn zoom_uv(zoom: f32, pan: Vec2) -> Rect {
+    let half = 0.5 / zoom.max(1.0);
+    let center = pos2(0.5, 0.5) + clamp_pan(zoom, pan);
+    Rect::from_center_size(center, Vec2::splat(half * 2.0))
+}
+
+/// Playback statistics, see [`Player::debug_stats`]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DebugStats {
+    /// Measured rate of frames shown per second
+    pub avg_fps: f32,
+    /// Frame rate of the video stream
+    pub display_fps: f32,
+    /// Size of the video stream (width, height)
+    pub video_resolution: (u32, u32),
+    /// Codec of the video stream
+    pub video_codec: String,
+    /// Codec of the audio stream
+    pub audio_codec: String,
+    /// Bitrate of the media (bits/s)
+    pub bitrate: u64,
+    /// Position of the last video frame shown (seconds)
+    pub video_pts: f64,
+    /// Position of the audio playback (seconds)
+    pub audio_pts: f64,
+    /// Audio position minus video position (seconds)
+    pub av_sync_delta: f64,
+    /// Output latency of the audio device (seconds)
+    pub audio_latency: f64,
+}
+
+/// Image file format used by [`Player::export_frame`]
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum ImageFormat {
+    /// Lossless PNG
+    Png,
+    /// JPEG with a quality from 1 to 100
+    Jpeg {
+        /// Encoder quality, higher is better
+        quality: u8,
+    },
+    /// Uncompressed bitmap
+    Bmp,
+}
+
+/// Display aspect ratio (width / height)
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct AspectRatio(pub f32);
+
+impl FromStr for AspectRatio {
+    type Err = anyhow::Error;
+
+    /// Parse a ratio like `"16:9"`, `"4/3"` or `"2.39"`
+    fn from_str(s: &str) -> Result<Self> {
+        let ratio = if let Some((w, h)) = s.split_once([':', '/']) {
+            w.trim().parse::<f32>()? / h.trim().parse::<f32>()?
+        } else {
+            s.trim().parse::<f32>()?
+        };
+        if !ratio.is_finite() || ratio <= 0.0 {
+            bail!("Invalid aspect ratio: {}", s);
         }
+        Ok(Self(ratio))
+    }
+}
+
+impl From<AspectRatio> for f32 {
+    fn from(value: AspectRatio) -> Self {
+        value.0
     }
 }
 
@@ -135,28 +434,111 @@ impl Player {
             "Loading video frame idx={}, pts={}, dur={}",
             self.frame_counter, frame.pts, frame.duration
         );
-        self.frame.set(frame.data, TextureOptions::default());
+        if frame.pts < self.frame_pts && self.seek_target.is_none() {
+            // the stream started again (looped), don't carry timing over from the last frame
+            info!(
+                "Video PTS went backwards {:.3} -> {:.3}, stream restarted",
+                self.frame_pts, frame.pts
+            );
+            self.frame_pts = 0.0;
+            self.frame_instant = Instant::now();
+            self.state.set_audio_pts(frame.pts);
+        }
+        let image = Arc::new(frame.data);
+        if !self.upload_gpu_frame(&image) {
+            self.frame
+                .set(ImageData::Color(image.clone()), TextureOptions::default());
+        }
+        self.frame_image = Some(image);
         self.frame_pts = frame.pts;
         self.frame_duration = frame.duration;
         self.frame_counter += 1;
         self.frame_instant = Instant::now();
+        self.frame_shown = self.frame_instant;
         self.state.set_video_pts(frame.pts);
 
+        // first frame after a seek or stall, resume playback
+        if matches!(
+            self.state.state(),
+            PlayerState::Seeking | PlayerState::Buffering
+        ) {
+            if self.pause_after_seek {
+                self.state.set_state(PlayerState::Paused);
+            } else {
+                self.state.set_state(PlayerState::Playing);
+            }
+        }
+        if let Some(target) = self.seek_target.take() {
+            self.events
+                .emit(PlayerEvent::SeekCompleted(target - self.state.start_time()));
+        }
+        self.pause_after_seek = false;
+
         // apply playback speed by adjusting frame duration
         let speed = self.state.speed() as f64 / 1.0;
         //self.frame_duration *= speed;
 
-        // tweak duration for A/V sync
-        let diff = self.state.video_pts() - self.state.audio_pts();
-        let max_diff = self.frame_duration * 0.1;
-        //self.frame_duration += diff.clamp(-max_diff, max_diff);
+        // video is ahead of audio, hold this frame longer so audio can catch up
+        if let Some(diff) = self.av_sync_offset(frame.pts)
+            && diff > self.sync_tolerance
+        {
+            self.frame_duration += diff.min(1.0);
+        }
+    }
+
+    /// How far `video_pts` is ahead of the audio clock (seconds), negative when video is behind.
+    /// [None] when there is no audio output to sync to, or while seeking
+    fn av_sync_offset(&self, video_pts: f64) -> Option<f64> {
+        self.audio_clock().map(|a| video_pts - a)
+    }
+
+    /// Playback position of the audio output (seconds), [None] when there is no audio
+    /// output to sync to, or while seeking
+    fn audio_clock(&self) -> Option<f64> {
+        let has_audio_clock = self.state.selected_audio.load(Ordering::Relaxed) >= 0
+            && self.audio.as_ref().is_some_and(|a| a.channels() > 0);
+        if !has_audio_clock
+            || self.seek_target.is_some()
+            || self.state.state() != PlayerState::Playing
+        {
+            return None;
+        }
+        Some(self.state.synced_audio_pts())
+    }
+
+    /// Playback position of the shown video frame (seconds), paces video by the media clock
+    /// when there is no audio. [None] before the first frame, while seeking, when not playing
+    /// or when the buffered frames start over (looped)
+    fn video_clock(&self) -> Option<f64> {
+        if self.frame_image.is_none()
+            || self.seek_target.is_some()
+            || self.state.state() != PlayerState::Playing
+            || self
+                .video_buffer
+                .front_pts()
+                .is_some_and(|p| p < self.frame_pts)
+        {
+            return None;
+        }
+        Some(self.frame_pts + self.frame_instant.elapsed().as_secs_f64())
+    }
+
+    /// Earliest instant the next frame can be shown, the end of the current frame
+    /// limited by [`Player::max_fps`]
+    fn next_frame_instant(&self) -> Instant {
+        let end = self.frame_end_instant();
+        match self.max_fps {
+            Some(fps) => end.max(
+                self.frame_shown
+                    .add(Duration::from_secs_f64(1.0 / fps as f64)),
+            ),
+            None => end,
+        }
     }
 
     fn request_repaint_for_next_frame(&self) {
         let now = Instant::now();
-        let next_frame = self
-            .frame_instant
-            .add(Duration::from_secs_f64(self.frame_duration));
+        let next_frame = self.next_frame_instant();
         if now > next_frame {
             self.ctx.request_repaint();
         } else {
@@ -174,7 +556,7 @@ impl Player {
         }
 
         let now = Instant::now();
-        now >= self.frame_end_instant()
+        now >= self.next_frame_instant()
     }
 
     /// Instant when the current frame ends
@@ -183,79 +565,686 @@ impl Player {
             .add(Duration::from_secs_f64(self.frame_duration))
     }
 
+    /// Current playback position in seconds from the start of the media
+    pub fn current_pts(&self) -> f64 {
+        self.state.elapsed()
+    }
+
+    /// Duration of the media in seconds, `0.0` until the stream is probed or for live streams
+    pub fn duration(&self) -> f32 {
+        self.state.duration() as f32
+    }
+
+    /// Duration of the media in seconds, [None] until the media has been probed.
+    /// Live streams report `Some(0.0)`
+    pub fn duration_secs(&self) -> Option<f32> {
+        self.stream_info.as_ref().map(|_| self.duration())
+    }
+
+    /// Request the video frame at `target_pts` (seconds) from the seek preview decoder and
+    /// return the latest decoded preview, which may still be of an earlier request.
+    /// [None] when previews are disabled or nothing has been decoded yet
+    pub fn get_preview_frame(&self, target_pts: f64) -> Option<TextureId> {
+        let preview = self.preview_decoder.as_ref()?;
+        preview.request(target_pts);
+        preview.frame().map(|(_, t)| t.id())
+    }
+
+    /// Stream info (codecs, resolution, bitrate, languages) once the media has been probed
+    pub fn stream_info(&self) -> Option<&DecoderInfo> {
+        self.stream_info.as_ref()
+    }
+
+    /// If the media has been probed and playback can begin, this is `false` until the
+    /// decoder thread sends the stream metadata
+    pub fn is_ready(&self) -> bool {
+        self.stream_info.is_some()
+    }
+
+    /// Native resolution (width, height) of the playing video stream.
+    /// Returns [None] until [`PlayerEvent::MetadataReady`] fires, or for audio only media
+    pub fn native_video_size(&self) -> Option<(u32, u32)> {
+        self.current_video_stream()
+            .filter(|v| v.width > 0 && v.height > 0)
+            .map(|v| (v.width, v.height))
+    }
+
+    /// Aspect ratio (width / height) of the playing video stream, e.g. to size the window.
+    /// Returns [None] until [`PlayerEvent::MetadataReady`] fires, or for audio only media
+    pub fn video_aspect_ratio(&self) -> Option<f32> {
+        self.native_video_size().map(|(w, h)| w as f32 / h as f32)
+    }
+
+    /// Seek to a position in seconds from the start of the media, clamped to the stream duration.
+    /// The seek is performed by the decoder thread, the player will be in
+    /// [`PlayerState::Seeking`] until the first frame at the new position is shown.
+    pub fn seek(&mut self, seconds: f64) {
+        let duration = self.state.duration();
+        let target = if duration > 0.0 {
+            seconds.clamp(0.0, duration)
+        } else {
+            seconds.max(0.0)
+        };
+        // the decoder and frames use stream PTS, which don't start at 0 for all containers
+        if let Some(f) = &self.seek_callback {
+            f(target);
+        }
+        let pts = target + self.state.start_time();
+        self.state.set_seek(pts);
+        self.state.set_state(PlayerState::Seeking);
+        self.seek_target = Some(pts);
+
+        // drop frames decoded before the seek
+        self.video_buffer.clear(self.state.seek_generation());
+        self.frame_instant = Instant::now();
+        self.frame_duration = 0.0;
+        self.ctx.request_repaint();
+    }
+
+    /// Seek relative to the current playback position, see [`Player::seek`]
+    pub fn seek_relative(&mut self, delta_seconds: f64) {
+        self.seek(self.current_pts() + delta_seconds);
+    }
+
+    /// Step a number of frames forward (or backward if negative) while paused
+    fn step_frames(&mut self, frames: i32) {
+        if self.state.state() != PlayerState::Paused {
+            return;
+        }
+        let fps = self
+            .current_video_stream()
+            .map(|s| s.fps)
+            .filter(|fps| *fps > 0.0)
+            .unwrap_or(30.0);
+        self.seek_relative(frames as f64 / fps as f64);
+        self.pause_after_seek = true;
+        let target = self
+            .seek_target
+            .map(|t| t - self.state.start_time())
+            .unwrap_or(self.current_pts());
+        self.show_osd(&format!(
+            "Frame: {} ({})",
+            self.frame_counter,
+            format_time_ms(target)
+        ));
+    }
+
+    /// Copy of the video frame currently displayed, [None] if no frame has been decoded yet
+    pub fn snapshot(&self) -> Option<ColorImage> {
+        self.frame_image.as_ref().map(|i| i.as_ref().clone())
+    }
+
+    /// Process pending frames and return the current frame scaled to `width`x`height`,
+    /// without drawing to a [Ui]. Letterboxed with black bars when the aspect ratio is
+    /// maintained, black if no frame has been decoded yet.
+    pub fn render_headless(&mut self, width: u32, height: u32) -> ColorImage {
+        self.process_state();
+
+        let (width, height) = (width as usize, height as usize);
+        let Some(frame) = self
+            .frame_image
+            .as_ref()
+            .filter(|_| width > 0 && height > 0)
+        else {
+            return ColorImage::filled([width, height], Color32::BLACK);
+        };
+        if frame.size == [width, height] {
+            return frame.as_ref().clone();
+        }
+        let size = self.video_frame_size(Rect::from_min_size(
+            Pos2::ZERO,
+            vec2(width as f32, height as f32),
+        ));
+        let (w, h) = (
+            (size.x as usize).clamp(1, width),
+            (size.y as usize).clamp(1, height),
+        );
+        let scaled = crate::thumbnail::scale_image(frame, w, h);
+        if [w, h] == [width, height] {
+            return scaled;
+        }
+        let mut out = ColorImage::filled([width, height], Color32::BLACK);
+        let (x0, y0) = ((width - w) / 2, (height - h) / 2);
+        for y in 0..h {
+            let dst = (y0 + y) * width + x0;
+            out.pixels[dst..dst + w].copy_from_slice(&scaled.pixels[y * w..(y + 1) * w]);
+        }
+        out
+    }
+
+    /// Save a snapshot of the current frame as PNG in the working directory
+    fn save_snapshot(&mut self) {
+        if self.frame_image.is_none() {
+            return;
+        }
+        let ts = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let path = format!("snapshot_{}.png", ts);
+        match self.export_frame(&path, ImageFormat::Png) {
+            Ok(_) => self.show_osd(&format!("Snapshot saved: {}", path)),
+            Err(e) => {
+                error!("Failed to save snapshot: {}", e);
+                self.show_osd("Snapshot failed");
+            }
+        }
+    }
+
+    /// Write the current frame to an image file
+    pub fn export_frame(&self, path: &str, format: ImageFormat) -> Result<()> {
+        let Some(frame) = self.frame_image.as_ref() else {
+            bail!("No frame decoded yet");
+        };
+        let pixels = frame
+            .pixels
+            .iter()
+            .flat_map(|p| p.to_srgba_unmultiplied())
+            .collect::<Vec<_>>();
+        let Some(img) = image::RgbaImage::from_raw(frame.size[0] as _, frame.size[1] as _, pixels)
+        else {
+            bail!("Invalid frame size");
+        };
+        match format {
+            ImageFormat::Png => img.save_with_format(path, image::ImageFormat::Png)?,
+            ImageFormat::Bmp => img.save_with_format(path, image::ImageFormat::Bmp)?,
+            ImageFormat::Jpeg { quality } => {
+                // jpeg has no alpha channel
+                let rgb = image::DynamicImage::ImageRgba8(img).to_rgb8();
+                let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+                image::codecs::jpeg::JpegEncoder::new_with_quality(file, quality.clamp(1, 100))
+                    .encode_image(&rgb)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that a stream of the given type exists
+    fn check_stream(&self, kind: StreamType, stream_index: i32) -> Result<()> {
+        let Some(info) = self.stream_info.as_ref() else {
+            bail!("Stream info not available yet");
+        };
+        if !info
+            .streams
+            .iter()
+            .any(|s| s.r#type == kind && s.index == stream_index)
+        {
+            bail!("No {:?} stream with index {}", kind, stream_index);
+        }
+        Ok(())
+    }
+
+    /// Select the audio stream to play, buffered samples from the previous stream are dropped
+    pub fn select_audio_track(&mut self, stream_index: i32) -> Result<()> {
+        self.check_stream(StreamType::Audio, stream_index)?;
+        self.state
+            .selected_audio
+            .store(stream_index as _, Ordering::Relaxed);
+        // drop the samples of the previous stream which are queued in the audio output
+        self.state.request_audio_resync(self.state.video_pts());
+        self.events.emit(PlayerEvent::TrackChanged {
+            kind: StreamType::Audio,
+            index: stream_index,
+        });
+        Ok(())
+    }
+
+    /// Select the subtitle stream to show
+    pub fn select_subtitle_track(&mut self, stream_index: i32) -> Result<()> {
+        self.check_stream(StreamType::Subtitle, stream_index)?;
+        self.state
+            .selected_subtitle
+            .store(stream_index as _, Ordering::Relaxed);
+        self.subtitles[0].take();
+        self.events.emit(PlayerEvent::TrackChanged {
+            kind: StreamType::Subtitle,
+            index: stream_index,
+        });
+        Ok(())
+    }
+
+    /// Select a second subtitle stream which is shown at the top of the frame, e.g. a
+    /// forced track for signs alongside the dialogue. [None] hides it
+    pub fn select_secondary_subtitle_track(&mut self, stream_index: Option<i32>) -> Result<()> {
+        if let Some(i) = stream_index {
+            self.check_stream(StreamType::Subtitle, i)?;
+        }
+        self.state
+            .selected_secondary_subtitle
+            .store(stream_index.map_or(-1, |i| i as _), Ordering::Relaxed);
+        self.subtitles[1].take();
+        Ok(())
+    }
+
+    /// Select the video stream to play
+    pub fn select_video_track(&mut self, stream_index: i32) -> Result<()> {
+        self.check_stream(StreamType::Video, stream_index)?;
+        self.state
+            .selected_video
+            .store(stream_index as _, Ordering::Relaxed);
+        self.events.emit(PlayerEvent::TrackChanged {
+            kind: StreamType::Video,
+            index: stream_index,
+        });
+        Ok(())
+    }
+
+    /// Load subtitles from an external `.srt` or `.ass`/`.ssa` file.
+    /// These are shown instead of any subtitle stream embedded in the media.
+    #[cfg(feature = "subtitles")]
+    pub fn load_subtitle_file(&mut self, path: &str) -> Result<()> {
+        self.external_subtitles = crate::subtitle::load_subtitle_file(path)?;
+        info!(
+            "Loaded {} subtitles from {}",
+            self.external_subtitles.len(),
+            path
+        );
+        Ok(())
+    }
+
+    /// If the viewport is in fullscreen mode
+    pub fn is_fullscreen(&self) -> bool {
+        self.fullscreen
+    }
+
+    /// Enter/Exit fullscreen mode for the viewport, the window size is restored on exit
+    pub fn set_fullscreen(&mut self, fullscreen: bool) {
+        if fullscreen == self.fullscreen {
+            return;
+        }
+        if fullscreen {
+            self.pre_fullscreen_size = self
+                .ctx
+                .input(|i| i.viewport().inner_rect)
+                .map(|r| r.size());
+            self.ctx
+                .send_viewport_cmd(egui::ViewportCommand::Fullscreen(true));
+        } else {
+            self.ctx
+                .send_viewport_cmd(egui::ViewportCommand::Fullscreen(false));
+            if let Some(size) = self.pre_fullscreen_size.take() {
+                self.ctx
+                    .send_viewport_cmd(egui::ViewportCommand::InnerSize(size));
+            }
+        }
+        self.fullscreen = fullscreen;
+    }
+
+    /// Playback volume from `0.0` (silent) to `1.0` (full volume)
+    pub fn volume(&self) -> f32 {
+        self.state.volume()
+    }
+
+    /// Set the playback volume, values outside `0.0..=1.0` are clamped to the nearest bound.
+    /// The volume is stored with 8-bit precision so [`Player::volume`] may differ slightly.
+    pub fn set_volume(&mut self, v: f32) {
+        self.state.set_volume(v.clamp(0.0, 1.0));
+    }
+
+    /// If audio output is muted
+    pub fn muted(&self) -> bool {
+        self.state.muted()
+    }
+
+    /// Mute/Unmute audio output, the volume is kept while muted
+    pub fn set_muted(&mut self, m: bool) {
+        self.state.set_muted(m);
+    }
+
+    /// Playback speed multiplier, `1.0` is normal speed
+    pub fn playback_speed(&self) -> f32 {
+        self.state.speed()
+    }
+
+    /// Set the playback speed multiplier, values outside `0.05..=10.0` are clamped to the
+    /// nearest bound. The speed is stored in steps of `0.05`, values in between are rounded down.
+    pub fn set_playback_speed(&mut self, s: f32) {
+        self.state.set_speed(s.clamp(0.05, 10.0));
+    }
+
+    /// If playback restarts from the beginning at the end of the stream
+    pub fn is_looping(&self) -> bool {
+        self.state.looping()
+    }
+
+    /// Enable/Disable restarting playback from the beginning at the end of the stream
+    pub fn set_looping(&mut self, enabled: bool) {
+        self.state.set_looping(enabled);
+    }
+
+    /// Audio delay in seconds, positive values play audio later than video
+    pub fn audio_delay(&self) -> f64 {
+        self.state.audio_delay()
+    }
+
+    /// Set the audio delay in seconds to correct A/V sync issues in the source media
+    pub fn set_audio_delay(&mut self, delay: f64) {
+        self.state.set_audio_delay(delay);
+    }
+
+    /// Subtitle delay in seconds, positive values show subtitles later than video
+    pub fn subtitle_delay(&self) -> f64 {
+        self.state.subtitle_delay()
+    }
+
+    /// Set the subtitle delay in seconds to correct out of sync subtitles
+    pub fn set_subtitle_delay(&mut self, delay: f64) {
+        self.state.set_subtitle_delay(delay);
+    }
+
+    /// Colour adjustments applied to the video
+    pub fn video_filters(&self) -> VideoFilters {
+        self.state.video_filters()
+    }
+
+    /// Set brightness/contrast/saturation of the video, applied by the decoder
+    /// to newly decoded frames
+    pub fn set_video_filters(&mut self, filters: VideoFilters) {
+        self.state.set_video_filters(filters);
+    }
+
     /// Enable/Disable built-in keybind controls
     pub fn enable_keybinds(&mut self, v: bool) {
         self.key_binds = v;
     }
 
+    /// Replace the keys used by the built-in keybind controls
+    pub fn with_keybind_map(mut self, map: KeyBindMap) -> Self {
+        self.key_map = map;
+        self
+    }
+
     /// Handle key input
     fn handle_keys(&mut self, ui: &mut Ui) {
-        const SEEK_STEP: f32 = 5.0;
+        const SEEK_STEP: f64 = 5.0;
         const VOLUME_STEP: f32 = 0.01;
         const SPEED_STEP: f32 = 0.1;
+        const AUDIO_DELAY_STEP: f64 = 0.05;
+        const SUBTITLE_DELAY_STEP: f64 = 0.1;
 
         if !self.key_binds {
             return;
         }
 
+        let mut snapshot = false;
+        let mut reset_zoom = false;
+        let mut reload = false;
+        let mut seek = 0.0;
+        let mut step = 0;
+        let mut delay = 0.0;
+        let mut sub_delay = 0.0;
+        let mut fullscreen = None;
+        let mut skip = None;
+        let mut ab_loop = None;
         ui.input(|inputs| {
             for e in &inputs.events {
                 match e {
-                    Event::Key { key, pressed, .. } if *pressed => match key {
-                        Key::Space => {
-                            if self.state.state() == PlayerState::Playing {
-                                self.state.set_state(PlayerState::Paused);
-                            } else {
-                                self.state.set_state(PlayerState::Playing);
+                    Event::Key {
+                        key,
+                        pressed,
+                        modifiers,
+                        ..
+                    } if *pressed => {
+                        // mapped keys only trigger without modifiers, Ctrl+S etc. stay with the app
+                        let bound = |k: &Key, b: Option<Key>| b == Some(*k) && modifiers.is_none();
+                        match key {
+                            Key::OpenBracket if modifiers.command => {
+                                sub_delay -= SUBTITLE_DELAY_STEP;
                             }
+                            Key::CloseBracket if modifiers.command => {
+                                sub_delay += SUBTITLE_DELAY_STEP;
+                            }
+                            // A-B loop on Shift+[ / Shift+], layouts report either the shifted
+                            // bracket or the curly bracket. Both together clear the loop
+                            Key::OpenBracket | Key::CloseBracket
+                                if modifiers.shift
+                                    && inputs.key_down(Key::OpenBracket)
+                                    && inputs.key_down(Key::CloseBracket) =>
+                            {
+                                ab_loop = Some((None, None));
+                            }
+                            Key::OpenCurlyBracket | Key::CloseCurlyBracket
+                                if inputs.key_down(Key::OpenCurlyBracket)
+                                    && inputs.key_down(Key::CloseCurlyBracket) =>
+                            {
+                                ab_loop = Some((None, None));
+                            }
+                            Key::OpenCurlyBracket => {
+                                ab_loop = Some((Some(self.current_pts()), self.loop_b));
+                            }
+                            Key::OpenBracket if modifiers.shift => {
+                                ab_loop = Some((Some(self.current_pts()), self.loop_b));
+                            }
+                            Key::CloseCurlyBracket => {
+                                ab_loop = Some((self.loop_a, Some(self.current_pts())));
+                            }
+                            Key::CloseBracket if modifiers.shift => {
+                                ab_loop = Some((self.loop_a, Some(self.current_pts())));
+                            }
+                            k if bound(k, self.key_map.play_pause) => {
+                                if self.state.state() == PlayerState::Playing {
+                                    self.state.set_state(PlayerState::Paused);
+                                } else {
+                                    self.state.set_state(PlayerState::Playing);
+                                }
+                            }
+                            k if bound(k, self.key_map.seek_forward) => {
+                                seek += SEEK_STEP;
+                            }
+                            k if bound(k, self.key_map.seek_backward) => {
+                                seek -= SEEK_STEP;
+                            }
+                            k if bound(k, self.key_map.volume_up) => {
+                                self.state.incr_volume(VOLUME_STEP);
+                            }
+                            k if bound(k, self.key_map.volume_down) => {
+                                self.state.decr_volume(VOLUME_STEP);
+                            }
+                            k if bound(k, self.key_map.fullscreen) => {
+                                fullscreen = Some(!self.fullscreen);
+                            }
+                            k if bound(k, self.key_map.debug) => {
+                                self.debug = !self.debug;
+                            }
+                            k if bound(k, self.key_map.speed_up) => {
+                                self.state.incr_speed(SPEED_STEP);
+                            }
+                            k if bound(k, self.key_map.speed_down) => {
+                                self.state.decr_speed(SPEED_STEP);
+                            }
+                            Key::Escape if self.fullscreen => {
+                                fullscreen = Some(false);
+                            }
+                            Key::M => {
+                                self.state.set_muted(!self.state.muted());
+                            }
+                            k if bound(k, self.key_map.snapshot)
+                                || bound(k, self.key_map.export_frame) =>
+                            {
+                                snapshot = true;
+                            }
+                            Key::R if modifiers.command => {
+                                reload = true;
+                            }
+                            Key::R => {
+                                reset_zoom = true;
+                            }
+                            k if bound(k, self.key_map.playlist_next) => {
+                                skip = Some(true);
+                            }
+                            k if bound(k, self.key_map.playlist_prev) => {
+                                skip = Some(false);
+                            }
+                            Key::Comma => {
+                                step -= 1;
+                            }
+                            Key::Period => {
+                                step += 1;
+                            }
+                            Key::Plus | Key::Equals => {
+                                delay += AUDIO_DELAY_STEP;
+                            }
+                            Key::Minus => {
+                                delay -= AUDIO_DELAY_STEP;
+                            }
+                            _ => {}
                         }
-                        Key::OpenBracket => {
-                            self.state.decr_speed(SPEED_STEP);
-                        }
-                        Key::CloseBracket => {
-                            self.state.incr_speed(SPEED_STEP);
-                        }
-                        Key::ArrowRight => {
-                            // not implemented
-                        }
-                        Key::ArrowLeft => {
-                            // not implemented
-                        }
-                        Key::ArrowUp => {
-                            self.state.incr_volume(VOLUME_STEP);
-                        }
-                        Key::ArrowDown => {
-                            self.state.decr_volume(VOLUME_STEP);
-                        }
-                        Key::F => {
-                            self.fullscreen = !self.fullscreen;
-                        }
-                        Key::F1 => {
-                            self.debug = !self.debug;
-                        }
-                        Key::M => {
-                            self.state.set_muted(!self.state.muted());
-                        }
-                        _ => {}
-                    },
+                    }
                     _ => {}
                 }
             }
         });
+        if let Some(fs) = fullscreen {
+            self.set_fullscreen(fs);
+        }
+        if let Some((a, b)) = ab_loop {
+            self.set_ab_loop(a, b);
+            match (self.loop_a, self.loop_b) {
+                (Some(a), Some(b)) => self.show_osd(&format!(
+                    "A-B loop: {} - {}",
+                    format_time_ms(a),
+                    format_time_ms(b)
+                )),
+                (Some(a), None) => self.show_osd(&format!("A-B loop: {} -", format_time_ms(a))),
+                (None, Some(b)) => self.show_osd(&format!("A-B loop: - {}", format_time_ms(b))),
+                (None, None) => self.show_osd("A-B loop cleared"),
+            }
+        }
+        if snapshot {
+            self.save_snapshot();
+        }
+        if reset_zoom {
+            self.reset_zoom();
+        }
+        if reload {
+            self.reload_stream_with_osd();
+        }
+        if let Some(next) = skip {
+            let r = if next {
+                self.open_next()
+            } else {
+                self.open_prev()
+            };
+            if let Err(e) = r {
+                error!("Failed to open playlist item: {}", e);
+                self.set_error(e.to_string());
+            }
+        }
+        if seek != 0.0 {
+            self.seek_relative(seek);
+        }
+        if step != 0 {
+            self.step_frames(step);
+        }
+        if delay != 0.0 {
+            self.set_audio_delay(self.audio_delay() + delay);
+            self.show_osd(&format!(
+                "Audio delay: {:.0}ms",
+                self.audio_delay() * 1000.0
+            ));
+        }
+        if sub_delay != 0.0 {
+            self.set_subtitle_delay(self.subtitle_delay() + sub_delay);
+            self.show_osd(&format!(
+                "Subtitle delay: {:.0}ms",
+                self.subtitle_delay() * 1000.0
+            ));
+        }
+    }
+
+    /// If a second decoder can seek around the media for thumbnails or previews,
+    /// not the case for live and real-time streams
+    fn can_seek_decode(&self, md: &DecoderInfo) -> bool {
+        md.duration > 0.0 && !StreamingOptions::is_realtime(&self.input_path)
     }
 
     fn process_state(&mut self) {
+        #[cfg(feature = "audio")]
+        if self.audio.is_none()
+            && let Err(e) = self.open_audio()
+        {
+            // don't retry every frame, play the video without sound
+            error!("Failed to open audio device: {}", e);
+            self.no_audio = true;
+            let _ = self.open_audio();
+        }
+        if let Some(index) = self.state.take_playlist_request()
+            && let Err(e) = self.open_playlist_index(index)
+        {
+            error!("Failed to open playlist item: {}", e);
+            self.set_error(e.to_string());
+        }
+        if let Some(pts) = self.state.take_seek_request() {
+            self.seek(pts);
+        }
+        if self.state.take_debug_toggle() {
+            self.debug = !self.debug;
+        }
+        if self.state.take_stream_reload() {
+            self.reload_stream_with_osd();
+        }
+        if let Some(pts) = self.state.take_preview_request() {
+            self.get_preview_frame(pts);
+        }
+        if let Some(preview) = &self.preview_decoder {
+            let frame = preview.frame();
+            self.state
+                .set_preview_frame(frame.map(|(pts, t)| (pts, SizedTexture::from_handle(&t))));
+        }
         let current_state = self.state.state();
         if self.stream_info.is_none()
             && let Ok(md) = self.rx_metadata.try_recv()
         {
             self.state.set_duration(md.duration as _);
+            self.state.set_start_time(md.start_time);
+            // chapters from the container, unless the app set its own
+            if !md.chapters.is_empty() && self.state.chapters().is_empty() {
+                self.state.set_chapters(
+                    md.chapters
+                        .iter()
+                        .map(|c| Chapter {
+                            title: c.title.clone(),
+                            start_seconds: (c.start - md.start_time).max(0.0),
+                        })
+                        .collect(),
+                );
+            }
+            self.state.set_streams(md.streams.clone());
+            if let Some(interval) = self.thumbnail_interval
+                && self.can_seek_decode(&md)
+            {
+                match ThumbnailCache::new(&self.ctx, &self.input_path, interval, md.duration as _) {
+                    Ok(cache) => self.state.set_thumbnails(cache),
+                    Err(e) => warn!("Failed to start thumbnail cache: {}", e),
+                }
+            }
+            if self.seek_preview
+                && self.can_seek_decode(&md)
+                && md.streams.iter().any(|s| s.r#type == StreamType::Video)
+            {
+                match SeekPreviewDecoder::new(&self.ctx, &self.input_path) {
+                    Ok(preview) => self.preview_decoder = Some(preview),
+                    Err(e) => warn!("Failed to start seek preview decoder: {}", e),
+                }
+            }
+            self.cover_art = md.metadata.cover_art.as_ref().map(|img| {
+                self.ctx
+                    .load_texture("cover_art", img.clone(), Default::default())
+            });
+            self.events.emit(PlayerEvent::MetadataReady(md.clone()));
             self.stream_info.replace(md);
+            self.state.set_ready(true);
             if current_state != PlayerState::Playing {
                 self.state.set_state(PlayerState::Playing);
             }
+            self.playing_since = Instant::now();
+            if let Some(start) = self.start_time.take() {
+                self.seek(start);
+            }
         }
 
+        self.process_subtitles();
+
         if current_state == PlayerState::Stopped {
             // nothing to do, playback is stopped
             return;
@@ -269,41 +1258,158 @@ impl Player {
 
         // reset avg fps every 1s
         let n_frames = self.frame_counter - self.last_frame_counter;
-        if n_frames >= 30 {
-            self.avg_fps = n_frames as f32 / (Instant::now() - self.avg_fps_start).as_secs_f32();
+        if let Some(fps) = average_fps(n_frames, self.avg_fps_start.elapsed()) {
+            self.avg_fps = fps;
             self.avg_fps_start = Instant::now();
             self.last_frame_counter = self.frame_counter;
         }
 
-        if let Ok(msg) = self.rx_video.recv() {
-            self.load_frame(msg);
-            // break on video frame
-            // once we load the next frame this loop will not call again until
-            // this frame is over (pts + duration)
-            self.request_repaint_for_next_frame();
-            return;
+        // skip frames before the seek target, or late frames when audio is ahead. Without
+        // audio a frame rate cap paces by the video clock, dropping frames it can't show
+        let audio_clock = self.audio_clock();
+        let video_clock = self
+            .max_fps
+            .filter(|_| audio_clock.is_none())
+            .and_then(|_| self.video_clock());
+        let clock = self
+            .seek_target
+            .or(audio_clock.map(|a| a - self.sync_tolerance))
+            .or(video_clock)
+            .unwrap_or(f64::NEG_INFINITY);
+        match self.video_buffer.next_frame_due(clock) {
+            Some(msg) => {
+                self.video_starved = None;
+                let pts = msg.pts;
+                self.load_frame(msg);
+                // the frame was due part way through, keep the clock running from there
+                if let Some(clock) = video_clock
+                    && clock > pts
+                {
+                    let late = (clock - pts).min(self.frame_duration);
+                    self.frame_instant -= Duration::from_secs_f64(late);
+                }
+                // jump back to the start of the A/B loop region
+                if let (Some(a), Some(b)) = (self.loop_a, self.loop_b)
+                    && a < b
+                    && self.state.looping()
+                    && self.current_pts() >= b
+                {
+                    self.seek(a);
+                }
+                // break on video frame
+                // once we load the next frame this loop will not call again until
+                // this frame is over (pts + duration)
+                self.request_repaint_for_next_frame();
+                return;
+            }
+            // media without a video stream never receives frames, it can't starve
+            None if !self.video_buffer.is_finished()
+                && self.state.selected_video.load(Ordering::Relaxed) < 0 => {}
+            None if !self.video_buffer.is_finished() => {
+                let since = *self.video_starved.get_or_insert_with(Instant::now);
+                if since.elapsed() >= self.buffering_threshold
+                    && self.state.state() == PlayerState::Playing
+                {
+                    self.state.set_state(PlayerState::Buffering);
+                }
+            }
+            None => {
+                // decoder thread exited, end of stream
+                if matches!(
+                    self.state.state(),
+                    PlayerState::Playing | PlayerState::Buffering
+                ) {
+                    info!("End of stream: {}", self.input_path);
+                    self.state.set_state(PlayerState::Stopped);
+                    if let Some(f) = &self.end_callback {
+                        f();
+                    }
+                    self.events.emit(PlayerEvent::EndOfStream);
+                    // auto-advance to the next playlist item
+                    if let Err(e) = self.open_next() {
+                        error!("Failed to open next playlist item: {}", e);
+                        self.set_error(e.to_string());
+                    }
+                }
+            }
         }
 
         // if no frames were found just request repaint again
         self.request_repaint_for_next_frame();
     }
 
+    /// Write the frame to the GPU texture when enabled, returns `false` when the
+    /// egui texture should be used instead
+    #[cfg(feature = "gpu_texture")]
+    fn upload_gpu_frame(&mut self, image: &ColorImage) -> bool {
+        match self.gpu_frame.as_mut().filter(|_| self.gpu_upload) {
+            Some(gpu) => {
+                gpu.upload(image);
+                true
+            }
+            None => false,
+        }
+    }
+
+    #[cfg(not(feature = "gpu_texture"))]
+    fn upload_gpu_frame(&mut self, _image: &ColorImage) -> bool {
+        false
+    }
+
+    /// Texture id and size of the current video frame
+    fn frame_texture(&self) -> (TextureId, [usize; 2]) {
+        #[cfg(feature = "gpu_texture")]
+        if self.gpu_upload
+            && let Some(gpu) = self.gpu_frame.as_ref()
+            && let Some(id) = gpu.id()
+        {
+            return (id, gpu.size());
+        }
+        (self.frame.id(), self.frame.size())
+    }
+
     fn generate_frame_image(&self, size: Vec2) -> Image<'_> {
-        Image::new(SizedTexture::new(self.frame.id(), size)).sense(Sense::click())
+        Image::new(SizedTexture::new(self.frame_texture().0, size))
+            .uv(zoom_uv(self.zoom, self.pan))
+            .sense(Sense::click_and_drag())
+    }
+
+    /// Zoom with the scroll wheel and pan by dragging the zoomed frame
+    fn handle_zoom(&mut self, ui: &mut Ui, frame_response: &Response) {
+        const ZOOM_MAX: f32 = 10.0;
+
+        if !self.key_binds {
+            return;
+        }
+        if frame_response.hovered() {
+            let scroll = ui.input(|i| i.raw_scroll_delta.y);
+            if scroll != 0.0 {
+                self.zoom = (self.zoom * (scroll / 200.0).exp()).clamp(1.0, ZOOM_MAX);
+            }
+        }
+        if self.zoom > 1.0 && frame_response.dragged() {
+            let size = self.video_frame_size(frame_response.rect);
+            if size.x > 0.0 && size.y > 0.0 {
+                self.pan -= frame_response.drag_delta() / size / self.zoom;
+            }
+        }
+        self.pan = clamp_pan(self.zoom, self.pan);
     }
 
-    fn render_frame(&self, ui: &mut Ui) -> Response {
-        self.render_frame_at(ui, ui.available_rect_before_wrap())
+    /// Reset the video zoom and pan to show the whole frame
+    pub fn reset_zoom(&mut self) {
+        self.zoom = 1.0;
+        self.pan = Vec2::ZERO;
     }
 
     /// Exact size of the video frame inside a given [Rect]
     fn video_frame_size(&self, rect: Rect) -> Vec2 {
-        if self.maintain_aspect {
-            let bv = self.current_video_stream();
-            let video_size = bv
-                .map(|v| vec2(v.width as f32, v.height as f32))
-                .unwrap_or(rect.size());
-            let ratio = video_size.x / video_size.y;
+        if self.state.maintain_aspect() {
+            let ratio = self.aspect_ratio_override.unwrap_or_else(|| {
+                self.current_video_stream()
+                    .map(|v| v.display_aspect_ratio())
+                    .unwrap_or(rect.width() / rect.height())
+            });
             let rect_ratio = rect.width() / rect.height();
             if ratio > rect_ratio {
                 let h = rect.width() / ratio;
@@ -323,17 +1429,92 @@ impl Player {
         let video_size = self.video_frame_size(rect);
         ui.painter()
             .rect(rect, 0.0, Color32::BLACK, Stroke::NONE, StrokeKind::Middle);
-        ui.put(rect, self.generate_frame_image(video_size))
+        let response = ui.put(rect, self.generate_frame_image(video_size));
+        // audio only media, show the cover art in place of the video
+        if self.frame_image.is_none()
+            && self.state.state() != PlayerState::Stopped
+            && self.playing_since.elapsed() >= Duration::from_secs(1)
+            && let Some(cover) = &self.cover_art
+        {
+            let size = cover.size_vec2();
+            let scale = (rect.width() / size.x).min(rect.height() / size.y);
+            ui.painter().image(
+                cover.id(),
+                Rect::from_center_size(rect.center(), size * scale),
+                Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0)),
+                Color32::WHITE,
+            );
+        }
+        response
+    }
+
+    /// Receive decoded subtitle events, packets are drained even when subtitles are
+    /// disabled so the decoder never blocks on a full channel
+    #[allow(unused_variables)]
+    fn process_subtitles(&mut self) {
+        while let Ok(pkt) = self.rx_subtitle.try_recv() {
+            #[cfg(feature = "subtitles")]
+            {
+                let primary = self.state.selected_subtitle.load(Ordering::Relaxed);
+                let secondary = self
+                    .state
+                    .selected_secondary_subtitle
+                    .load(Ordering::Relaxed);
+                let slot = if pkt.stream_index as isize == primary {
+                    0
+                } else if pkt.stream_index as isize == secondary {
+                    1
+                } else {
+                    continue;
+                };
+                let resolution = self
+                    .native_video_size()
+                    .map(|(w, h)| vec2(w as _, h as _))
+                    .unwrap_or(Vec2::ZERO);
+                self.subtitles[slot] = Some(Subtitle::new(
+                    decode_subtitle_text(&pkt.data, self.subtitle_encoding.as_deref()),
+                    pkt.pts,
+                    pkt.duration,
+                    AVCodecID::AV_CODEC_ID_ASS,
+                    resolution,
+                ));
+            }
+        }
     }
 
-    fn render_subtitles(&mut self, _ui: &mut Ui) {
+    #[allow(unused_variables)]
+    fn render_subtitles(&mut self, ui: &mut Ui) {
+        let pts = self.state.video_pts() - self.state.subtitle_delay();
         #[cfg(feature = "subtitles")]
-        if let Some(s) = self.subtitle.as_ref() {
-            let sub_end = s.pts + s.duration;
-            if sub_end < self.current_pts() {
-                self.subtitle.take();
+        {
+            for cue in self.subtitles.iter_mut() {
+                if cue.as_ref().is_some_and(|s| s.pts + s.duration < pts) {
+                    cue.take();
+                }
+            }
+            let [primary, secondary] = &mut self.subtitles;
+            let shown = if !self.external_subtitles.is_empty() {
+                self.external_subtitles
+                    .iter_mut()
+                    .find(|s| s.pts <= pts && pts < s.pts + s.duration)
             } else {
-                ui.add(s);
+                primary.as_mut().filter(|s| s.pts <= pts)
+            };
+            if let Some(s) = shown {
+                s.apply_style(&self.subtitle_style);
+                s.offset = self.subtitle_offset;
+                let response = ui.add(&*s);
+                if response.dragged() {
+                    self.subtitle_offset += response.drag_delta();
+                    let offset = self.subtitle_offset;
+                    self.ctx
+                        .data_mut(|d| d.insert_persisted(subtitle_offset_id(), offset));
+                }
+            }
+            if let Some(s) = secondary.as_mut().filter(|s| s.pts <= pts) {
+                s.apply_style(&self.subtitle_style);
+                s.at_top = true;
+                ui.add(&*s);
             }
         }
     }
@@ -354,8 +1535,26 @@ impl Player {
     }
 
     fn show_osd(&mut self, msg: &str) {
+        self.osd_message(msg, Duration::from_secs(2));
+    }
+
+    /// Show a message in the top right corner of the player for `duration`,
+    /// replacing any message currently shown. Use `\n` for multiple lines,
+    /// a very long `duration` (e.g. [Duration::MAX]) keeps it until [Player::clear_osd]
+    pub fn osd_message(&mut self, msg: &str, duration: Duration) {
+        /// Used when `duration` overflows [Instant], about 30 years
+        const FOREVER: Duration = Duration::from_secs(30 * 365 * 24 * 3600);
+        let now = Instant::now();
         self.osd = Some(msg.to_string());
-        self.osd_end = Instant::now() + Duration::from_secs(2);
+        self.osd_end = now
+            .checked_add(duration)
+            .or_else(|| now.checked_add(FOREVER))
+            .unwrap_or(now);
+    }
+
+    /// Hide the current on-screen message
+    pub fn clear_osd(&mut self) {
+        self.osd = None;
     }
 
     /// Get the currently playing video stream info
@@ -388,19 +1587,49 @@ impl Player {
         }
     }
 
+    /// Output latency of the audio device, zero without an audio device
+    pub fn audio_latency(&self) -> Duration {
+        self.audio
+            .as_ref()
+            .map(|a| a.latency())
+            .unwrap_or(Duration::ZERO)
+    }
+
+    /// Playback statistics shown by the debug overlay
+    pub fn debug_stats(&self) -> DebugStats {
+        let video = self.current_video_stream();
+        let audio = self.current_audio_stream();
+        let video_pts = self.state.video_pts();
+        let audio_pts = self.state.synced_audio_pts();
+        DebugStats {
+            avg_fps: self.avg_fps,
+            display_fps: video.map(|s| s.fps).unwrap_or(0.0),
+            video_resolution: video.map(|s| (s.width, s.height)).unwrap_or((0, 0)),
+            video_codec: video.map(|s| s.codec.clone()).unwrap_or_default(),
+            audio_codec: audio.map(|s| s.codec.clone()).unwrap_or_default(),
+            bitrate: self.stream_info.as_ref().map(|i| i.bitrate).unwrap_or(0),
+            video_pts,
+            audio_pts,
+            av_sync_delta: audio_pts - video_pts,
+            audio_latency: self.audio_latency().as_secs_f64(),
+        }
+    }
+
     fn debug_inner(&mut self, frame_response: Rect) -> LayoutJob {
         let font = TextFormat::simple(FontId::monospace(11.), Color32::WHITE);
 
         let mut layout = LayoutJob::default();
-        let v_pts = self.state.video_pts();
-        let a_pts = self.state.audio_pts();
+        let stats = self.debug_stats();
 
         layout.append(
             &format!(
-                "sync: v:{:.3}s, a:{:.3}s, a-sync:{:.3}s",
-                v_pts,
-                a_pts,
-                a_pts - v_pts,
+                "sync: v:{}, a:{}, a-sync:{:.3}s, a-delay:{:.0}ms, a-latency:{:.0}ms, s-delay:{:.0}ms",
+                format_time_ms(stats.video_pts),
+                format_time_ms(stats.audio_pts),
+                stats.av_sync_delta,
+                self.state.audio_delay() * 1000.0,
+                stats.audio_latency * 1000.0,
+                self.state.subtitle_delay() * 1000.0,
             ),
             0.0,
             font.clone(),
@@ -408,26 +1637,30 @@ impl Player {
 
         let video_stream = self.current_video_stream();
         let video_size = self.video_frame_size(frame_response);
+        let [render_w, render_h] = self.frame_texture().1;
         layout.append(
             &format!(
-                "\nplayback: {:.2} fps ({:.2}x), volume={:.0}%, resolution={}x{}",
-                self.avg_fps,
-                self.avg_fps / video_stream.map(|s| s.fps).unwrap_or(1.0),
+                "\nplayback: {:.2} fps ({:.2}x), volume={:.0}%, resolution={}x{}, render={}x{} ({:.0}%)",
+                stats.avg_fps,
+                stats.avg_fps / video_stream.map(|s| s.fps).unwrap_or(1.0),
                 100.0 * (self.state.volume()),
                 video_size.x,
-                video_size.y
+                video_size.y,
+                render_w,
+                render_h,
+                100.0 * self.render_scale
             ),
             0.0,
             font.clone(),
         );
 
         if let Some(info) = self.stream_info.as_ref() {
-            let bitrate_str = if info.bitrate > 1_000_000 {
-                format!("{:.1}M", info.bitrate as f32 / 1_000_000.0)
-            } else if info.bitrate > 1_000 {
-                format!("{:.1}k", info.bitrate as f32 / 1_000.0)
+            let bitrate_str = if stats.bitrate > 1_000_000 {
+                format!("{:.1}M", stats.bitrate as f32 / 1_000_000.0)
+            } else if stats.bitrate > 1_000 {
+                format!("{:.1}k", stats.bitrate as f32 / 1_000.0)
             } else {
-                info.bitrate.to_string()
+                stats.bitrate.to_string()
             };
 
             layout.append(
@@ -439,6 +1672,9 @@ impl Player {
                 0.0,
                 font.clone(),
             );
+            if !info.metadata.is_empty() {
+                layout.append(&format!("\nmetadata: {}", info.metadata), 0.0, font.clone());
+            }
 
             fn print_chan(layout: &mut LayoutJob, font: TextFormat, chan: Option<&StreamInfo>) {
                 if let Some(c) = chan {
@@ -456,17 +1692,92 @@ impl Player {
     /// Create a new [`Player`].
     pub fn new(ctx: &egui::Context, input_path: &str) -> Result<Self> {
         let state = SharedPlaybackState::new();
-
-        let (media_player, streams) =
+        let decoder =
             MediaDecoder::new(input_path, state.clone()).expect("Failed to create media playback");
+        Self::from_decoder(ctx, input_path, state, decoder)
+    }
 
-        let audio = Self::open_audio(state.clone(), streams.audio)?;
+    /// Create a new [`Player`] using [`StreamingOptions`] and a [`HwDecoderPref`], both are
+    /// applied before the decoder is started
+    pub fn new_with_options(
+        ctx: &egui::Context,
+        input_path: &str,
+        options: StreamingOptions,
+        hw_pref: HwDecoderPref,
+    ) -> Result<Self> {
+        let state = SharedPlaybackState::new();
+        let preload = options.preload_seconds;
+        let decoder = MediaDecoder::new_with_hw_pref(input_path, state.clone(), options, hw_pref)?;
+        let mut player = Self::from_decoder(ctx, input_path, state, decoder)?;
+        player.preload = preload;
+        player.hw_pref = hw_pref;
+        Ok(player)
+    }
+
+    /// Create a new [`Player`] with keybinds enabled, the video aspect ratio maintained and the
+    /// [`crate::DefaultOverlay`] attached. This is the recommended starting point for most apps.
+    #[cfg(feature = "default-overlay")]
+    pub fn new_autoplay(ctx: &egui::Context, path: &str) -> Result<Self> {
+        let mut player = Self::new(ctx, path)?;
+        player.key_binds = true;
+        player.set_maintain_aspect(true);
+        Ok(player.with_overlay(crate::DefaultOverlay::default()))
+    }
+
+    /// Create a new [`Player`] for media held in memory, e.g. served from a database.
+    /// The media must be playable without seeking back in the data, seeking back decodes
+    /// it again from the start up to the new position.
+    pub fn new_from_bytes(ctx: &egui::Context, data: Arc<[u8]>) -> Result<Self> {
+        Self::new_from_bytes_with_options(
+            ctx,
+            data,
+            StreamingOptions::default(),
+            HwDecoderPref::default(),
+        )
+    }
+
+    /// Create a new [`Player`] for media held in memory using [`StreamingOptions`] and a
+    /// [`HwDecoderPref`], see [`Player::new_from_bytes`]
+    pub fn new_from_bytes_with_options(
+        ctx: &egui::Context,
+        data: Arc<[u8]>,
+        options: StreamingOptions,
+        hw_pref: HwDecoderPref,
+    ) -> Result<Self> {
+        let state = SharedPlaybackState::new();
+        let preload = options.preload_seconds;
+        let decoder = MediaDecoder::new_from_bytes_with_options(
+            data.clone(),
+            state.clone(),
+            options,
+            hw_pref,
+        )?;
+        // thumbnails need a second decoder which can't be created for in-memory media
+        let mut player =
+            Self::from_decoder(ctx, "memory", state, decoder)?.with_thumbnail_interval(None);
+        player.preload = preload;
+        player.hw_pref = hw_pref;
+        player.bytes = Some(data);
+        Ok(player)
+    }
+
+    fn from_decoder(
+        ctx: &egui::Context,
+        input_path: &str,
+        state: SharedPlaybackState,
+        (media_player, streams): (MediaDecoder, MediaStreams),
+    ) -> Result<Self> {
+        #[cfg(feature = "audio")]
+        let audio = None;
+        #[cfg(not(feature = "audio"))]
+        let audio = Some(Box::new(NoAudioDevice::new(streams.audio)) as Box<dyn AudioDevice>);
 
         let init_size = ctx.available_rect();
-        Ok(Self {
+        let player = Self {
             state,
             overlay: Box::new(NoOverlay),
             key_binds: false,
+            key_map: KeyBindMap::default(),
             input_path: input_path.to_string(),
             frame: ctx.load_texture(
                 "video_frame",
@@ -476,28 +1787,462 @@ impl Player {
                 ),
                 Default::default(),
             ),
+            frame_image: None,
             frame_instant: Instant::now(),
+            cover_art: None,
+            playing_since: Instant::now(),
             frame_pts: 0.0,
             frame_duration: 0.0,
             ctx: ctx.clone(),
             audio,
-            subtitle: None,
+            #[cfg(feature = "audio")]
+            audio_device: None,
+            #[cfg(feature = "audio")]
+            rx_audio: Arc::new(std::sync::Mutex::new(streams.audio)),
+            #[cfg(feature = "audio")]
+            audio_tap: None,
+            #[cfg(feature = "audio")]
+            no_audio: false,
+            subtitles: [None, None],
+            external_subtitles: Vec::new(),
+            #[cfg(feature = "subtitles")]
+            subtitle_style: Default::default(),
+            #[cfg(feature = "subtitles")]
+            subtitle_encoding: None,
+            #[cfg(feature = "subtitles")]
+            subtitle_offset: ctx
+                .data_mut(|d| d.get_persisted(subtitle_offset_id()))
+                .unwrap_or_default(),
             media_player,
             rx_metadata: streams.metadata,
-            rx_video: streams.video,
+            video_buffer: VideoFrameBuffer::spawn(
+                streams.video,
+                streams.video_resized,
+                VideoFrameBuffer::DEFAULT_DURATION,
+            )?,
+            preload: StreamingOptions::default().preload_seconds,
+            bytes: None,
             debug: false,
             avg_fps: 0.0,
             avg_fps_start: Instant::now(),
             frame_counter: 0,
+            seek_target: None,
+            pause_after_seek: false,
+            start_time: None,
+            video_starved: None,
+            buffering_threshold: Duration::from_millis(500),
+            sync_tolerance: 0.1,
+            max_fps: None,
+            frame_shown: Instant::now(),
             last_frame_counter: 0,
             error: None,
+            #[cfg(feature = "notifications")]
+            toasts: Some(egui_notify::Toasts::default()),
             osd: None,
-            maintain_aspect: true,
+            aspect_ratio_override: None,
             fullscreen: false,
+            pre_fullscreen_size: None,
             osd_end: Instant::now(),
             stream_info: None,
+            thumbnail_interval: None,
+            seek_preview: false,
+            preview_decoder: None,
             rx_subtitle: streams.subtitle,
-        })
+            events: EventBus::default(),
+            end_callback: None,
+            seek_callback: None,
+            context_menu: None,
+            playlist: None,
+            hw_pref: HwDecoderPref::default(),
+            loop_a: None,
+            loop_b: None,
+            render_scale: 1.0,
+            overlay_opacity: 1.0,
+            #[cfg(feature = "gpu_texture")]
+            gpu_upload: false,
+            #[cfg(feature = "gpu_texture")]
+            gpu_frame: None,
+            zoom: 1.0,
+            pan: Vec2::ZERO,
+        };
+        // audio output is opened on the first frame, unless a builder opened it already
+        Ok(player)
+    }
+
+    /// Play audio on a specific output device instead of the system default
+    #[cfg(feature = "audio")]
+    pub fn with_audio_device(mut self, device: crate::audio::AudioDevice) -> Result<Self> {
+        self.audio_device = Some(device);
+        self.no_audio = false;
+        self.open_audio()?;
+        Ok(self)
+    }
+
+    /// Don't open an audio output device, decoded audio is discarded.
+    /// Useful for test harnesses or muted previews on machines without audio hardware
+    #[cfg_attr(not(feature = "audio"), allow(unused_mut))]
+    pub fn with_no_audio(mut self) -> Self {
+        #[cfg(feature = "audio")]
+        {
+            self.no_audio = true;
+            self.audio = Some(Box::new(NoAudioDevice::new_shared(self.rx_audio.clone())));
+        }
+        self
+    }
+
+    /// Play audio on the output device with this name, see `AudioDevice::enumerate_devices`
+    #[cfg(feature = "audio")]
+    pub fn with_audio_device_name(self, name: &str) -> Result<Self> {
+        self.with_audio_device(crate::audio::AudioDevice::open_by_name(name)?)
+    }
+
+    /// Set which hardware decoder is used for video the next time media is opened, e.g. by
+    /// [`Player::reload`] or a playlist. The decoder of the current media is not restarted,
+    /// use [`Player::new_with_options`] to apply it to the first open
+    pub fn with_hw_decoder_pref(mut self, pref: HwDecoderPref) -> Self {
+        self.hw_pref = pref;
+        self
+    }
+
+    /// Set how many seconds of video are decoded ahead of playback the next time media is
+    /// opened. Higher values smooth over decoder stalls but use more memory.
+    /// Use [`StreamingOptions::preload_seconds`] with [`Player::new_with_options`] to apply
+    /// it to the first open
+    pub fn with_preload(mut self, seconds: f32) -> Self {
+        self.preload = seconds;
+        self
+    }
+
+    /// Set the A/B loop region (seconds), when both points are set and looping is enabled
+    /// playback jumps back to `a` once it reaches `b`
+    pub fn set_ab_loop(&mut self, a: Option<f64>, b: Option<f64>) {
+        self.loop_a = a;
+        self.loop_b = b;
+        self.state.set_ab_loop(a, b);
+    }
+
+    /// The A/B loop region (seconds)
+    pub fn ab_loop(&self) -> (Option<f64>, Option<f64>) {
+        (self.loop_a, self.loop_b)
+    }
+
+    /// Attach a playlist, items are played in order and advance automatically at the end
+    /// of each stream or with the `N` / `P` keys. If no playlist item is selected yet the
+    /// first item is opened immediately.
+    pub fn set_playlist(&mut self, mut pl: Playlist) -> Result<()> {
+        let start = if pl.current().is_none() {
+            pl.next().map(str::to_string)
+        } else {
+            None
+        };
+        self.state.set_playlist_index(pl.index());
+        self.playlist = Some(pl);
+        if let Some(path) = start {
+            self.open_item(&path)?;
+        }
+        Ok(())
+    }
+
+    /// Open a playlist item in place of the current media, including in-memory media
+    fn open_item(&mut self, path: &str) -> Result<()> {
+        self.bytes = None;
+        self.open(path)
+    }
+
+    /// The attached playlist
+    pub fn playlist(&self) -> Option<&Playlist> {
+        self.playlist.as_ref()
+    }
+
+    /// Open the next playlist item, returns false at the end of the playlist
+    pub fn open_next(&mut self) -> Result<bool> {
+        let Some(path) = self
+            .playlist
+            .as_mut()
+            .and_then(|p| p.next())
+            .map(str::to_string)
+        else {
+            return Ok(false);
+        };
+        self.open_item(&path)?;
+        Ok(true)
+    }
+
+    /// Open the playlist item at `index`, returns false if it doesn't exist
+    pub fn open_playlist_index(&mut self, index: usize) -> Result<bool> {
+        let Some(path) = self
+            .playlist
+            .as_mut()
+            .and_then(|p| p.select(index))
+            .map(str::to_string)
+        else {
+            return Ok(false);
+        };
+        self.open_item(&path)?;
+        Ok(true)
+    }
+
+    /// Open the previous playlist item, returns false at the start of the playlist
+    pub fn open_prev(&mut self) -> Result<bool> {
+        let Some(path) = self
+            .playlist
+            .as_mut()
+            .and_then(|p| p.prev())
+            .map(str::to_string)
+        else {
+            return Ok(false);
+        };
+        self.open_item(&path)?;
+        Ok(true)
+    }
+
+    /// Receive [PlayerEvent] notifications, each receiver gets every event sent after
+    /// it was created. Dropping the receiver unsubscribes.
+    pub fn subscribe(&mut self) -> Receiver<PlayerEvent> {
+        self.events.subscribe()
+    }
+
+    fn set_error(&mut self, error: String) {
+        self.events.emit(PlayerEvent::PlaybackError(error.clone()));
+        #[cfg(feature = "notifications")]
+        if let Some(toasts) = self.toasts.as_mut() {
+            toasts.error(error.clone());
+        }
+        self.error = Some(error);
+    }
+
+    /// The error which stopped playback, if any
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// Re-open the current media from the start, e.g. to retry after a network error.
+    /// The decoder is re-created, frame state is reset and the error is cleared.
+    pub fn reload(&mut self) -> Result<()> {
+        let path = self.input_path.clone();
+        self.open(&path)?;
+        self.state.set_state(PlayerState::Stopped);
+        Ok(())
+    }
+
+    /// Restart the decoder from [`Player::input_path`] without rebuilding the player,
+    /// e.g. after a network stream dropped. Playback continues from the last shown
+    /// frame, live streams continue from the live edge
+    pub fn reload_stream(&mut self) -> Result<()> {
+        let pts = self.current_pts();
+        let seekable = self.duration() > 0.0;
+        self.media_player.cancel();
+        self.reload()?;
+        if seekable && pts > 0.0 {
+            self.start_time = Some(pts);
+        }
+        Ok(())
+    }
+
+    fn reload_stream_with_osd(&mut self) {
+        self.show_osd("Reloading stream…");
+        if let Err(e) = self.reload_stream() {
+            error!("Failed to reload stream: {}", e);
+            self.set_error(e.to_string());
+        }
+    }
+
+    /// Path or URL of the current media
+    pub fn input_path(&self) -> &str {
+        &self.input_path
+    }
+
+    /// Open different media in place of the current one, see [`Player::reload`].
+    /// The current path is kept if the new media can't be opened
+    pub fn set_input_path(&mut self, path: &str) -> Result<()> {
+        let old = std::mem::replace(&mut self.input_path, path.to_string());
+        let old_bytes = self.bytes.take();
+        if let Err(e) = self.reload() {
+            self.input_path = old;
+            self.bytes = old_bytes;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Stop playback and the decoder thread, [`Player::reload`] starts the media again
+    pub fn stop(&mut self) {
+        self.media_player.cancel();
+        self.state.set_state(PlayerState::Stopped);
+    }
+
+    /// Replace the current media, dropping the current decoder and resetting frame state.
+    /// Volume, mute, speed, looping and audio delay are kept.
+    fn open(&mut self, path: &str) -> Result<()> {
+        info!("Opening {}", path);
+        let state = SharedPlaybackState::new();
+        state.set_volume(self.state.volume());
+        state.set_muted(self.state.muted());
+        state.set_speed(self.state.speed());
+        state.set_looping(self.state.looping());
+        state.set_audio_delay(self.state.audio_delay());
+        state.set_subtitle_delay(self.state.subtitle_delay());
+        state.set_maintain_aspect(self.state.maintain_aspect());
+        state.set_video_filters(self.state.video_filters());
+        state.set_render_scale(self.render_scale);
+        state.set_overlay_opacity(self.overlay_opacity);
+        state.set_playlist_index(self.playlist.as_ref().and_then(|p| p.index()));
+
+        let options = StreamingOptions {
+            preload_seconds: self.preload,
+            ..Default::default()
+        };
+        let (media_player, streams) = match &self.bytes {
+            Some(bytes) => MediaDecoder::new_from_bytes_with_options(
+                bytes.clone(),
+                state.clone(),
+                options,
+                self.hw_pref,
+            )?,
+            None => MediaDecoder::new_with_hw_pref(path, state.clone(), options, self.hw_pref)?,
+        };
+        self.media_player = media_player;
+        self.rx_metadata = streams.metadata;
+        self.video_buffer = VideoFrameBuffer::spawn(
+            streams.video,
+            streams.video_resized,
+            self.video_buffer.max_duration(),
+        )?;
+        self.rx_subtitle = streams.subtitle;
+        self.state = state;
+        self.input_path = path.to_string();
+        #[cfg(feature = "audio")]
+        {
+            self.rx_audio = Arc::new(std::sync::Mutex::new(streams.audio));
+            self.open_audio()?;
+        }
+        #[cfg(not(feature = "audio"))]
+        {
+            self.audio = Some(Box::new(NoAudioDevice::new(streams.audio)));
+        }
+
+        self.stream_info = None;
+        self.preview_decoder = None;
+        self.frame_image = None;
+        self.frame_pts = 0.0;
+        self.frame_duration = 0.0;
+        self.frame_instant = Instant::now();
+        self.cover_art = None;
+        self.seek_target = None;
+        self.pause_after_seek = false;
+        self.video_starved = None;
+        self.subtitles = [None, None];
+        self.external_subtitles.clear();
+        self.loop_a = None;
+        self.loop_b = None;
+        self.zoom = 1.0;
+        self.pan = Vec2::ZERO;
+        self.error = None;
+        self.ctx.request_repaint();
+        Ok(())
+    }
+
+    /// Display the video at a fixed aspect ratio (width / height) instead of the stream's
+    /// native ratio, useful for anamorphic content. See [`AspectRatio`] for parsing `"16:9"` etc.
+    pub fn with_aspect_ratio_override(mut self, ratio: Option<f32>) -> Self {
+        self.aspect_ratio_override = ratio.filter(|r| r.is_finite() && *r > 0.0);
+        self
+    }
+
+    /// Start with the debug info shown, it can still be toggled with the debug key (`F1`)
+    pub fn with_debug_default(mut self, enabled: bool) -> Self {
+        self.set_debug(enabled);
+        self
+    }
+
+    /// Show or hide the debug info
+    pub fn set_debug(&mut self, enabled: bool) {
+        self.debug = enabled;
+    }
+
+    /// If the debug info is shown
+    pub fn is_debug(&self) -> bool {
+        self.debug
+    }
+
+    /// Show the video at its aspect ratio (fit, the default) or stretch it to fill the frame
+    pub fn with_maintain_aspect(mut self, v: bool) -> Self {
+        self.set_maintain_aspect(v);
+        self
+    }
+
+    /// Show the video at its aspect ratio (fit) or stretch it to fill the frame
+    pub fn set_maintain_aspect(&mut self, v: bool) {
+        self.state.set_maintain_aspect(v);
+    }
+
+    /// Set the chapters of the media, shown by overlays such as [`crate::ChapterOverlay`]
+    pub fn set_chapters(&mut self, mut chapters: Vec<Chapter>) {
+        chapters.sort_by(|a, b| a.start_seconds.total_cmp(&b.start_seconds));
+        self.state.set_chapters(chapters);
+    }
+
+    /// Set a callback which is called once when playback reaches the end of the stream,
+    /// the player is then [`PlayerState::Stopped`]
+    pub fn on_end(mut self, f: impl Fn() + Send + 'static) -> Self {
+        self.end_callback = Some(Box::new(f));
+        self
+    }
+
+    /// Set a callback which is called with the target position (seconds, clamped to the
+    /// duration) whenever a seek starts, from keys, the overlay or [`Player::seek`].
+    /// It runs on the UI thread so it must not block, e.g. to save watch history
+    pub fn with_on_seek(mut self, f: impl Fn(f64) + Send + 'static) -> Self {
+        self.seek_callback = Some(Box::new(f));
+        self
+    }
+
+    /// Show a menu when the video frame is right-clicked, `builder` is called each frame
+    /// the menu is open so items can reflect the current playback state.
+    /// This replaces the menu of the [`crate::DefaultOverlay`]
+    pub fn with_context_menu(
+        mut self,
+        builder: impl Fn(&SharedPlaybackState) -> Vec<MenuItem> + Send + 'static,
+    ) -> Self {
+        self.context_menu = Some(Box::new(builder));
+        self
+    }
+
+    /// Set the playback volume (`0.0` to `1.0`) before playback starts, e.g. to restore it
+    /// from a previous session. Must be called before the first [`Player::ui`] call because
+    /// the audio thread reads the volume on its first callback.
+    pub fn with_initial_volume(self, v: f32) -> Self {
+        self.state.set_volume(v);
+        self
+    }
+
+    /// Start playing at a speed multiplier other than 1x, e.g. a podcast app's 1.5x default.
+    /// Clamped and rounded like [`Player::set_playback_speed`]. Audio is time-stretched so
+    /// its pitch is kept, and from 2x non-reference video frames are no longer decoded.
+    pub fn with_initial_speed(self, s: f32) -> Self {
+        self.state.set_speed(s.clamp(0.05, 10.0));
+        self
+    }
+
+    /// Decode subtitle streams with a legacy encoding such as `windows-1252`, `shift_jis`
+    /// or `gb2312`, instead of UTF-8. Any label from the
+    /// [Encoding Standard](https://encoding.spec.whatwg.org/#names-and-labels) supported
+    /// by `encoding_rs` can be used, unknown labels fall back to UTF-8
+    #[cfg(feature = "subtitles")]
+    pub fn with_subtitle_encoding(mut self, enc: &str) -> Self {
+        if encoding_rs::Encoding::for_label(enc.as_bytes()).is_none() {
+            warn!("Unknown subtitle encoding {}, using UTF-8", enc);
+        }
+        self.subtitle_encoding = Some(enc.to_string());
+        self
+    }
+
+    /// Override the font size, color, margin or boldness of all subtitles,
+    /// regardless of the style set by the subtitle file
+    #[cfg(feature = "subtitles")]
+    pub fn with_subtitle_style(mut self, style: crate::subtitle::SubtitleStyle) -> Self {
+        self.subtitle_style = style;
+        self
     }
 
     /// Add an overlay for the player
@@ -506,57 +2251,220 @@ impl Player {
         self
     }
 
-    #[allow(unused)]
-    fn open_audio(
-        state: SharedPlaybackState,
-        rx: Receiver<AudioSamples>,
-    ) -> Result<Box<dyn AudioDevice>> {
-        #[cfg(feature = "audio")]
-        return Ok(Box::new(
-            crate::audio::AudioDevice::open_default_audio_stream(state, rx)?,
-        ));
-        Ok(Box::new(NoAudioDevice::new(rx)))
+    /// Set how long playback can wait for video frames before showing [`PlayerState::Buffering`]
+    pub fn with_buffering_threshold(mut self, threshold: Duration) -> Self {
+        self.buffering_threshold = threshold;
+        self
+    }
+
+    /// Start playback at a position (seconds) instead of the beginning, e.g. to resume
+    /// where the user stopped. The seek happens once the media has been probed
+    pub fn with_start_time(mut self, seconds: f64) -> Self {
+        self.start_time = Some(seconds.max(0.0));
+        self
+    }
+
+    /// Set how far (seconds) video may drift from the audio clock before frames are
+    /// dropped (video behind) or held (video ahead), default 0.1s
+    pub fn with_sync_tolerance(mut self, seconds: f64) -> Self {
+        self.sync_tolerance = seconds.max(0.0);
+        self
+    }
+
+    /// Limit how often the player shows new frames, e.g. to avoid repainting at 144Hz for
+    /// high frame rate video. Frames which can't be shown in time are dropped so playback
+    /// keeps the speed of the media. Values <= 0 remove the limit
+    pub fn with_max_fps(mut self, fps: f32) -> Self {
+        self.max_fps = (fps > 0.0).then_some(fps);
+        self
+    }
+
+    /// Set the interval between seekbar preview thumbnails in seconds, [None] (the default)
+    /// disables them. Thumbnails are decoded on a separate thread once the stream metadata is
+    /// known, live and real-time streams get no thumbnails.
+    pub fn with_thumbnail_interval(mut self, interval: Option<f64>) -> Self {
+        self.thumbnail_interval = interval;
+        self
+    }
+
+    /// Decode the exact frame under the pointer while hovering the seekbar (default off).
+    /// This starts another video decoder once the stream metadata is known
+    pub fn with_seek_preview(mut self, enabled: bool) -> Self {
+        self.seek_preview = enabled;
+        self
+    }
+
+    /// Seconds of decoded video buffered ahead of playback (default 0.5s). A longer buffer
+    /// smooths over decoders with variable latency such as HLS, at the cost of memory
+    pub fn with_video_buffer_duration(self, secs: f32) -> Self {
+        self.video_buffer.set_max_duration(secs);
+        self
+    }
+
+    /// Write decoded frames straight into a wgpu texture instead of uploading them
+    /// through the egui texture manager, which saves a CPU copy per frame for large
+    /// videos. Needs the render state from [`Player::with_wgpu_render_state`]
+    #[cfg(feature = "gpu_texture")]
+    pub fn with_gpu_texture_upload(mut self, enabled: bool) -> Self {
+        self.gpu_upload = enabled;
+        self
+    }
+
+    /// Write decoded frames straight into a wgpu texture, this needs the
+    /// `gpu_texture` feature and does nothing without it
+    #[cfg(not(feature = "gpu_texture"))]
+    pub fn with_gpu_texture_upload(self, _enabled: bool) -> Self {
+        self
+    }
+
+    /// Set the wgpu render state of the app (`eframe::Frame::wgpu_render_state`),
+    /// used by [`Player::with_gpu_texture_upload`]
+    #[cfg(feature = "gpu_texture")]
+    pub fn with_wgpu_render_state(mut self, render_state: egui_wgpu::RenderState) -> Self {
+        self.gpu_frame = Some(crate::gpu_texture::GpuFrameTexture::new(render_state));
+        self
+    }
+
+    /// Decode video at a fraction of its native resolution (0.1-1.0) to reduce the
+    /// scaling and upload cost on slow hardware, the frame is still shown at full size
+    pub fn with_render_scale(mut self, scale: f32) -> Self {
+        self.render_scale = scale.clamp(0.1, 1.0);
+        self.state.set_render_scale(self.render_scale);
+        self
+    }
+
+    /// Opacity of the overlay controls (0.0-1.0, default 1.0), applied on top of their
+    /// show/hide animation
+    pub fn with_overlay_opacity(mut self, opacity: f32) -> Self {
+        self.overlay_opacity = opacity.clamp(0.0, 1.0);
+        self.state.set_overlay_opacity(self.overlay_opacity);
+        self
+    }
+
+    /// Register a callback which receives the interleaved samples sent to the audio device,
+    /// after volume and speed are applied. Useful for level metering or recording.
+    #[cfg(feature = "audio")]
+    pub fn capture_audio_samples(mut self, cb: impl Fn(&[f32]) + Send + Sync + 'static) -> Self {
+        self.audio_tap = Some(Arc::new(cb));
+        if let Err(e) = self.open_audio() {
+            error!("Failed to open audio device: {}", e);
+        }
+        self
+    }
+
+    /// Start the audio output for the current stream
+    #[cfg(feature = "audio")]
+    fn open_audio(&mut self) -> Result<()> {
+        // release the current output before opening the device again
+        self.audio.take();
+        if self.no_audio {
+            self.audio = Some(Box::new(NoAudioDevice::new_shared(self.rx_audio.clone())));
+            return Ok(());
+        }
+        let device = match self.audio_device.clone() {
+            Some(d) => d,
+            None => crate::audio::AudioDevice::new()?,
+        };
+        self.audio = Some(Box::new(device.open_shared_audio_stream(
+            self.state.clone(),
+            self.rx_audio.clone(),
+            self.audio_tap.clone(),
+        )?));
+        Ok(())
     }
 
     /// Render player in available space
     pub fn render(&mut self, ui: &mut Ui) -> Response {
-        let size = ui.available_size();
+        self.ui_at(ui, ui.available_rect_before_wrap())
+    }
 
-        self.handle_keys(ui);
+    /// Render the player inside `rect` instead of using all the available space like
+    /// [`Player::render`]. The video, subtitles, overlay and OSD are all constrained to `rect`,
+    /// which makes it possible to place multiple players in a grid:
+    ///
+    /// ```ignore
+    /// let size = ui.available_size() / 2.0;
+    /// for (i, player) in players.iter_mut().enumerate() {
+    ///     let min = ui.min_rect().min + vec2((i % 2) as f32 * size.x, (i / 2) as f32 * size.y);
+    ///     player.ui_at(ui, Rect::from_min_size(min, size));
+    /// }
+    /// ```
+    pub fn ui_at(&mut self, ui: &mut Ui, rect: Rect) -> Response {
         self.process_state();
-        let frame_response = self.render_frame(ui);
-        self.render_subtitles(ui);
-        self.render_overlay(ui, &frame_response);
-        if let Some(error) = &self.error {
-            ui.painter().text(
-                pos2(size.x / 2.0, size.y / 2.0),
-                Align2::CENTER_BOTTOM,
-                error,
-                FontId::proportional(30.),
-                Color32::DARK_RED,
-            );
-        }
+        let frame_response = self.render_frame_at(ui, rect);
+        self.handle_zoom(ui, &frame_response);
+        let mut frame_ui = ui.new_child(UiBuilder::new().max_rect(rect));
+        self.render_subtitles(&mut frame_ui);
+        self.render_overlay(&mut frame_ui, &frame_response);
+        self.render_context_menu(&frame_response);
+        // after the overlay, so keys consumed by a focused overlay widget are not handled twice
+        self.handle_keys(ui);
+        self.render_error(&mut frame_ui, rect);
         if self.osd_end < Instant::now() {
             self.osd.take();
         }
         if let Some(osd) = &self.osd {
-            ui.painter().text(
-                pos2(size.x - 10.0, 50.0),
-                Align2::RIGHT_TOP,
-                osd,
-                FontId::proportional(20.),
-                Color32::WHITE,
-            );
+            for (i, line) in osd.lines().enumerate() {
+                frame_ui.painter().text(
+                    pos2(rect.max.x - 10.0, rect.min.y + 50.0 + i as f32 * 24.0),
+                    Align2::RIGHT_TOP,
+                    line,
+                    FontId::proportional(20.),
+                    Color32::WHITE,
+                );
+            }
         }
         if self.debug {
-            self.render_debug(ui, &frame_response);
+            self.render_debug(&mut frame_ui, &frame_response);
         }
         frame_response
     }
 
     fn render_overlay(&mut self, ui: &mut Ui, frame: &Response) {
+        self.state
+            .set_custom_context_menu(self.context_menu.is_some());
         self.overlay.show(ui, frame, &self.state);
     }
+
+    #[cfg(feature = "notifications")]
+    fn render_error(&mut self, ui: &mut Ui, rect: Rect) {
+        if let Some(toasts) = self.toasts.as_mut() {
+            toasts.show(ui.ctx());
+        } else if let Some(error) = &self.error {
+            Self::paint_error(ui, rect, error);
+        }
+    }
+
+    #[cfg(not(feature = "notifications"))]
+    fn render_error(&mut self, ui: &mut Ui, rect: Rect) {
+        if let Some(error) = &self.error {
+            Self::paint_error(ui, rect, error);
+        }
+    }
+
+    fn paint_error(ui: &mut Ui, rect: Rect, error: &str) {
+        ui.painter().text(
+            rect.center(),
+            Align2::CENTER_BOTTOM,
+            error,
+            FontId::proportional(30.),
+            Color32::DARK_RED,
+        );
+    }
+
+    fn render_context_menu(&self, frame: &Response) {
+        let Some(builder) = &self.context_menu else {
+            return;
+        };
+        frame.context_menu(|ui| {
+            for item in builder(&self.state) {
+                if ui.button(&item.label).clicked() {
+                    (item.action)(&self.state);
+                    ui.close();
+                }
+            }
+        });
+    }
 }
 
 impl Widget for &mut Player {
@@ -564,3 +2472,36 @@ impl Widget for &mut Player {
         self.render(ui)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aspect_ratio() {
+        assert_eq!("16:9".parse::<AspectRatio>().unwrap().0, 16.0 / 9.0);
+        assert_eq!("4/3".parse::<AspectRatio>().unwrap().0, 4.0 / 3.0);
+        assert_eq!(" 2.39 ".parse::<AspectRatio>().unwrap().0, 2.39);
+        assert!("16:0".parse::<AspectRatio>().is_err());
+        assert!("wide".parse::<AspectRatio>().is_err());
+    }
+
+    #[test]
+    fn zoom() {
+        assert_eq!(
+            zoom_uv(1.0, vec2(0.3, 0.0)),
+            Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0))
+        );
+        let uv = zoom_uv(2.0, vec2(1.0, -0.1));
+        assert_eq!(uv, Rect::from_min_max(pos2(0.5, 0.15), pos2(1.0, 0.65)));
+        assert_eq!(clamp_pan(4.0, vec2(-1.0, 0.2)), vec2(-0.375, 0.2));
+    }
+
+    #[test]
+    fn avg_fps() {
+        assert_eq!(average_fps(30, Duration::from_millis(500)), None);
+        assert_eq!(average_fps(0, Duration::from_secs(2)), None);
+        assert_eq!(average_fps(2, Duration::from_secs(2)), Some(1.0));
+        assert_eq!(average_fps(60, Duration::from_secs(1)), Some(60.0));
+    }
+}
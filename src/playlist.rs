@@ -0,0 +1,128 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Queue of media paths played one after another by a [`crate::Player`]
+#[derive(Debug, Clone, Default)]
+pub struct Playlist {
+    items: Vec<String>,
+    /// Index of the current item, [None] before the first call to [`Playlist::next`]
+    cursor: Option<usize>,
+}
+
+impl Playlist {
+    /// Create a new playlist, nothing is selected until [`Playlist::next`] is called
+    pub fn new(items: Vec<String>) -> Self {
+        Self {
+            items,
+            cursor: None,
+        }
+    }
+
+    /// Add a media path to the end of the playlist
+    pub fn push(&mut self, path: &str) {
+        self.items.push(path.to_string());
+    }
+
+    /// All media paths in play order
+    pub fn items(&self) -> &[String] {
+        &self.items
+    }
+
+    /// Index of the current item
+    pub fn index(&self) -> Option<usize> {
+        self.cursor
+    }
+
+    /// The current media path
+    pub fn current(&self) -> Option<&str> {
+        self.cursor
+            .and_then(|i| self.items.get(i))
+            .map(String::as_str)
+    }
+
+    /// Move to the next item, returns [None] at the end of the playlist
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<&str> {
+        let next = self.cursor.map(|i| i + 1).unwrap_or(0);
+        if next >= self.items.len() {
+            return None;
+        }
+        self.cursor = Some(next);
+        self.current()
+    }
+
+    /// Move to the previous item, returns [None] at the start of the playlist
+    pub fn prev(&mut self) -> Option<&str> {
+        let prev = self.cursor?.checked_sub(1)?;
+        self.cursor = Some(prev);
+        self.current()
+    }
+
+    /// Move to the item at `index`, returns [None] if it doesn't exist
+    pub fn select(&mut self, index: usize) -> Option<&str> {
+        if index >= self.items.len() {
+            return None;
+        }
+        self.cursor = Some(index);
+        self.current()
+    }
+
+    /// Randomize the play order, the current item is moved to the front
+    pub fn shuffle(&mut self) {
+        let mut seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0)
+            | 1;
+        if let Some(i) = self.cursor.take() {
+            self.items.swap(0, i);
+            self.cursor = Some(0);
+        }
+        let start = self.cursor.map(|_| 1).unwrap_or(0);
+        // Fisher-Yates using xorshift64
+        for i in (start + 1..self.items.len()).rev() {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            let j = start + (seed % (i - start + 1) as u64) as usize;
+            self.items.swap(i, j);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn navigate() {
+        let mut pl = Playlist::new(vec!["a".to_string(), "b".to_string()]);
+        assert_eq!(pl.current(), None);
+        assert_eq!(pl.prev(), None);
+        assert_eq!(pl.next(), Some("a"));
+        assert_eq!(pl.next(), Some("b"));
+        assert_eq!(pl.next(), None);
+        assert_eq!(pl.current(), Some("b"));
+        assert_eq!(pl.prev(), Some("a"));
+        assert_eq!(pl.prev(), None);
+        assert_eq!(pl.index(), Some(0));
+        assert_eq!(pl.select(1), Some("b"));
+        assert_eq!(pl.select(2), None);
+        assert_eq!(pl.index(), Some(1));
+    }
+
+    #[test]
+    fn shuffle() {
+        let items = (0..20).map(|i| i.to_string()).collect::<Vec<_>>();
+        let mut pl = Playlist::new(items.clone());
+        pl.next();
+        pl.next();
+        pl.shuffle();
+        assert_eq!(pl.current(), Some("1"));
+        assert_eq!(pl.index(), Some(0));
+        let mut shuffled = pl.items().to_vec();
+        shuffled.sort();
+        let mut sorted = items;
+        sorted.sort();
+        assert_eq!(shuffled, sorted);
+    }
+}
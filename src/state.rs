@@ -1,9 +1,21 @@
-use crate::PlayerState;
-use std::sync::Arc;
+use crate::stream::{StreamInfo, StreamType, VideoFilters};
+use crate::{PlayerState, ThumbnailCache};
+use egui::TextureHandle;
+use egui::load::SizedTexture;
 use std::sync::atomic::{
     AtomicBool, AtomicI8, AtomicI16, AtomicI64, AtomicIsize, AtomicU8, AtomicU16, AtomicU32,
     AtomicU64, Ordering,
 };
+use std::sync::{Arc, Mutex, RwLock};
+
+/// A named position in the media
+#[derive(Clone, Debug, PartialEq)]
+pub struct Chapter {
+    /// Chapter title
+    pub title: String,
+    /// Chapter start position in seconds
+    pub start_seconds: f64,
+}
 
 /// Shared playback state
 #[derive(Clone, Debug)]
@@ -13,12 +25,37 @@ pub struct SharedPlaybackState {
     speed: Arc<AtomicU8>,
     mute: Arc<AtomicBool>,
     looping: Arc<AtomicBool>,
+    maintain_aspect: Arc<AtomicBool>,
+    /// Stream metadata has been received from the decoder
+    ready: Arc<AtomicBool>,
     duration: Arc<AtomicU64>,
-
+    /// Decoded media ahead of the playback position
+    buffered_duration: Arc<AtomicU64>,
+    /// Pending seek target (milliseconds), -1 when no seek is queued
+    seek: Arc<AtomicI64>,
+    /// Incremented by every queued seek, decoded frames carry the generation they belong to
+    seek_generation: Arc<AtomicU64>,
+    /// Seek requested by an overlay (milliseconds), -1 when nothing is requested
+    seek_request: Arc<AtomicI64>,
+    /// A/B loop region start and end (ms, -1 means not set)
+    loop_a: Arc<AtomicI64>,
+    loop_b: Arc<AtomicI64>,
+
+    /// PTS of the start of the media (milliseconds)
+    start_time: Arc<AtomicI64>,
     video_pts: Arc<AtomicI64>,
     audio_pts: Arc<AtomicI64>,
+    /// The audio clock is set from the next samples at or after this PTS (milliseconds),
+    /// -1 once it runs from the output. Pending from the start and after each seek
+    audio_resync: Arc<AtomicI64>,
+    audio_delay: Arc<AtomicI64>,
+    subtitle_delay: Arc<AtomicI64>,
     subtitle_pts: Arc<AtomicI64>,
 
+    /// RMS level of the left / right output channel, 0-255 for 0.0-1.0
+    audio_level_l: Arc<AtomicU8>,
+    audio_level_r: Arc<AtomicU8>,
+
     // Current audio config
     pub sample_rate: Arc<AtomicU32>,
     pub channels: Arc<AtomicU8>,
@@ -27,6 +64,39 @@ pub struct SharedPlaybackState {
     pub selected_video: Arc<AtomicIsize>,
     pub selected_audio: Arc<AtomicIsize>,
     pub selected_subtitle: Arc<AtomicIsize>,
+    /// Subtitle stream shown at the top of the frame together with the primary one, -1 for none
+    pub selected_secondary_subtitle: Arc<AtomicIsize>,
+
+    /// All streams available in the media
+    streams: Arc<RwLock<Vec<StreamInfo>>>,
+
+    /// Chapters shown on the seekbar
+    chapters: Arc<RwLock<Vec<Chapter>>>,
+
+    /// Seekbar preview thumbnails
+    thumbnails: Arc<RwLock<Option<ThumbnailCache>>>,
+    /// Seekbar position (milliseconds) an overlay wants a preview frame for, -1 for none
+    preview_request: Arc<AtomicI64>,
+    /// Preview frame decoded at an exact seekbar position
+    preview_frame: Arc<RwLock<Option<(f64, SizedTexture)>>>,
+
+    /// Colour adjustments applied by the decoder
+    video_filters: Arc<Mutex<VideoFilters>>,
+    /// Fraction of the native resolution video is decoded to (percent)
+    render_scale: Arc<AtomicU8>,
+    /// Opacity of the overlay controls (percent)
+    overlay_opacity: Arc<AtomicU8>,
+
+    /// Index of the playing playlist item, -1 without a playlist
+    playlist_index: Arc<AtomicIsize>,
+    /// Playlist item requested by an overlay, -1 when nothing is requested
+    playlist_request: Arc<AtomicIsize>,
+    /// An overlay asked the player to show or hide the debug info
+    debug_toggle: Arc<AtomicBool>,
+    /// An overlay asked the player to restart the decoder
+    stream_reload: Arc<AtomicBool>,
+    /// The player shows its own right-click menu, overlays should not add one
+    custom_context_menu: Arc<AtomicBool>,
 }
 
 impl SharedPlaybackState {
@@ -40,15 +110,43 @@ impl SharedPlaybackState {
             speed: Arc::new(AtomicU8::new(20)),
             mute: Arc::new(AtomicBool::new(false)),
             looping: Arc::new(AtomicBool::new(false)),
+            maintain_aspect: Arc::new(AtomicBool::new(true)),
+            ready: Arc::new(AtomicBool::new(false)),
+            start_time: Arc::new(AtomicI64::new(0)),
             video_pts: Arc::new(AtomicI64::new(0)),
             audio_pts: Arc::new(AtomicI64::new(0)),
+            audio_resync: Arc::new(AtomicI64::new(0)),
+            audio_delay: Arc::new(AtomicI64::new(0)),
+            subtitle_delay: Arc::new(AtomicI64::new(0)),
             subtitle_pts: Arc::new(AtomicI64::new(0)),
             duration: Arc::new(AtomicU64::new(0)),
+            buffered_duration: Arc::new(AtomicU64::new(0)),
+            seek: Arc::new(AtomicI64::new(-1)),
+            seek_generation: Arc::new(AtomicU64::new(0)),
+            seek_request: Arc::new(AtomicI64::new(-1)),
+            loop_a: Arc::new(AtomicI64::new(-1)),
+            loop_b: Arc::new(AtomicI64::new(-1)),
+            audio_level_l: Arc::new(AtomicU8::new(0)),
+            audio_level_r: Arc::new(AtomicU8::new(0)),
             sample_rate: Arc::new(AtomicU32::new(48_000)),
             channels: Arc::new(AtomicU8::new(2)),
             selected_video: Arc::new(AtomicIsize::new(-1)),
             selected_audio: Arc::new(AtomicIsize::new(-1)),
             selected_subtitle: Arc::new(AtomicIsize::new(-1)),
+            selected_secondary_subtitle: Arc::new(AtomicIsize::new(-1)),
+            streams: Arc::new(RwLock::new(Vec::new())),
+            chapters: Arc::new(RwLock::new(Vec::new())),
+            thumbnails: Arc::new(RwLock::new(None)),
+            preview_request: Arc::new(AtomicI64::new(-1)),
+            preview_frame: Arc::new(RwLock::new(None)),
+            video_filters: Arc::new(Mutex::new(VideoFilters::default())),
+            render_scale: Arc::new(AtomicU8::new(100)),
+            overlay_opacity: Arc::new(AtomicU8::new(100)),
+            playlist_index: Arc::new(AtomicIsize::new(-1)),
+            playlist_request: Arc::new(AtomicIsize::new(-1)),
+            debug_toggle: Arc::new(AtomicBool::new(false)),
+            stream_reload: Arc::new(AtomicBool::new(false)),
+            custom_context_menu: Arc::new(AtomicBool::new(false)),
         }
     }
 
@@ -123,8 +221,27 @@ impl SharedPlaybackState {
         self.looping.store(looping, Ordering::Relaxed);
     }
 
+    /// If the video is shown at its aspect ratio (fit) instead of stretched to the frame
+    pub fn maintain_aspect(&self) -> bool {
+        self.maintain_aspect.load(Ordering::Relaxed)
+    }
+
+    pub fn set_maintain_aspect(&self, maintain_aspect: bool) {
+        self.maintain_aspect
+            .store(maintain_aspect, Ordering::Relaxed);
+    }
+
+    /// If the media has been probed and playback can begin
+    pub fn ready(&self) -> bool {
+        self.ready.load(Ordering::Relaxed)
+    }
+
+    pub fn set_ready(&self, ready: bool) {
+        self.ready.store(ready, Ordering::Relaxed);
+    }
+
     pub fn duration(&self) -> f64 {
-        self.duration.load(Ordering::Relaxed) as f64 * Self::PTS_SCALE
+        self.duration.load(Ordering::Relaxed) as f64 / Self::PTS_SCALE
     }
 
     pub fn set_duration(&self, new: f64) {
@@ -132,6 +249,271 @@ impl SharedPlaybackState {
             .store((new * Self::PTS_SCALE) as _, Ordering::Relaxed);
     }
 
+    /// Seconds of media decoded ahead of the playback position
+    pub fn buffered_duration(&self) -> f64 {
+        self.buffered_duration.load(Ordering::Relaxed) as f64 / Self::PTS_SCALE
+    }
+
+    pub fn set_buffered_duration(&self, secs: f64) {
+        self.buffered_duration
+            .store((secs.max(0.0) * Self::PTS_SCALE) as _, Ordering::Relaxed);
+    }
+
+    /// Queue a seek to the given position (seconds), picked up by the decoder thread
+    pub fn set_seek(&self, pts: f64) {
+        self.seek_generation.fetch_add(1, Ordering::Relaxed);
+        self.seek
+            .store((pts.max(0.0) * Self::PTS_SCALE) as _, Ordering::Relaxed);
+    }
+
+    /// Take the pending seek position (seconds), if any
+    pub fn take_seek(&self) -> Option<f64> {
+        let seek = self.seek.swap(-1, Ordering::Relaxed);
+        if seek >= 0 {
+            Some(seek as f64 / Self::PTS_SCALE)
+        } else {
+            None
+        }
+    }
+
+    /// Number of seeks queued so far, video frames decoded before the latest seek are
+    /// tagged with a lower generation
+    pub fn seek_generation(&self) -> u64 {
+        self.seek_generation.load(Ordering::Relaxed)
+    }
+
+    /// Ask the player to seek to a position (seconds), used by overlays.
+    /// Unlike [`SharedPlaybackState::set_seek`] this goes through [`crate::Player::seek`]
+    /// so the player drops frames from before the seek
+    pub fn request_seek(&self, secs: f64) {
+        self.seek_request
+            .store((secs.max(0.0) * Self::PTS_SCALE) as _, Ordering::Relaxed);
+    }
+
+    /// Ask the player to seek to a fraction (`0.0` to `1.0`) of the duration, e.g. from a seekbar
+    pub fn request_seek_fraction(&self, frac: f64) {
+        self.request_seek(frac.clamp(0.0, 1.0) * self.duration());
+    }
+
+    /// Take the seek position (seconds) requested by an overlay, if any
+    pub fn take_seek_request(&self) -> Option<f64> {
+        let seek = self.seek_request.swap(-1, Ordering::Relaxed);
+        (seek >= 0).then(|| seek as f64 / Self::PTS_SCALE)
+    }
+
+    /// Set the A/B loop region (seconds), [None] clears the point
+    pub fn set_ab_loop(&self, a: Option<f64>, b: Option<f64>) {
+        let to_pts = |v: Option<f64>| {
+            v.map(|v| (v.max(0.0) * Self::PTS_SCALE) as i64)
+                .unwrap_or(-1)
+        };
+        self.loop_a.store(to_pts(a), Ordering::Relaxed);
+        self.loop_b.store(to_pts(b), Ordering::Relaxed);
+    }
+
+    /// A/B loop region (seconds)
+    pub fn ab_loop(&self) -> (Option<f64>, Option<f64>) {
+        let from_pts = |v: i64| (v >= 0).then(|| v as f64 / Self::PTS_SCALE);
+        (
+            from_pts(self.loop_a.load(Ordering::Relaxed)),
+            from_pts(self.loop_b.load(Ordering::Relaxed)),
+        )
+    }
+
+    /// Set the list of streams available in the media
+    pub fn set_streams(&self, streams: Vec<StreamInfo>) {
+        if let Ok(mut s) = self.streams.write() {
+            *s = streams;
+        }
+    }
+
+    fn streams_of_type(&self, kind: StreamType) -> Vec<StreamInfo> {
+        self.streams
+            .read()
+            .map(|s| s.iter().filter(|s| s.r#type == kind).cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// All audio streams available in the media
+    pub fn available_audio_streams(&self) -> Vec<StreamInfo> {
+        self.streams_of_type(StreamType::Audio)
+    }
+
+    /// All subtitle streams available in the media
+    pub fn available_subtitle_streams(&self) -> Vec<StreamInfo> {
+        self.streams_of_type(StreamType::Subtitle)
+    }
+
+    /// RMS level (0.0-1.0) of the left and right audio output
+    pub fn audio_level(&self) -> (f32, f32) {
+        (
+            self.audio_level_l.load(Ordering::Relaxed) as f32 / u8::MAX as f32,
+            self.audio_level_r.load(Ordering::Relaxed) as f32 / u8::MAX as f32,
+        )
+    }
+
+    /// Update the audio level from interleaved output samples,
+    /// mono output is reported on both channels
+    pub fn update_audio_level(&self, samples: &[f32], channels: usize) {
+        let rms = |chan: usize| {
+            let (sum, n) = samples
+                .iter()
+                .skip(chan)
+                .step_by(channels.max(1))
+                .fold((0.0, 0), |(sum, n), s| (sum + s * s, n + 1));
+            if n == 0 { 0.0 } else { (sum / n as f32).sqrt() }
+        };
+        let l = rms(0);
+        let r = if channels > 1 { rms(1) } else { l };
+        let to_u8 = |v: f32| (v.clamp(0.0, 1.0) * u8::MAX as f32).round() as u8;
+        self.audio_level_l.store(to_u8(l), Ordering::Relaxed);
+        self.audio_level_r.store(to_u8(r), Ordering::Relaxed);
+    }
+
+    /// Index of the playing playlist item
+    pub fn playlist_index(&self) -> Option<usize> {
+        usize::try_from(self.playlist_index.load(Ordering::Relaxed)).ok()
+    }
+
+    pub fn set_playlist_index(&self, index: Option<usize>) {
+        self.playlist_index
+            .store(index.map(|i| i as isize).unwrap_or(-1), Ordering::Relaxed);
+    }
+
+    /// Ask the player to open a playlist item, e.g. when it is clicked in an overlay
+    pub fn request_playlist_index(&self, index: usize) {
+        self.playlist_request.store(index as _, Ordering::Relaxed);
+    }
+
+    /// Take the pending playlist item request, if any
+    pub fn take_playlist_request(&self) -> Option<usize> {
+        usize::try_from(self.playlist_request.swap(-1, Ordering::Relaxed)).ok()
+    }
+
+    /// Ask the player to show or hide its debug info
+    pub fn request_debug_toggle(&self) {
+        self.debug_toggle.store(true, Ordering::Relaxed);
+    }
+
+    /// Take the pending debug toggle request
+    pub fn take_debug_toggle(&self) -> bool {
+        self.debug_toggle.swap(false, Ordering::Relaxed)
+    }
+
+    /// Ask the player to restart the decoder from the same input, see
+    /// [`crate::Player::reload_stream`]
+    pub fn request_stream_reload(&self) {
+        self.stream_reload.store(true, Ordering::Relaxed);
+    }
+
+    /// Take the pending stream reload request
+    pub fn take_stream_reload(&self) -> bool {
+        self.stream_reload.swap(false, Ordering::Relaxed)
+    }
+
+    /// If the player has a right-click menu set with [`crate::Player::with_context_menu`]
+    pub fn custom_context_menu(&self) -> bool {
+        self.custom_context_menu.load(Ordering::Relaxed)
+    }
+
+    pub fn set_custom_context_menu(&self, custom: bool) {
+        self.custom_context_menu.store(custom, Ordering::Relaxed);
+    }
+
+    /// Set the chapters of the media
+    pub fn set_chapters(&self, chapters: Vec<Chapter>) {
+        if let Ok(mut c) = self.chapters.write() {
+            *c = chapters;
+        }
+    }
+
+    /// Chapters of the media, ordered by start time
+    pub fn chapters(&self) -> Vec<Chapter> {
+        self.chapters.read().map(|c| c.clone()).unwrap_or_default()
+    }
+
+    /// Set the thumbnail cache used for seekbar previews
+    pub fn set_thumbnails(&self, cache: ThumbnailCache) {
+        if let Ok(mut t) = self.thumbnails.write() {
+            t.replace(cache);
+        }
+    }
+
+    /// Opacity of the overlay controls (0.0-1.0)
+    pub fn overlay_opacity(&self) -> f32 {
+        self.overlay_opacity.load(Ordering::Relaxed) as f32 / 100.0
+    }
+
+    pub fn set_overlay_opacity(&self, opacity: f32) {
+        let opacity = (opacity.clamp(0.0, 1.0) * 100.0).round();
+        self.overlay_opacity.store(opacity as _, Ordering::Relaxed);
+    }
+
+    /// Ask the player to decode a preview frame at `pts` (seconds)
+    pub fn request_preview(&self, pts: f64) {
+        self.preview_request
+            .store((pts.max(0.0) * Self::PTS_SCALE) as _, Ordering::Relaxed);
+    }
+
+    /// Take the pending preview request, if any
+    pub fn take_preview_request(&self) -> Option<f64> {
+        let pts = self.preview_request.swap(-1, Ordering::Relaxed);
+        (pts >= 0).then(|| pts as f64 / Self::PTS_SCALE)
+    }
+
+    /// Latest preview frame, returns the PTS (seconds) it shows and the texture
+    pub fn preview_frame(&self) -> Option<(f64, SizedTexture)> {
+        *self.preview_frame.read().ok()?
+    }
+
+    pub fn set_preview_frame(&self, frame: Option<(f64, SizedTexture)>) {
+        if let Ok(mut f) = self.preview_frame.write() {
+            *f = frame;
+        }
+    }
+
+    /// Colour adjustments applied to decoded video
+    pub fn video_filters(&self) -> VideoFilters {
+        self.video_filters.lock().map(|f| *f).unwrap_or_default()
+    }
+
+    pub fn set_video_filters(&self, filters: VideoFilters) {
+        if let Ok(mut f) = self.video_filters.lock() {
+            *f = filters;
+        }
+    }
+
+    /// Fraction of the native video resolution frames are scaled to (0.1-1.0)
+    pub fn render_scale(&self) -> f32 {
+        self.render_scale.load(Ordering::Relaxed) as f32 / 100.0
+    }
+
+    pub fn set_render_scale(&self, scale: f32) {
+        let scale = (scale.clamp(0.1, 1.0) * 100.0).round();
+        self.render_scale.store(scale as _, Ordering::Relaxed);
+    }
+
+    /// Get the preview thumbnail nearest to `pts` (seconds)
+    pub fn thumbnail_at(&self, pts: f64) -> Option<(f64, TextureHandle)> {
+        self.thumbnails.read().ok()?.as_ref()?.get(pts)
+    }
+
+    /// PTS (seconds) of the start of the media, non-zero for e.g. MPEG-TS
+    pub fn start_time(&self) -> f64 {
+        self.start_time.load(Ordering::Relaxed) as f64 / Self::PTS_SCALE
+    }
+
+    pub fn set_start_time(&self, secs: f64) {
+        self.start_time
+            .store((secs * Self::PTS_SCALE) as _, Ordering::Relaxed);
+    }
+
+    /// Playback position (seconds) from the start of the media, use this instead of
+    /// [`SharedPlaybackState::video_pts`] for seekbars and time displays
+    pub fn elapsed(&self) -> f64 {
+        (self.video_pts() - self.start_time()).max(0.0)
+    }
+
     pub fn video_pts(&self) -> f64 {
         self.video_pts.load(Ordering::Relaxed) as f64 / Self::PTS_SCALE
     }
@@ -155,13 +537,67 @@ impl SharedPlaybackState {
             .fetch_add((new * Self::PTS_SCALE) as _, Ordering::Relaxed);
     }
 
+    /// Set the audio clock again from the first samples at or after `pts` (seconds),
+    /// called by the decoder after seeking or re-opening the input
+    pub fn request_audio_resync(&self, pts: f64) {
+        self.audio_resync
+            .store((pts.max(0.0) * Self::PTS_SCALE) as _, Ordering::Relaxed);
+    }
+
+    /// The audio clock waits to be set from the next samples, queued samples are stale
+    pub fn audio_resync_pending(&self) -> bool {
+        self.audio_resync.load(Ordering::Relaxed) >= 0
+    }
+
+    /// Called by the audio output for each block of samples taken from the decoder.
+    /// While a resync is pending the clock is set from the first block which reaches the
+    /// resync PTS, plus the output `latency` (seconds). Returns false for blocks from
+    /// before the resync PTS, which should be dropped
+    pub fn sync_audio_clock(&self, pts: f64, duration: f64, latency: f64) -> bool {
+        let target = self.audio_resync.load(Ordering::Relaxed);
+        if target < 0 {
+            return true;
+        }
+        if pts + duration < target as f64 / Self::PTS_SCALE {
+            return false;
+        }
+        self.audio_resync.store(-1, Ordering::Relaxed);
+        self.set_audio_pts(pts + latency);
+        true
+    }
+
+    /// Audio delay (seconds), positive values play audio later
+    pub fn audio_delay(&self) -> f64 {
+        self.audio_delay.load(Ordering::Relaxed) as f64 / Self::PTS_SCALE
+    }
+
+    pub fn set_audio_delay(&self, delay: f64) {
+        self.audio_delay
+            .store((delay * Self::PTS_SCALE) as _, Ordering::Relaxed);
+    }
+
+    /// Audio PTS adjusted for the audio delay, used to compare with the video PTS
+    pub fn synced_audio_pts(&self) -> f64 {
+        self.audio_pts() - self.audio_delay()
+    }
+
+    /// Subtitle delay (seconds), positive values show subtitles later
+    pub fn subtitle_delay(&self) -> f64 {
+        self.subtitle_delay.load(Ordering::Relaxed) as f64 / Self::PTS_SCALE
+    }
+
+    pub fn set_subtitle_delay(&self, delay: f64) {
+        self.subtitle_delay
+            .store((delay * Self::PTS_SCALE) as _, Ordering::Relaxed);
+    }
+
     pub fn subtitle_pts(&self) -> f64 {
         self.subtitle_pts.load(Ordering::Relaxed) as f64 / Self::PTS_SCALE
     }
 
     pub fn set_subtitle_pts(&self, new: f64) {
         self.subtitle_pts
-            .store((new / Self::PTS_SCALE) as _, Ordering::Relaxed);
+            .store((new * Self::PTS_SCALE) as _, Ordering::Relaxed);
     }
 }
 
@@ -184,6 +620,13 @@ mod tests {
         assert_eq!(state.volume(), 0.0);
     }
 
+    #[test]
+    fn duration() {
+        let state = SharedPlaybackState::new();
+        state.set_duration(90.5);
+        assert_eq!(state.duration(), 90.5);
+    }
+
     #[test]
     fn speed() {
         let state = SharedPlaybackState::new();
@@ -198,4 +641,139 @@ mod tests {
         state.set_speed(99.0);
         assert_eq!((state.speed() * 10.0).round(), 100.0);
     }
+
+    #[test]
+    fn render_scale() {
+        let state = SharedPlaybackState::new();
+        assert_eq!(state.render_scale(), 1.0);
+        state.set_render_scale(0.5);
+        assert_eq!(state.render_scale(), 0.5);
+        state.set_render_scale(0.0);
+        assert_eq!(state.render_scale(), 0.1);
+        state.set_render_scale(2.0);
+        assert_eq!(state.render_scale(), 1.0);
+    }
+
+    #[test]
+    fn pts() {
+        let state = SharedPlaybackState::new();
+        state.set_video_pts(12.345);
+        assert_eq!(state.video_pts(), 12.345);
+        state.set_audio_pts(1.5);
+        assert_eq!(state.audio_pts(), 1.5);
+        state.incr_audio_pts(0.25);
+        assert_eq!(state.audio_pts(), 1.75);
+        state.set_subtitle_pts(42.5);
+        assert_eq!(state.subtitle_pts(), 42.5);
+    }
+
+    #[test]
+    fn audio_resync() {
+        // MPEG-TS style stream starting at 10s
+        let state = SharedPlaybackState::new();
+        state.set_start_time(10.0);
+        assert!(state.audio_resync_pending());
+        assert!(state.sync_audio_clock(10.0, 0.02, 0.0));
+        assert!(!state.audio_resync_pending());
+        assert_eq!(state.audio_pts(), 10.0);
+        // the video is in sync with the seeded clock
+        state.set_video_pts(10.0);
+        assert_eq!(state.synced_audio_pts() - state.video_pts(), 0.0);
+
+        // later samples don't move the clock, the output advances it
+        assert!(state.sync_audio_clock(10.5, 0.02, 0.0));
+        assert_eq!(state.audio_pts(), 10.0);
+
+        // samples still queued from before the seek are dropped
+        state.request_audio_resync(40.0);
+        assert!(!state.sync_audio_clock(10.52, 0.02, 0.0));
+        assert!(state.audio_resync_pending());
+        assert!(state.sync_audio_clock(39.99, 0.02, 0.05));
+        assert!((state.audio_pts() - 40.04).abs() < 1e-9);
+    }
+
+    #[test]
+    fn audio_level() {
+        let state = SharedPlaybackState::new();
+        assert_eq!(state.audio_level(), (0.0, 0.0));
+        state.update_audio_level(&[1.0, 0.0, -1.0, 0.0], 2);
+        assert_eq!(state.audio_level(), (1.0, 0.0));
+        state.update_audio_level(&[0.5, -0.5], 1);
+        let (l, r) = state.audio_level();
+        assert!((l - 0.5).abs() < 0.01);
+        assert_eq!(l, r);
+    }
+
+    #[test]
+    fn playlist_request() {
+        let state = SharedPlaybackState::new();
+        assert_eq!(state.playlist_index(), None);
+        state.set_playlist_index(Some(2));
+        assert_eq!(state.playlist_index(), Some(2));
+        assert_eq!(state.take_playlist_request(), None);
+        state.request_playlist_index(1);
+        assert_eq!(state.take_playlist_request(), Some(1));
+        assert_eq!(state.take_playlist_request(), None);
+    }
+
+    #[test]
+    fn ab_loop() {
+        let state = SharedPlaybackState::new();
+        assert_eq!(state.ab_loop(), (None, None));
+        state.set_ab_loop(Some(1.5), None);
+        assert_eq!(state.ab_loop(), (Some(1.5), None));
+        state.set_ab_loop(Some(1.5), Some(4.25));
+        assert_eq!(state.ab_loop(), (Some(1.5), Some(4.25)));
+        state.set_ab_loop(None, None);
+        assert_eq!(state.ab_loop(), (None, None));
+    }
+
+    #[test]
+    fn audio_delay() {
+        let state = SharedPlaybackState::new();
+        state.set_audio_pts(10.0);
+        assert_eq!(state.synced_audio_pts(), 10.0);
+        state.set_audio_delay(0.25);
+        assert_eq!(state.audio_delay(), 0.25);
+        assert_eq!(state.synced_audio_pts(), 9.75);
+        state.set_audio_delay(-0.05);
+        assert_eq!(state.synced_audio_pts(), 10.05);
+
+        state.set_subtitle_delay(-0.1);
+        assert_eq!(state.subtitle_delay(), -0.1);
+        assert_eq!(state.audio_delay(), -0.05);
+    }
+
+    #[test]
+    fn elapsed() {
+        let state = SharedPlaybackState::new();
+        state.set_start_time(1.4);
+        state.set_video_pts(11.4);
+        assert_eq!(state.elapsed(), 10.0);
+        state.set_video_pts(0.0);
+        assert_eq!(state.elapsed(), 0.0);
+    }
+
+    #[test]
+    fn seek() {
+        let state = SharedPlaybackState::new();
+        assert_eq!(state.take_seek(), None);
+        state.set_seek(12.5);
+        assert_eq!(state.take_seek(), Some(12.5));
+        assert_eq!(state.take_seek(), None);
+        state.set_seek(-3.0);
+        assert_eq!(state.take_seek(), Some(0.0));
+        assert_eq!(state.seek_generation(), 2);
+
+        state.set_duration(120.0);
+        assert_eq!(state.take_seek_request(), None);
+        state.request_seek_fraction(0.25);
+        assert_eq!(state.take_seek_request(), Some(30.0));
+        assert_eq!(state.take_seek_request(), None);
+        state.request_seek(12.5);
+        assert_eq!(state.take_seek_request(), Some(12.5));
+        // requests don't touch the decoder seek
+        assert_eq!(state.take_seek(), None);
+        assert_eq!(state.seek_generation(), 2);
+    }
 }
@@ -1,11 +1,12 @@
 use crate::stream::{
     DecoderInfo, MediaDecoderImpl, MediaDecoderThreadData, StreamInfo, StreamType,
 };
-use anyhow::Result;
+use anyhow::{Result, bail};
 use log::{error, info};
 use objc2::rc::Retained;
 use objc2_av_foundation::AVURLAsset;
 use objc2_foundation::{NSString, NSURL};
+use std::sync::atomic::Ordering;
 use std::thread::JoinHandle;
 
 /// Internal AVFoundation decoder thread instance
@@ -17,6 +18,9 @@ struct DecoderThread {
 
 impl DecoderThread {
     fn tick(&mut self) -> Result<()> {
+        if self.data.stop_flag.load(Ordering::Relaxed) {
+            bail!("Decoder cancelled");
+        }
         if self.info.is_none() {
             unsafe {
                 self.probe()?;
@@ -46,6 +50,7 @@ impl DecoderThread {
                     }
                 })
                 .unwrap_or(0.0),
+            start_time: 0.0,
             streams: tracks
                 .iter()
                 .filter_map(|t| {
@@ -69,8 +74,10 @@ impl DecoderThread {
                                 height: size.height as _,
                                 fps: fps as _,
                                 language: None,
+                                bitrate: unsafe { t.estimatedDataRate() } as _,
+                                sar: None,
                             })
-                        },
+                        }
                         "soun" => {
                             let lang = unsafe { t.languageCode() };
                             Some(StreamInfo {
@@ -87,12 +94,16 @@ impl DecoderThread {
                                 height: 0,
                                 fps: 0.0,
                                 language: lang.map(|l| l.to_string()),
+                                bitrate: unsafe { t.estimatedDataRate() } as _,
+                                sar: None,
                             })
-                        },
+                        }
                         _ => None,
                     }
                 })
                 .collect(),
+            metadata: Default::default(),
+            chapters: Vec::new(),
         });
         Ok(())
     }
@@ -110,6 +121,9 @@ impl AvFoundationDecoder {
 
 impl MediaDecoderImpl for AvFoundationDecoder {
     fn start(&mut self) -> Result<JoinHandle<()>> {
+        if self.data.bytes.is_some() {
+            bail!("In-memory media is not supported by AVFoundation");
+        }
         let mut instance = DecoderThread {
             data: self.data.clone(),
             asset: unsafe {
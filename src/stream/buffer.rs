@@ -0,0 +1,199 @@
+use crate::stream::VideoFrame;
+use anyhow::Result;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Decoded video frames waiting to be shown, filled by a caching thread so a decoder
+/// with variable latency (e.g. HLS segment downloads) doesn't starve the display
+#[derive(Clone)]
+pub struct VideoFrameBuffer {
+    frames: Arc<Mutex<VecDeque<VideoFrame>>>,
+    /// Max span of the buffered frames (milliseconds)
+    max_duration: Arc<AtomicU64>,
+    /// The decoder has finished, no more frames will be buffered
+    closed: Arc<AtomicBool>,
+    /// Frames from an older seek generation are dropped
+    generation: Arc<AtomicU64>,
+}
+
+impl VideoFrameBuffer {
+    /// Seconds of video buffered by default
+    pub const DEFAULT_DURATION: f32 = 0.5;
+
+    /// Upper bound on buffered frames, for streams without frame durations
+    const MAX_FRAMES: usize = 240;
+
+    pub fn new(max_duration: f32) -> Self {
+        let ret = Self {
+            frames: Arc::new(Mutex::new(VecDeque::new())),
+            max_duration: Arc::new(AtomicU64::new(0)),
+            closed: Arc::new(AtomicBool::new(false)),
+            generation: Arc::new(AtomicU64::new(0)),
+        };
+        ret.set_max_duration(max_duration);
+        ret
+    }
+
+    /// Start a thread which moves frames from the decoder channel into the buffer.
+    /// `rx_resized` receives a replacement channel when the decoder resizes it
+    pub fn spawn(
+        rx: Receiver<VideoFrame>,
+        rx_resized: Receiver<Receiver<VideoFrame>>,
+        max_duration: f32,
+    ) -> Result<Self> {
+        let buffer = Self::new(max_duration);
+        let cache = buffer.clone();
+        std::thread::Builder::new()
+            .name("video-frame-buffer".to_string())
+            .spawn(move || {
+                let mut rx = rx;
+                // stop once the player dropped its handle
+                while Arc::strong_count(&cache.frames) > 1 {
+                    if let Ok(r) = rx_resized.try_recv() {
+                        rx = r;
+                    }
+                    if cache.is_full() {
+                        std::thread::sleep(Duration::from_millis(5));
+                        continue;
+                    }
+                    match rx.recv_timeout(Duration::from_millis(10)) {
+                        Ok(frame) => cache.push(frame),
+                        Err(RecvTimeoutError::Timeout) => continue,
+                        Err(RecvTimeoutError::Disconnected) => {
+                            // the old channel is closed when the decoder resizes it
+                            if let Ok(r) = rx_resized.try_recv() {
+                                rx = r;
+                                continue;
+                            }
+                            break;
+                        }
+                    }
+                }
+                cache.closed.store(true, Ordering::Relaxed);
+            })?;
+        Ok(buffer)
+    }
+
+    /// Max seconds of video held in the buffer
+    pub fn max_duration(&self) -> f32 {
+        self.max_duration.load(Ordering::Relaxed) as f32 / 1000.0
+    }
+
+    pub fn set_max_duration(&self, secs: f32) {
+        self.max_duration
+            .store((secs.max(0.0) * 1000.0) as u64, Ordering::Relaxed);
+    }
+
+    /// Seconds of video between the first and last buffered frame
+    pub fn duration(&self) -> f64 {
+        let Ok(frames) = self.frames.lock() else {
+            return 0.0;
+        };
+        match (frames.front(), frames.back()) {
+            (Some(first), Some(last)) => (last.pts + last.duration - first.pts).max(0.0),
+            _ => 0.0,
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        let len = self.frames.lock().map(|f| f.len()).unwrap_or(0);
+        len >= Self::MAX_FRAMES || self.duration() >= self.max_duration() as f64
+    }
+
+    fn push(&self, frame: VideoFrame) {
+        if let Ok(mut frames) = self.frames.lock()
+            && frame.generation >= self.generation.load(Ordering::Relaxed)
+        {
+            frames.push_back(frame);
+        }
+    }
+
+    /// Drop all buffered frames after a seek, along with frames from before seek
+    /// `generation` which are still in the decoder channel
+    pub fn clear(&self, generation: u64) {
+        self.generation.store(generation, Ordering::Relaxed);
+        if let Ok(mut frames) = self.frames.lock() {
+            frames.clear();
+        }
+    }
+
+    /// The decoder has finished and every buffered frame was taken
+    pub fn is_finished(&self) -> bool {
+        self.closed.load(Ordering::Relaxed) && self.frames.lock().is_ok_and(|f| f.is_empty())
+    }
+
+    /// PTS of the next buffered frame
+    pub fn front_pts(&self) -> Option<f64> {
+        self.frames.lock().ok()?.front().map(|f| f.pts)
+    }
+
+    /// Take the next frame which is still due at the playback `clock` (seconds),
+    /// frames which ended before `clock` are dropped without being shown
+    pub fn next_frame_due(&self, clock: f64) -> Option<VideoFrame> {
+        let mut frames = self.frames.lock().ok()?;
+        while let Some(frame) = frames.pop_front() {
+            if frame.pts + frame.duration >= clock {
+                return Some(frame);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use egui::ColorImage;
+
+    fn frame(pts: f64) -> VideoFrame {
+        VideoFrame {
+            data: ColorImage::default(),
+            stream_index: 0,
+            pts,
+            duration: 0.04,
+            generation: 0,
+        }
+    }
+
+    #[test]
+    fn next_frame_due() {
+        let buffer = VideoFrameBuffer::new(1.0);
+        for i in 0..10 {
+            buffer.push(frame(i as f64 * 0.04));
+        }
+        assert!((buffer.duration() - 0.4).abs() < 1e-9);
+        assert!(!buffer.is_full());
+
+        let f = buffer.next_frame_due(f64::NEG_INFINITY).unwrap();
+        assert_eq!(f.pts, 0.0);
+        // frames which ended before the clock are skipped
+        let f = buffer.next_frame_due(0.2).unwrap();
+        assert!((f.pts - 0.16).abs() < 1e-9);
+        assert!((buffer.front_pts().unwrap() - 0.2).abs() < 1e-9);
+        assert!(buffer.next_frame_due(1.0).is_none());
+        assert!(!buffer.is_finished());
+        buffer.closed.store(true, Ordering::Relaxed);
+        assert!(buffer.is_finished());
+    }
+
+    #[test]
+    fn clear_drops_stale_frames() {
+        let buffer = VideoFrameBuffer::new(1.0);
+        buffer.push(frame(10.0));
+        // seek back to 2s, frames decoded before the seek are still arriving
+        buffer.clear(1);
+        assert!(buffer.front_pts().is_none());
+        buffer.push(frame(10.04));
+        assert!(buffer.next_frame_due(2.0).is_none());
+
+        buffer.push(VideoFrame {
+            generation: 1,
+            ..frame(2.0)
+        });
+        let f = buffer.next_frame_due(2.0).unwrap();
+        assert_eq!(f.pts, 2.0);
+    }
+}
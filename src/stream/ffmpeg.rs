@@ -1,28 +1,46 @@
+use crate::stream::filter::EqFilter;
 use crate::stream::{
-    AudioSamples, DecoderInfo, MediaDecoderImpl, MediaDecoderThreadData, StreamInfo,
-    SubtitlePacket, VideoFrame,
+    AudioSamples, ChapterInfo, DecoderInfo, HwDecoderPref, MediaDecoderImpl,
+    MediaDecoderThreadData, MediaMetadata, StreamInfo, StreamingOptions, SubtitlePacket,
+    VideoFrame,
 };
 use anyhow::{Result, bail};
 use egui::{Color32, ColorImage, Vec2};
 use ffmpeg_rs_raw::ffmpeg_sys_the_third::{
-    AV_NOPTS_VALUE, AVMediaType, AVPixelFormat, AVSampleFormat, av_get_bytes_per_sample,
-    av_get_pix_fmt_name, av_get_sample_fmt_name, av_q2d, avcodec_get_name,
+    AV_DICT_IGNORE_SUFFIX, AV_DISPOSITION_ATTACHED_PIC, AV_NOPTS_VALUE, AV_TIME_BASE,
+    AVCodecContext, AVDiscard, AVFrame, AVHWDeviceContext, AVHWDeviceType, AVMediaType, AVPacket,
+    AVPixelFormat, AVSEEK_FLAG_BACKWARD, AVSampleFormat, AVSubtitle, AVSubtitleType, av_dict_get,
+    av_get_pix_fmt_name, av_get_sample_fmt_name, av_hwdevice_get_type_name, av_q2d, av_seek_frame,
+    avcodec_decode_subtitle2, avcodec_get_name, avsubtitle_free,
 };
 use ffmpeg_rs_raw::{
     AudioFifo, AvFrameRef, AvPacketRef, Decoder, Demuxer, DemuxerInfo, Resample, Scaler,
     StreamType, get_frame_from_hw, rstr,
 };
-use log::{error, warn};
-use std::io::Write;
+use log::{error, info, warn};
+use std::collections::HashMap;
+use std::io::{Cursor, Write};
 use std::mem::transmute;
+use std::ptr;
+use std::sync::Arc;
 use std::sync::atomic::Ordering;
+use std::sync::mpsc::{TrySendError, sync_channel};
 use std::thread::JoinHandle;
 
 fn video_frame_to_image(frame: &AvFrameRef) -> Result<ColorImage> {
+    let format: AVPixelFormat = unsafe { transmute(frame.format) };
+    let pixels = if format == AVPixelFormat::AV_PIX_FMT_RGBA && frame.linesize[0] == frame.width * 4
+    {
+        // fast path for tightly packed frames, copy the whole image as one slice
+        let len = frame.width as usize * frame.height as usize;
+        unsafe { std::slice::from_raw_parts::<Color32>(frame.data[0] as *const _, len) }.to_vec()
+    } else {
+        map_frame_to_pixels(frame)?
+    };
     Ok(ColorImage {
         source_size: Vec2::new(frame.width as _, frame.height as _),
         size: [frame.width as _, frame.height as _],
-        pixels: map_frame_to_pixels(frame)?,
+        pixels,
     })
 }
 
@@ -73,18 +91,56 @@ struct DecoderThread {
     resample: Resample,
     audio_fifo: AudioFifo,
     info: Option<DemuxerInfo>,
+    /// Audio stream currently being decoded
+    active_audio: isize,
+    /// Preferred hardware decoder
+    hw_pref: HwDecoderPref,
+    /// If the hardware decoder failed to open, all decoders are software after this
+    hw_failed: bool,
+    /// End time of the last decoded video frame (seconds)
+    last_video_pts: f64,
+    /// Colour adjustment filter, [None] when no adjustments are set
+    filter: Option<EqFilter>,
+    /// Capacity of the video frame channel
+    video_capacity: usize,
+    /// Non-reference video frames are skipped for fast playback
+    skip_nonref: bool,
+    /// Seek generation of the frames being decoded
+    seek_generation: u64,
 }
 
 impl DecoderThread {
     fn tick(&mut self) -> Result<()> {
+        if self.data.stop_flag.load(Ordering::Relaxed) {
+            bail!("Decoder cancelled");
+        }
         if self.info.is_none() {
             self.probe()?;
         }
 
+        if let Some(pts) = self.data.playback.take_seek() {
+            // a seek queued after this one bumps the generation again and is taken next tick
+            self.seek_generation = self.data.playback.seek_generation();
+            self.seek(pts)?;
+        }
+        self.check_selected_streams()?;
+        self.update_frame_discard();
+
         let (pkt, _) = unsafe { self.demuxer.get_packet()? };
         let v_index = self.data.playback.selected_video.load(Ordering::Relaxed);
         let a_index = self.data.playback.selected_audio.load(Ordering::Relaxed);
-        // let s_index = self.data.selected_subtitle.load(Ordering::Relaxed);
+        let s_index = self.data.playback.selected_subtitle.load(Ordering::Relaxed);
+        let s2_index = self
+            .data
+            .playback
+            .selected_secondary_subtitle
+            .load(Ordering::Relaxed);
+        if let Some(pkt) = pkt.as_ref()
+            && (pkt.stream_index == s_index as _ || pkt.stream_index == s2_index as _)
+        {
+            // subtitles are not decoded into frames
+            return self.send_subtitle(pkt);
+        }
         if let Some(pkt) = pkt.as_ref()
             && !(pkt.stream_index == v_index as _ || pkt.stream_index == a_index as _)
         {
@@ -92,13 +148,206 @@ impl DecoderThread {
             return Ok(());
         }
         self.decode_packet(pkt.as_ref())?;
+        self.data
+            .playback
+            .set_buffered_duration((self.last_video_pts - self.data.playback.video_pts()).max(0.0));
         if pkt.is_none() {
+            if self.data.playback.looping() {
+                // start again from the beginning instead of ending the decode loop
+                info!("Stream ended (EOF), looping");
+                self.reopen()?;
+                return Ok(());
+            }
             bail!("Stream ended (EOF)");
         }
 
         Ok(())
     }
 
+    /// Setup decoders for newly selected streams
+    fn check_selected_streams(&mut self) -> Result<()> {
+        if self.info.is_none() {
+            return Ok(());
+        }
+        let v_index = self.data.playback.selected_video.load(Ordering::Relaxed);
+        let a_index = self.data.playback.selected_audio.load(Ordering::Relaxed);
+        let s_index = self.data.playback.selected_subtitle.load(Ordering::Relaxed);
+        let s2_index = self
+            .data
+            .playback
+            .selected_secondary_subtitle
+            .load(Ordering::Relaxed);
+        self.setup_decoders(&[v_index, a_index, s_index, s2_index])?;
+        if a_index != self.active_audio {
+            // drop samples buffered from the previous audio stream
+            self.audio_fifo = AudioFifo::new(
+                FfmpegDecoder::OUT_SAMPLE_FORMAT,
+                self.data.playback.channels.load(Ordering::Relaxed) as _,
+            )?;
+            self.active_audio = a_index;
+        }
+        Ok(())
+    }
+
+    /// Create a new decoder using the hardware decoder preference
+    fn new_decoder(&self) -> Decoder {
+        let mut decoder = Decoder::new();
+        if !self.hw_failed {
+            match self.hw_pref {
+                HwDecoderPref::Any => decoder.enable_hw_decoder_any(),
+                HwDecoderPref::PreferVaapi => {
+                    decoder.enable_hw_decoder(AVHWDeviceType::AV_HWDEVICE_TYPE_VAAPI)
+                }
+                HwDecoderPref::PreferNvdec => {
+                    decoder.enable_hw_decoder(AVHWDeviceType::AV_HWDEVICE_TYPE_CUDA)
+                }
+                HwDecoderPref::PreferVideoToolbox => {
+                    decoder.enable_hw_decoder(AVHWDeviceType::AV_HWDEVICE_TYPE_VIDEOTOOLBOX)
+                }
+                HwDecoderPref::SoftwareOnly => {}
+            }
+        }
+        decoder
+    }
+
+    /// If video decoders are created with a hardware device
+    fn hw_enabled(&self) -> bool {
+        !self.hw_failed && self.hw_pref != HwDecoderPref::SoftwareOnly
+    }
+
+    /// Setup decoders for the given stream indexes which don't have one yet.
+    /// If a hardware decoder fails to open all decoders are re-created in software.
+    fn setup_decoders(&mut self, indexes: &[isize]) -> Result<()> {
+        let Some(info) = self.info.as_ref() else {
+            return Ok(());
+        };
+        let streams = info
+            .streams
+            .iter()
+            .filter(|s| {
+                indexes.contains(&(s.index as isize))
+                    && self.decoder.get_decoder(s.index as _).is_none()
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        for stream in streams {
+            if let Err(e) = self.decoder.setup_decoder(&stream, None) {
+                if stream.stream_type != StreamType::Video || !self.hw_enabled() {
+                    return Err(e);
+                }
+                warn!(
+                    "Failed to open hardware decoder for stream {}, using software: {}",
+                    stream.index, e
+                );
+                self.hw_failed = true;
+                self.decoder = self.new_decoder();
+                return self.setup_decoders(indexes);
+            }
+        }
+        Ok(())
+    }
+
+    /// Skip decoding non-reference video frames while playing at 2x speed or faster
+    fn update_frame_discard(&mut self) {
+        let skip = skip_nonref_frames(self.data.playback.speed(), self.skip_nonref);
+        if skip != self.skip_nonref {
+            info!("Skipping non-reference frames: {}", skip);
+            self.skip_nonref = skip;
+        }
+        let v_index = self.data.playback.selected_video.load(Ordering::Relaxed);
+        if let Some(decoder) = self.decoder.get_decoder(v_index as _) {
+            // set every tick, decoders are re-created on stream changes and hw fallback
+            unsafe {
+                (*decoder.context).skip_frame = if skip {
+                    AVDiscard::AVDISCARD_NONREF
+                } else {
+                    AVDiscard::AVDISCARD_DEFAULT
+                };
+            }
+        }
+    }
+
+    /// Seek the demuxer to a position (seconds) and reset all decoder state
+    fn seek(&mut self, pts: f64) -> Result<()> {
+        let ts = (pts * AV_TIME_BASE as f64) as i64;
+        let ret =
+            unsafe { av_seek_frame(self.demuxer.context(), -1, ts, AVSEEK_FLAG_BACKWARD as _) };
+        if ret < 0 && self.data.bytes.is_some() {
+            // in-memory media has no seek callback, decode it again from the start.
+            // The player and the audio resync drop everything before the target
+            self.reopen()?;
+        } else if ret < 0 {
+            bail!("Failed to seek to {:.3}s", pts);
+        } else {
+            // re-create decoders to drop any frames buffered from before the seek
+            self.decoder = self.new_decoder();
+            let v_index = self.data.playback.selected_video.load(Ordering::Relaxed);
+            let a_index = self.data.playback.selected_audio.load(Ordering::Relaxed);
+            self.setup_decoders(&[v_index, a_index])?;
+        }
+        self.audio_fifo = AudioFifo::new(
+            FfmpegDecoder::OUT_SAMPLE_FORMAT,
+            self.data.playback.channels.load(Ordering::Relaxed) as _,
+        )?;
+        self.data.playback.set_video_pts(pts);
+        self.data.playback.set_audio_pts(pts);
+        self.data.playback.request_audio_resync(pts);
+        self.data.playback.set_buffered_duration(0.0);
+        self.last_video_pts = pts;
+        Ok(())
+    }
+
+    /// Re-open the input from the beginning, this also works for inputs which can't seek
+    fn reopen(&mut self) -> Result<()> {
+        let v_index = self.data.playback.selected_video.load(Ordering::Relaxed);
+        let a_index = self.data.playback.selected_audio.load(Ordering::Relaxed);
+        let s_index = self.data.playback.selected_subtitle.load(Ordering::Relaxed);
+        let s2_index = self
+            .data
+            .playback
+            .selected_secondary_subtitle
+            .load(Ordering::Relaxed);
+
+        self.demuxer = FfmpegDecoder::open_demuxer(
+            &self.data.path,
+            self.data.bytes.as_ref(),
+            &self.data.options,
+        )?;
+        self.decoder = self.new_decoder();
+        self.info = None;
+        self.probe()?;
+
+        // keep the streams selected before looping
+        self.data
+            .playback
+            .selected_video
+            .store(v_index, Ordering::Relaxed);
+        self.data
+            .playback
+            .selected_audio
+            .store(a_index, Ordering::Relaxed);
+        self.data
+            .playback
+            .selected_subtitle
+            .store(s_index, Ordering::Relaxed);
+        self.data
+            .playback
+            .selected_secondary_subtitle
+            .store(s2_index, Ordering::Relaxed);
+        self.check_selected_streams()?;
+
+        self.audio_fifo = AudioFifo::new(
+            FfmpegDecoder::OUT_SAMPLE_FORMAT,
+            self.data.playback.channels.load(Ordering::Relaxed) as _,
+        )?;
+        self.data.playback.set_video_pts(0.0);
+        self.data.playback.set_audio_pts(0.0);
+        self.data.playback.request_audio_resync(0.0);
+        self.data.playback.set_buffered_duration(0.0);
+        self.last_video_pts = 0.0;
+        Ok(())
+    }
+
     fn decode_packet(&mut self, pkt: Option<&AvPacketRef>) -> Result<()> {
         let frames = self.decoder.decode_pkt(pkt)?;
         for (frame, stream_index) in frames {
@@ -112,37 +361,65 @@ impl DecoderThread {
                 AVMediaType::AVMEDIA_TYPE_AUDIO => {
                     self.send_audio(frame, stream_index, q)?;
                 }
-                AVMediaType::AVMEDIA_TYPE_SUBTITLE => {
-                    self.send_subtitle(frame, stream_index, q)?;
-                }
                 _ => continue,
             }
         }
         Ok(())
     }
 
-    fn send_video(&mut self, frame: AvFrameRef, stream_index: i32, q: f64) -> Result<()> {
-        // convert to RBGA
+    fn send_video(&mut self, mut frame: AvFrameRef, stream_index: i32, q: f64) -> Result<()> {
+        let filters = self.data.playback.video_filters();
+        if filters.is_default() {
+            self.filter = None;
+        } else {
+            unsafe {
+                let ptr: *mut AVFrame = &mut *frame;
+                if !self
+                    .filter
+                    .as_ref()
+                    .is_some_and(|f| f.matches(ptr, &filters))
+                {
+                    self.filter = Some(EqFilter::new(ptr, filters)?);
+                }
+                if let Some(f) = self.filter.as_mut()
+                    && !f.process(ptr)?
+                {
+                    return Ok(());
+                }
+            }
+        }
+
+        // convert to RBGA, downscaled by the render scale
+        let scale = self.data.playback.render_scale();
+        let width = (frame.width as f32 * scale).round().max(1.0);
+        let height = (frame.height as f32 * scale).round().max(1.0);
         let new_frame = self.scaler.process_frame(
             &frame,
-            frame.width as _,
-            frame.height as _,
+            width as _,
+            height as _,
             AVPixelFormat::AV_PIX_FMT_RGBA,
         )?;
-        self.data.tx_v.send(VideoFrame {
-            data: video_frame_to_image(&new_frame)?,
-            stream_index,
-            pts: if frame.pts != AV_NOPTS_VALUE {
-                frame.pts as f64 * q
-            } else {
-                0.0
-            },
-            duration: if frame.duration != AV_NOPTS_VALUE {
-                frame.duration as f64 * q
-            } else {
-                0.0
+        let pts = if frame.pts != AV_NOPTS_VALUE {
+            frame.pts as f64 * q
+        } else {
+            0.0
+        };
+        let duration = if frame.duration != AV_NOPTS_VALUE {
+            frame.duration as f64 * q
+        } else {
+            0.0
+        };
+        self.data.send(
+            &self.data.tx_v,
+            VideoFrame {
+                data: video_frame_to_image(&new_frame)?,
+                stream_index,
+                pts,
+                duration,
+                generation: self.seek_generation,
             },
-        })?;
+        )?;
+        self.last_video_pts = pts + duration;
         Ok(())
     }
 
@@ -161,20 +438,20 @@ impl DecoderThread {
         drop(frame);
 
         while let Some(f) = self.audio_fifo.get_frame(512 * target_channels as usize)? {
-            let bps = unsafe { av_get_bytes_per_sample(FfmpegDecoder::OUT_SAMPLE_FORMAT) };
-
-            self.data.tx_a.send(AudioSamples {
+            // one plane per channel, linesize can include padding so only take nb_samples
+            let samples = AudioSamples {
                 data: unsafe {
                     f.data
                         .iter()
+                        .take(target_channels as usize)
                         .filter_map(|data| {
                             if data.is_null() {
                                 None
                             } else {
                                 Some(
                                     std::slice::from_raw_parts(
-                                        *data as *const _,
-                                        f.linesize[0] as usize / bps as usize,
+                                        *data as *const f32,
+                                        f.nb_samples as usize,
                                     )
                                     .to_vec(),
                                 )
@@ -194,16 +471,78 @@ impl DecoderThread {
                 } else {
                     0.0
                 },
-            })?;
+            };
+            self.data.send(&self.data.tx_a, samples)?;
         }
         Ok(())
     }
 
-    fn send_subtitle(&mut self, _frame: AvFrameRef, stream_index: i32, _q: f64) -> Result<()> {
-        self.data.tx_s.send(SubtitlePacket {
-            data: vec![],
-            stream_index,
-        })?;
+    /// Decode a subtitle packet and send each text rect as an ASS event line.
+    /// Text decoders (SRT, WebVTT etc.) output ASS, bitmap subtitles are skipped
+    fn send_subtitle(&mut self, pkt: &AvPacketRef) -> Result<()> {
+        let Some(decoder) = self.decoder.get_decoder(pkt.stream_index as _) else {
+            return Ok(());
+        };
+        let stream = unsafe { self.demuxer.get_stream(pkt.stream_index as _)? };
+        let q = unsafe { av_q2d((*stream).time_base) };
+        let mut events = Vec::new();
+        unsafe {
+            let mut sub: AVSubtitle = std::mem::zeroed();
+            let mut got_sub = 0;
+            let ret = avcodec_decode_subtitle2(
+                decoder.context,
+                &mut sub,
+                &mut got_sub,
+                ptr::from_ref::<AVPacket>(pkt) as _,
+            );
+            if ret < 0 {
+                warn!("Failed to decode subtitle packet: {}", ret);
+                return Ok(());
+            }
+            if got_sub == 0 {
+                return Ok(());
+            }
+            for i in 0..sub.num_rects as usize {
+                let rect = *sub.rects.add(i);
+                match (*rect).type_ {
+                    AVSubtitleType::SUBTITLE_ASS if !(*rect).ass.is_null() => {
+                        events.push(rstr!((*rect).ass).to_string());
+                    }
+                    AVSubtitleType::SUBTITLE_TEXT if !(*rect).text.is_null() => {
+                        // wrap plain text in an event with the default style
+                        events.push(format!("0,0,Default,,0,0,0,,{}", rstr!((*rect).text)));
+                    }
+                    _ => {}
+                }
+            }
+            let start = sub.start_display_time as f64 / 1000.0;
+            // some decoders leave the end time unset and rely on the packet duration
+            let duration = if sub.end_display_time > sub.start_display_time
+                && sub.end_display_time != u32::MAX
+            {
+                sub.end_display_time as f64 / 1000.0 - start
+            } else {
+                pkt.duration as f64 * q
+            };
+            avsubtitle_free(&mut sub);
+
+            let pts = if pkt.pts != AV_NOPTS_VALUE {
+                pkt.pts as f64 * q
+            } else {
+                0.0
+            };
+            for event in events {
+                self.data.send(
+                    &self.data.tx_s,
+                    SubtitlePacket {
+                        data: event.into_bytes(),
+                        stream_index: pkt.stream_index,
+                        pts: pts + start,
+                        duration,
+                    },
+                )?;
+            }
+        }
         Ok(())
     }
 
@@ -211,25 +550,30 @@ impl DecoderThread {
         let probe = unsafe { self.demuxer.probe_input()? };
         self.info.replace(probe.clone());
 
+        // cover art is not a playable video stream
+        let cover_index = unsafe { attached_pic_stream(&self.demuxer) };
+
         // pick the best video/audio/subtitle stream
         let pick_video = probe
             .streams
             .iter()
-            .filter(|s| s.stream_type == StreamType::Video)
+            .filter(|s| s.stream_type == StreamType::Video && Some(s.index as usize) != cover_index)
             .max_by_key(|s| s.width * s.height)
             .map(|s| s.index as isize)
             .unwrap_or(-1);
+        // no audio or subtitles for e.g. thumbnail decoders
+        let video_only = self.data.options.video_only;
         let pick_audio = probe
             .streams
             .iter()
-            .filter(|s| s.stream_type == StreamType::Audio)
+            .filter(|s| !video_only && s.stream_type == StreamType::Audio)
             .max_by_key(|s| s.bitrate)
             .map(|s| s.index as isize)
             .unwrap_or(-1);
         let pick_subtitle = probe
             .streams
             .iter()
-            .filter(|s| s.stream_type == StreamType::Subtitle)
+            .filter(|s| !video_only && s.stream_type == StreamType::Subtitle)
             .next()
             .map(|s| s.index as isize)
             .unwrap_or(-1);
@@ -241,89 +585,310 @@ impl DecoderThread {
             .playback
             .selected_audio
             .store(pick_audio, Ordering::Relaxed);
+        self.active_audio = pick_audio;
         self.data
             .playback
             .selected_subtitle
             .store(pick_subtitle, Ordering::Relaxed);
 
-        for stream in probe.streams.iter() {
-            if stream.index == pick_video as _
-                || stream.index == pick_audio as _
-                || stream.index == pick_subtitle as _
-            {
-                self.decoder.setup_decoder(stream, None)?;
+        self.setup_decoders(&[pick_video, pick_audio, pick_subtitle])?;
+
+        // grow the video channel for high frame rate streams, before any frame is sent
+        if let Some(fps) = probe
+            .streams
+            .iter()
+            .find(|s| s.index as isize == pick_video)
+            .map(|s| s.fps)
+        {
+            let capacity = self.data.options.video_capacity(fps);
+            if capacity > self.video_capacity {
+                info!("Resizing video buffer to {} frames ({}fps)", capacity, fps);
+                let (tx, rx) = sync_channel(capacity);
+                self.data.tx_v = tx;
+                self.data.tx_vr.send(rx)?;
+                self.video_capacity = capacity;
             }
         }
 
-        let inf = DecoderInfo {
-            bitrate: probe.bitrate as _,
-            duration: probe.duration,
-            streams: probe
-                .streams
-                .iter()
-                .filter_map(|s| {
-                    Some(StreamInfo {
-                        r#type: match s.stream_type {
-                            StreamType::Unknown => return None,
-                            StreamType::Video => crate::stream::StreamType::Video,
-                            StreamType::Audio => crate::stream::StreamType::Audio,
-                            StreamType::Subtitle => crate::stream::StreamType::Subtitle,
-                        },
-                        index: s.index as _,
-                        codec: unsafe {
-                            if let Some(dec) = self.decoder.get_decoder(s.index as _) {
-                                dec.codec_name()
-                            } else {
-                                let n = avcodec_get_name(transmute(s.codec as i32));
-                                rstr!(n).to_string()
-                            }
-                        },
-                        format: unsafe {
-                            if s.width != 0 {
-                                let n = av_get_pix_fmt_name(transmute(s.format as i32));
-                                rstr!(n).to_string()
-                            } else {
-                                let n = av_get_sample_fmt_name(transmute(s.format as i32));
-                                rstr!(n).to_string()
+        let inf = decoder_info(&self.demuxer, &probe, cover_index, Some(&self.decoder));
+
+        match self.data.tx_m.try_send(inf) {
+            // the info from a previous probe (e.g. before looping) was not read yet
+            Ok(()) | Err(TrySendError::Full(_)) => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Non-reference frames are skipped from 2x speed, and decoded again once back at 1x.
+/// Speeds in between keep the current mode so it doesn't flip on small speed changes
+fn skip_nonref_frames(speed: f32, skipping: bool) -> bool {
+    if speed >= 2.0 {
+        true
+    } else if speed <= 1.0 {
+        false
+    } else {
+        skipping
+    }
+}
+
+/// Type name of the hardware device a decoder was opened with, e.g. `vaapi` or `cuda`.
+/// [None] for software decoders, including when no requested device could be created
+unsafe fn hw_device_name(ctx: *const AVCodecContext) -> Option<String> {
+    unsafe {
+        let device = (*ctx).hw_device_ctx;
+        if device.is_null() {
+            return None;
+        }
+        let device = (*device).data as *const AVHWDeviceContext;
+        let n = av_hwdevice_get_type_name((*device).type_);
+        if n.is_null() {
+            None
+        } else {
+            Some(rstr!(n).to_string())
+        }
+    }
+}
+
+/// Build the [DecoderInfo] of a probed input. `decoder` holds the opened stream decoders,
+/// without it codec names come from the stream codec ids
+fn decoder_info(
+    demuxer: &Demuxer,
+    probe: &DemuxerInfo,
+    cover_index: Option<usize>,
+    decoder: Option<&Decoder>,
+) -> DecoderInfo {
+    let mut metadata = MediaMetadata::from_tags(&unsafe { container_tags(demuxer) });
+    if let Some(index) = cover_index {
+        match unsafe { cover_art(demuxer, index) } {
+            Ok(img) => metadata.cover_art = Some(img),
+            Err(e) => warn!("Failed to decode cover art: {}", e),
+        }
+    }
+
+    DecoderInfo {
+        bitrate: probe.bitrate as _,
+        duration: probe.duration,
+        start_time: unsafe {
+            let start = (*demuxer.context()).start_time;
+            if start == AV_NOPTS_VALUE {
+                0.0
+            } else {
+                start as f64 / AV_TIME_BASE as f64
+            }
+        },
+        streams: probe
+            .streams
+            .iter()
+            .filter_map(|s| {
+                Some(StreamInfo {
+                    r#type: match s.stream_type {
+                        StreamType::Unknown => return None,
+                        StreamType::Video => crate::stream::StreamType::Video,
+                        StreamType::Audio => crate::stream::StreamType::Audio,
+                        StreamType::Subtitle => crate::stream::StreamType::Subtitle,
+                    },
+                    index: s.index as _,
+                    codec: unsafe {
+                        if let Some(dec) = decoder.and_then(|d| d.get_decoder(s.index as _)) {
+                            match hw_device_name(dec.context) {
+                                Some(hw) if s.stream_type == StreamType::Video => {
+                                    format!("{} ({})", dec.codec_name(), hw)
+                                }
+                                _ => dec.codec_name(),
                             }
-                        },
-                        channels: s.channels,
-                        sample_rate: s.sample_rate as _,
-                        width: s.width as _,
-                        height: s.height as _,
-                        fps: s.fps,
-                        language: if s.language.is_empty() {
-                            None
                         } else {
-                            Some(s.language.clone())
-                        },
-                    })
+                            let n = avcodec_get_name(transmute(s.codec as i32));
+                            rstr!(n).to_string()
+                        }
+                    },
+                    format: unsafe {
+                        if s.width != 0 {
+                            let n = av_get_pix_fmt_name(transmute(s.format as i32));
+                            rstr!(n).to_string()
+                        } else {
+                            let n = av_get_sample_fmt_name(transmute(s.format as i32));
+                            rstr!(n).to_string()
+                        }
+                    },
+                    channels: s.channels,
+                    sample_rate: s.sample_rate as _,
+                    width: s.width as _,
+                    height: s.height as _,
+                    fps: s.fps,
+                    language: if s.language.is_empty() {
+                        None
+                    } else {
+                        Some(s.language.clone())
+                    },
+                    bitrate: s.bitrate as _,
+                    sar: unsafe { stream_sar(demuxer, s.index as _) },
                 })
-                .collect(),
-        };
+            })
+            .collect(),
+        metadata,
+        chapters: unsafe { chapters(demuxer) },
+    }
+}
 
-        self.data.tx_m.send(inf)?;
-        Ok(())
+/// Metadata tags of the container, keys are lower case
+unsafe fn container_tags(demuxer: &Demuxer) -> HashMap<String, String> {
+    let mut tags = HashMap::new();
+    unsafe {
+        let ctx = demuxer.context();
+        let mut tag = ptr::null();
+        loop {
+            tag = av_dict_get(
+                (*ctx).metadata,
+                c"".as_ptr(),
+                tag,
+                AV_DICT_IGNORE_SUFFIX as _,
+            );
+            if tag.is_null() {
+                break;
+            }
+            tags.insert(
+                rstr!((*tag).key).to_lowercase(),
+                rstr!((*tag).value).to_string(),
+            );
+        }
+    }
+    tags
+}
+
+/// Chapters of the container, in order of start time
+unsafe fn chapters(demuxer: &Demuxer) -> Vec<ChapterInfo> {
+    unsafe {
+        let ctx = demuxer.context();
+        let mut chapters = (0..(*ctx).nb_chapters as usize)
+            .map(|i| {
+                let chapter = *(*ctx).chapters.add(i);
+                let tb = av_q2d((*chapter).time_base);
+                let title = av_dict_get((*chapter).metadata, c"title".as_ptr(), ptr::null(), 0);
+                ChapterInfo {
+                    title: if title.is_null() {
+                        format!("Chapter {}", i + 1)
+                    } else {
+                        rstr!((*title).value).to_string()
+                    },
+                    start: (*chapter).start as f64 * tb,
+                    end: (*chapter).end as f64 * tb,
+                }
+            })
+            .collect::<Vec<_>>();
+        chapters.sort_by(|a, b| a.start.total_cmp(&b.start));
+        chapters
+    }
+}
+
+/// Sample aspect ratio of a video stream, [None] when unknown
+unsafe fn stream_sar(demuxer: &Demuxer, index: usize) -> Option<(u32, u32)> {
+    unsafe {
+        let stream = demuxer.get_stream(index).ok()?;
+        let sar = (*(*stream).codecpar).sample_aspect_ratio;
+        (sar.num > 0 && sar.den > 0).then_some((sar.num as u32, sar.den as u32))
+    }
+}
+
+/// Index of the stream holding embedded cover art, if any
+unsafe fn attached_pic_stream(demuxer: &Demuxer) -> Option<usize> {
+    unsafe {
+        let ctx = demuxer.context();
+        (0..(*ctx).nb_streams as usize).find(|i| {
+            let stream = *(*ctx).streams.add(*i);
+            (*stream).disposition & AV_DISPOSITION_ATTACHED_PIC as i32 != 0
+        })
     }
 }
 
+/// Decode the embedded cover art, the attached picture is a single JPEG/PNG packet
+unsafe fn cover_art(demuxer: &Demuxer, index: usize) -> Result<ColorImage> {
+    let pkt = unsafe {
+        let ctx = demuxer.context();
+        let stream = *(*ctx).streams.add(index);
+        let pkt = &(*stream).attached_pic;
+        if pkt.data.is_null() || pkt.size <= 0 {
+            bail!("Attached picture is empty");
+        }
+        std::slice::from_raw_parts(pkt.data, pkt.size as usize)
+    };
+    let img = image::load_from_memory(pkt)?.to_rgba8();
+    Ok(ColorImage::from_rgba_unmultiplied(
+        [img.width() as _, img.height() as _],
+        img.as_raw(),
+    ))
+}
+
 pub(crate) struct FfmpegDecoder {
     data: MediaDecoderThreadData,
+    hw_pref: HwDecoderPref,
 }
 
 impl FfmpegDecoder {
     const OUT_SAMPLE_FORMAT: AVSampleFormat = AVSampleFormat::AV_SAMPLE_FMT_FLTP;
 
     pub(crate) fn new(data: MediaDecoderThreadData) -> Self {
-        Self { data }
+        Self {
+            data,
+            hw_pref: HwDecoderPref::default(),
+        }
+    }
+
+    /// Create a demuxer for the input path or in-memory media
+    fn open_demuxer(
+        path: &str,
+        bytes: Option<&Arc<[u8]>>,
+        opts: &StreamingOptions,
+    ) -> Result<Demuxer> {
+        #[cfg(feature = "dash")]
+        if bytes.is_none() && crate::dash::is_dash(path) {
+            let mut dash = crate::dash::DashStream::new(path);
+            dash.load()?;
+            return Ok(Demuxer::new_custom_io(
+                dash.reader()?,
+                Some(path.to_string()),
+            )?);
+        }
+        Ok(if let Some(bytes) = bytes {
+            Demuxer::new_custom_io(Cursor::new(bytes.clone()), None)?
+        } else if StreamingOptions::is_realtime(path) {
+            let demuxer = Demuxer::new(&opts.input_url(path))?;
+            unsafe {
+                let ctx = demuxer.context();
+                (*ctx).probesize = opts.probe_size as _;
+                (*ctx).max_picture_buffer = opts.buffer_size as _;
+            }
+            demuxer
+        } else {
+            Demuxer::new(path)?
+        })
+    }
+
+    /// Read the [DecoderInfo] of an input without opening decoders or starting a thread
+    pub(crate) fn probe_only(path: &str) -> Result<DecoderInfo> {
+        let mut demuxer = Self::open_demuxer(path, None, &StreamingOptions::default())?;
+        let probe = unsafe { demuxer.probe_input()? };
+        let cover_index = unsafe { attached_pic_stream(&demuxer) };
+        Ok(decoder_info(&demuxer, &probe, cover_index, None))
+    }
+
+    /// Set the hardware decoder preference for video
+    pub(crate) fn with_hw_pref(mut self, pref: HwDecoderPref) -> Self {
+        self.hw_pref = pref;
+        self
     }
 }
 
 impl MediaDecoderImpl for FfmpegDecoder {
     fn start(&mut self) -> Result<JoinHandle<()>> {
+        let demuxer = Self::open_demuxer(
+            &self.data.path,
+            self.data.bytes.as_ref(),
+            &self.data.options,
+        )?;
         let mut instance = DecoderThread {
             data: self.data.clone(),
-            demuxer: Demuxer::new(&self.data.path)?,
+            demuxer,
             decoder: Decoder::new(),
             scaler: Scaler::new(),
             resample: Resample::new(
@@ -336,11 +901,22 @@ impl MediaDecoderImpl for FfmpegDecoder {
                 self.data.playback.channels.load(Ordering::Relaxed) as _,
             )?,
             info: None,
+            active_audio: -1,
+            hw_pref: self.hw_pref,
+            hw_failed: false,
+            last_video_pts: 0.0,
+            filter: None,
+            skip_nonref: false,
+            seek_generation: self.data.playback.seek_generation(),
+            video_capacity: self
+                .data
+                .options
+                .video_capacity(StreamingOptions::DEFAULT_FPS),
         };
         Ok(std::thread::Builder::new()
             .name("media-decoder-ffmpeg".to_string())
             .spawn(move || {
-                instance.decoder.enable_hw_decoder_any();
+                instance.decoder = instance.new_decoder();
                 loop {
                     if let Err(e) = instance.tick() {
                         error!("{}", e);
@@ -350,3 +926,93 @@ impl MediaDecoderImpl for FfmpegDecoder {
             })?)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SharedPlaybackState;
+    use crate::stream::MediaDecoder;
+    use std::time::Duration;
+
+    #[test]
+    fn skip_nonref() {
+        assert!(!skip_nonref_frames(1.0, false));
+        assert!(skip_nonref_frames(2.0, false));
+        assert!(skip_nonref_frames(4.0, false));
+        // between 1x and 2x the current mode is kept
+        assert!(skip_nonref_frames(1.5, true));
+        assert!(!skip_nonref_frames(1.5, false));
+        assert!(!skip_nonref_frames(1.0, true));
+        assert!(!skip_nonref_frames(0.5, true));
+    }
+
+    /// Decode every video frame of a file, optionally skipping non-reference frames
+    fn count_frames(path: &str, skip_nonref: bool) -> Result<usize> {
+        let mut demuxer = Demuxer::new(path)?;
+        let info = unsafe { demuxer.probe_input()? };
+        let stream = info
+            .streams
+            .iter()
+            .find(|s| s.stream_type == StreamType::Video)
+            .expect("no video stream");
+        let mut decoder = Decoder::new();
+        decoder.setup_decoder(stream, None)?;
+        let ctx = decoder
+            .get_decoder(stream.index as _)
+            .expect("decoder not opened")
+            .context;
+        unsafe {
+            (*ctx).skip_frame = if skip_nonref {
+                AVDiscard::AVDISCARD_NONREF
+            } else {
+                AVDiscard::AVDISCARD_DEFAULT
+            };
+        }
+        let mut frames = 0;
+        loop {
+            let (pkt, _) = unsafe { demuxer.get_packet()? };
+            frames += decoder.decode_pkt(pkt.as_ref())?.len();
+            if pkt.is_none() {
+                break;
+            }
+        }
+        Ok(frames)
+    }
+
+    #[test]
+    fn skip_nonref_decodes_fewer_frames() -> Result<()> {
+        // 16x16 H.264 main profile: an IDR frame then 10 groups of P, B, B in decode order,
+        // the B-frames are non-reference (nal_ref_idc = 0)
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/bframes.h264");
+        assert_eq!(count_frames(path, false)?, 31);
+        assert_eq!(count_frames(path, true)?, 11);
+        Ok(())
+    }
+
+    /// Number of video frames the decoder thread sends before it exits, up to `max`
+    fn decoded_frames(looping: bool, max: usize) -> Result<usize> {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/bframes.h264");
+        let state = SharedPlaybackState::new();
+        state.set_looping(looping);
+        let (_decoder, streams) = MediaDecoder::new_with_hw_pref(
+            path,
+            state,
+            StreamingOptions::default(),
+            HwDecoderPref::SoftwareOnly,
+        )?;
+        let mut frames = 0;
+        while frames < max && streams.video.recv_timeout(Duration::from_secs(5)).is_ok() {
+            frames += 1;
+        }
+        Ok(frames)
+    }
+
+    #[test]
+    fn looping_restarts_at_eof() -> Result<()> {
+        // the file has 31 frames, the decoder exits after them
+        assert_eq!(decoded_frames(false, 100)?, 31);
+        // more can only come from decoding it again from the start
+        assert_eq!(decoded_frames(true, 100)?, 100);
+        Ok(())
+    }
+}
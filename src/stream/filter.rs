@@ -0,0 +1,157 @@
+use crate::stream::VideoFilters;
+use anyhow::{Result, bail};
+use ffmpeg_rs_raw::ffmpeg_sys_the_third::{
+    AV_TIME_BASE, AVERROR, AVERROR_EOF, AVFilterContext, AVFilterGraph, AVFrame, EAGAIN,
+    av_buffersink_get_frame, av_buffersrc_add_frame, av_strdup, avfilter_get_by_name,
+    avfilter_graph_alloc, avfilter_graph_config, avfilter_graph_create_filter, avfilter_graph_free,
+    avfilter_graph_parse_ptr, avfilter_inout_alloc, avfilter_inout_free,
+};
+use std::ffi::CString;
+use std::ptr;
+
+/// `eq` filter graph applying [VideoFilters] to decoded video frames
+pub(crate) struct EqFilter {
+    graph: *mut AVFilterGraph,
+    src: *mut AVFilterContext,
+    sink: *mut AVFilterContext,
+    /// Input frame size and pixel format the graph was configured for
+    input: (i32, i32, i32),
+    filters: VideoFilters,
+}
+
+// the graph is only used by the decoder thread
+unsafe impl Send for EqFilter {}
+
+impl EqFilter {
+    /// Create a filter graph for frames matching `frame`
+    pub unsafe fn new(frame: *const AVFrame, filters: VideoFilters) -> Result<Self> {
+        unsafe {
+            let mut graph = avfilter_graph_alloc();
+            if graph.is_null() {
+                bail!("Failed to allocate filter graph");
+            }
+            let ret = Self::build(graph, frame, &filters);
+            match ret {
+                Ok((src, sink)) => Ok(Self {
+                    graph,
+                    src,
+                    sink,
+                    input: ((*frame).width, (*frame).height, (*frame).format),
+                    filters,
+                }),
+                Err(e) => {
+                    avfilter_graph_free(&mut graph);
+                    Err(e)
+                }
+            }
+        }
+    }
+
+    unsafe fn build(
+        graph: *mut AVFilterGraph,
+        frame: *const AVFrame,
+        filters: &VideoFilters,
+    ) -> Result<(*mut AVFilterContext, *mut AVFilterContext)> {
+        unsafe {
+            // the eq filter doesn't change timestamps, so the time base is not important
+            let sar = (*frame).sample_aspect_ratio;
+            let args = CString::new(format!(
+                "video_size={}x{}:pix_fmt={}:time_base=1/{}:pixel_aspect={}/{}",
+                (*frame).width,
+                (*frame).height,
+                (*frame).format,
+                AV_TIME_BASE,
+                sar.num,
+                sar.den.max(1)
+            ))?;
+            let mut src = ptr::null_mut();
+            let ret = avfilter_graph_create_filter(
+                &mut src,
+                avfilter_get_by_name(c"buffer".as_ptr()),
+                c"in".as_ptr(),
+                args.as_ptr(),
+                ptr::null_mut(),
+                graph,
+            );
+            if ret < 0 {
+                bail!("Failed to create buffer source: {}", ret);
+            }
+            let mut sink = ptr::null_mut();
+            let ret = avfilter_graph_create_filter(
+                &mut sink,
+                avfilter_get_by_name(c"buffersink".as_ptr()),
+                c"out".as_ptr(),
+                ptr::null(),
+                ptr::null_mut(),
+                graph,
+            );
+            if ret < 0 {
+                bail!("Failed to create buffer sink: {}", ret);
+            }
+
+            let mut outputs = avfilter_inout_alloc();
+            (*outputs).name = av_strdup(c"in".as_ptr());
+            (*outputs).filter_ctx = src;
+            (*outputs).pad_idx = 0;
+            (*outputs).next = ptr::null_mut();
+            let mut inputs = avfilter_inout_alloc();
+            (*inputs).name = av_strdup(c"out".as_ptr());
+            (*inputs).filter_ctx = sink;
+            (*inputs).pad_idx = 0;
+            (*inputs).next = ptr::null_mut();
+
+            let desc = CString::new(filters.to_string())?;
+            let ret = avfilter_graph_parse_ptr(
+                graph,
+                desc.as_ptr(),
+                &mut inputs,
+                &mut outputs,
+                ptr::null_mut(),
+            );
+            avfilter_inout_free(&mut inputs);
+            avfilter_inout_free(&mut outputs);
+            if ret < 0 {
+                bail!("Failed to parse filter graph '{}': {}", filters, ret);
+            }
+            let ret = avfilter_graph_config(graph, ptr::null_mut());
+            if ret < 0 {
+                bail!("Failed to configure filter graph: {}", ret);
+            }
+            Ok((src, sink))
+        }
+    }
+
+    /// If the graph can be used for `frame` with `filters`
+    pub unsafe fn matches(&self, frame: *const AVFrame, filters: &VideoFilters) -> bool {
+        unsafe {
+            self.filters == *filters
+                && self.input == ((*frame).width, (*frame).height, (*frame).format)
+        }
+    }
+
+    /// Filter a frame in place, returns false if the graph didn't output a frame
+    pub unsafe fn process(&mut self, frame: *mut AVFrame) -> Result<bool> {
+        unsafe {
+            let ret = av_buffersrc_add_frame(self.src, frame);
+            if ret < 0 {
+                bail!("Failed to add frame to filter graph: {}", ret);
+            }
+            let ret = av_buffersink_get_frame(self.sink, frame);
+            if ret == AVERROR(EAGAIN) || ret == AVERROR_EOF {
+                return Ok(false);
+            }
+            if ret < 0 {
+                bail!("Failed to get frame from filter graph: {}", ret);
+            }
+            Ok(true)
+        }
+    }
+}
+
+impl Drop for EqFilter {
+    fn drop(&mut self) {
+        unsafe {
+            avfilter_graph_free(&mut self.graph);
+        }
+    }
+}
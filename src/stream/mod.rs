@@ -2,25 +2,115 @@ use crate::SharedPlaybackState;
 use anyhow::Result;
 use anyhow::bail;
 use egui::ColorImage;
+use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
 use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, AtomicIsize, AtomicU8, AtomicU32};
-use std::sync::mpsc::{Receiver, SyncSender, sync_channel};
+use std::sync::atomic::{AtomicBool, AtomicIsize, AtomicU8, AtomicU32, Ordering};
+use std::sync::mpsc::{Receiver, SyncSender, TrySendError, sync_channel};
 use std::thread::JoinHandle;
+use std::time::Duration;
 
 #[cfg(feature = "avfoundation")]
 mod avfoundation;
+mod buffer;
+pub use buffer::VideoFrameBuffer;
 #[cfg(feature = "ffmpeg")]
 mod ffmpeg;
+#[cfg(feature = "ffmpeg")]
+mod filter;
 
 #[derive(Clone, Debug)]
 pub struct DecoderInfo {
     pub bitrate: u64,
     pub duration: f32,
+    /// PTS (seconds) of the first frame, non-zero for e.g. MPEG-TS
+    pub start_time: f64,
     pub streams: Vec<StreamInfo>,
+    pub metadata: MediaMetadata,
+    /// Chapters embedded in the container
+    pub chapters: Vec<ChapterInfo>,
 }
 
-#[derive(Clone, Debug)]
+/// Chapter embedded in the container (Matroska, MP4 etc.)
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChapterInfo {
+    /// Chapter title, `Chapter N` when the container has none
+    pub title: String,
+    /// Start PTS in seconds
+    pub start: f64,
+    /// End PTS in seconds
+    pub end: f64,
+}
+
+/// Title, artist etc. read from the container tags
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MediaMetadata {
+    /// Title of the track or movie
+    pub title: Option<String>,
+    /// Artist or author
+    pub artist: Option<String>,
+    /// Album name
+    pub album: Option<String>,
+    /// Release year
+    pub year: Option<u32>,
+    /// Comment or description
+    pub comment: Option<String>,
+    /// Embedded cover art
+    pub cover_art: Option<ColorImage>,
+}
+
+impl MediaMetadata {
+    /// Read the known tags from a map of lower case tag names
+    pub(crate) fn from_tags(tags: &HashMap<String, String>) -> Self {
+        let get = |keys: &[&str]| {
+            keys.iter()
+                .filter_map(|k| tags.get(*k))
+                .map(|v| v.trim())
+                .find(|v| !v.is_empty())
+                .map(|v| v.to_string())
+        };
+        Self {
+            title: get(&["title"]),
+            artist: get(&["artist", "album_artist", "author"]),
+            album: get(&["album"]),
+            // dates are usually "2020" or "2020-01-31"
+            year: get(&["date", "year", "creation_time"]).and_then(|d| d.get(..4)?.parse().ok()),
+            comment: get(&["comment", "description"]),
+            cover_art: None,
+        }
+    }
+
+    /// If none of the tags are set
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none()
+            && self.artist.is_none()
+            && self.album.is_none()
+            && self.year.is_none()
+            && self.comment.is_none()
+            && self.cover_art.is_none()
+    }
+}
+
+impl Display for MediaMetadata {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let unk = "unk";
+        write!(
+            f,
+            "{} - {}",
+            self.artist.as_deref().unwrap_or(unk),
+            self.title.as_deref().unwrap_or(unk)
+        )?;
+        if let Some(album) = &self.album {
+            write!(f, " ({})", album)?;
+        }
+        if let Some(year) = self.year {
+            write!(f, " [{}]", year)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum StreamType {
     Video,
     Audio,
@@ -39,6 +129,18 @@ pub struct StreamInfo {
     pub height: u32,
     pub fps: f32,
     pub language: Option<String>,
+    /// Bitrate of this stream in bits/s, 0 if unknown
+    pub bitrate: u64,
+    /// Sample (pixel) aspect ratio as (num, den), for anamorphic video
+    pub sar: Option<(u32, u32)>,
+}
+
+impl StreamInfo {
+    /// Aspect ratio the video should be displayed at, the frame size corrected by the SAR
+    pub fn display_aspect_ratio(&self) -> f32 {
+        let (num, den) = self.sar.unwrap_or((1, 1));
+        (self.width as f32 * num as f32) / (self.height as f32 * den as f32)
+    }
 }
 
 impl Display for StreamInfo {
@@ -72,7 +174,11 @@ impl Display for StreamInfo {
                     self.language.as_ref().map(|s| s.as_str()).unwrap_or("unk")
                 )
             }
+        }?;
+        if self.bitrate > 0 {
+            write!(f, " {}kbps", self.bitrate / 1000)?;
         }
+        Ok(())
     }
 }
 
@@ -86,6 +192,8 @@ pub struct VideoFrame {
     pub pts: f64,
     /// Duration this frame should be shown
     pub duration: f64,
+    /// [`SharedPlaybackState::seek_generation`] at the seek this frame was decoded after
+    pub generation: u64,
 }
 
 #[derive(Clone)]
@@ -104,8 +212,13 @@ pub struct AudioSamples {
 
 #[derive(Clone)]
 pub struct SubtitlePacket {
+    /// UTF-8 ASS event line (`ReadOrder,Layer,Style,Name,MarginL,MarginR,MarginV,Effect,Text`)
     pub data: Vec<u8>,
     pub stream_index: i32,
+    /// Start time in seconds
+    pub pts: f64,
+    /// Display duration in seconds
+    pub duration: f64,
 }
 
 /// Container holding the channels for each media type
@@ -114,6 +227,9 @@ pub struct MediaStreams {
     pub video: Receiver<VideoFrame>,
     pub audio: Receiver<AudioSamples>,
     pub subtitle: Receiver<SubtitlePacket>,
+    /// Replacement for [MediaStreams::video] with a larger capacity, sent before the first
+    /// frame when the stream frame rate needs a bigger buffer than the default
+    pub video_resized: Receiver<Receiver<VideoFrame>>,
 }
 
 /// Media stream producer, creates a stream of decoded data from a path or url.
@@ -128,18 +244,169 @@ pub struct MediaDecoder {
     data: MediaDecoderThreadData,
 }
 
+/// Options applied when opening a stream, the network options only apply to
+/// real-time streams (RTSP/RTMP)
+#[derive(Debug, Clone)]
+pub struct StreamingOptions {
+    /// Seconds of decoded video buffered ahead of playback
+    pub preload_seconds: f32,
+    /// Use TCP instead of UDP for RTSP transport
+    pub tcp_transport: bool,
+    /// Max number of bytes read while probing the stream
+    pub probe_size: u32,
+    /// Max number of bytes used for buffering real-time frames
+    pub buffer_size: u32,
+    /// Only decode video, no audio or subtitle stream is selected
+    pub video_only: bool,
+}
+
+impl Default for StreamingOptions {
+    fn default() -> Self {
+        Self {
+            preload_seconds: 1.0 / 3.0,
+            tcp_transport: true,
+            probe_size: 32_768,
+            buffer_size: 3_041_280,
+            video_only: false,
+        }
+    }
+}
+
+impl StreamingOptions {
+    /// Frame rate assumed before the stream is probed
+    pub(crate) const DEFAULT_FPS: f32 = 30.0;
+
+    /// Capacity of the video frame channel for a given frame rate
+    pub(crate) fn video_capacity(&self, fps: f32) -> usize {
+        ((fps * self.preload_seconds).ceil() as usize).max(1)
+    }
+
+    /// If the input is a real-time stream which these options apply to
+    pub fn is_realtime(input: &str) -> bool {
+        let scheme = input
+            .split_once("://")
+            .map(|(s, _)| s.to_ascii_lowercase())
+            .unwrap_or_default();
+        matches!(
+            scheme.as_str(),
+            "rtsp" | "rtsps" | "rtmp" | "rtmps" | "rtmpt" | "rtmpe"
+        )
+    }
+
+    /// Apply URL options to the input path
+    pub fn input_url(&self, input: &str) -> String {
+        let is_rtsp = input.to_ascii_lowercase().starts_with("rtsp");
+        if is_rtsp && self.tcp_transport {
+            // libavformat reads the lower transport from the RTSP url options
+            let sep = if input.contains('?') { '&' } else { '?' };
+            format!("{}{}tcp", input, sep)
+        } else {
+            input.to_string()
+        }
+    }
+}
+
+/// Which hardware decoder to use for video, decoding falls back to software
+/// when the hardware decoder cannot be opened
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HwDecoderPref {
+    /// Try any available hardware decoder
+    #[default]
+    Any,
+    /// VA-API (Linux)
+    PreferVaapi,
+    /// NVDEC / CUDA (NVIDIA)
+    PreferNvdec,
+    /// VideoToolbox (macOS)
+    PreferVideoToolbox,
+    /// Never use a hardware decoder
+    SoftwareOnly,
+}
+
+/// Colour adjustments applied to decoded video, using the values of ffmpeg's `eq` filter
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VideoFilters {
+    /// Brightness, `-1.0` to `1.0`
+    pub brightness: f32,
+    /// Contrast, `-1000.0` to `1000.0`
+    pub contrast: f32,
+    /// Saturation, `0.0` to `3.0`
+    pub saturation: f32,
+}
+
+impl Default for VideoFilters {
+    fn default() -> Self {
+        Self {
+            brightness: 0.0,
+            contrast: 1.0,
+            saturation: 1.0,
+        }
+    }
+}
+
+impl VideoFilters {
+    /// If the filters don't change the video
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl Display for VideoFilters {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "eq=brightness={}:contrast={}:saturation={}",
+            self.brightness, self.contrast, self.saturation
+        )
+    }
+}
+
 /// Data shared with the decoder thread including decoder controls
 #[derive(Debug, Clone)]
 pub struct MediaDecoderThreadData {
     pub path: String,
 
+    /// In-memory media, read instead of [path] when set
+    pub bytes: Option<Arc<[u8]>>,
+
+    /// Options used when [path] is a real-time stream
+    pub options: StreamingOptions,
+
+    /// Hardware decoder to use for video
+    pub hw_pref: HwDecoderPref,
+
     pub playback: SharedPlaybackState,
 
+    /// Set by [MediaDecoder::cancel], the decoder thread exits on its next tick
+    pub stop_flag: Arc<AtomicBool>,
+
     // channels to send data back
     pub tx_m: SyncSender<DecoderInfo>,
     pub tx_v: SyncSender<VideoFrame>,
     pub tx_a: SyncSender<AudioSamples>,
     pub tx_s: SyncSender<SubtitlePacket>,
+    pub tx_vr: SyncSender<Receiver<VideoFrame>>,
+}
+
+impl MediaDecoderThreadData {
+    /// Send to one of the channels, waiting while it is full. Unlike a blocking send this
+    /// still exits when [MediaDecoder::cancel] is called while nothing reads the channel
+    pub fn send<T>(&self, tx: &SyncSender<T>, msg: T) -> Result<()> {
+        let mut msg = msg;
+        loop {
+            if self.stop_flag.load(Ordering::Relaxed) {
+                bail!("Decoder cancelled");
+            }
+            match tx.try_send(msg) {
+                Ok(()) => return Ok(()),
+                Err(TrySendError::Full(m)) => {
+                    msg = m;
+                    std::thread::sleep(Duration::from_millis(5));
+                }
+                Err(TrySendError::Disconnected(_)) => bail!("Channel closed"),
+            }
+        }
+    }
 }
 
 pub trait MediaDecoderImpl {
@@ -150,18 +417,94 @@ pub trait MediaDecoderImpl {
 impl MediaDecoder {
     /// Creates a new media player stream and returns the receiver channel
     pub fn new(input: &str, state: SharedPlaybackState) -> Result<(Self, MediaStreams)> {
+        Self::new_with_options(input, state, StreamingOptions::default())
+    }
+
+    /// Creates a new media player stream using [StreamingOptions] for RTSP/RTMP inputs
+    pub fn new_with_options(
+        input: &str,
+        state: SharedPlaybackState,
+        options: StreamingOptions,
+    ) -> Result<(Self, MediaStreams)> {
+        Self::new_with_hw_pref(input, state, options, HwDecoderPref::default())
+    }
+
+    /// Creates a new media player stream using a [HwDecoderPref] for video decoding
+    pub fn new_with_hw_pref(
+        input: &str,
+        state: SharedPlaybackState,
+        options: StreamingOptions,
+        hw_pref: HwDecoderPref,
+    ) -> Result<(Self, MediaStreams)> {
+        Self::spawn_decoder(input, None, state, options, hw_pref)
+    }
+
+    /// Creates a new media player stream from media held in memory, without writing a
+    /// temporary file. The media must be playable without seeking back in the data
+    /// (e.g. mp4 files need the `moov` atom at the start). Seeking back decodes the data
+    /// again from the start up to the new position.
+    pub fn new_from_bytes(
+        data: Arc<[u8]>,
+        state: SharedPlaybackState,
+    ) -> Result<(Self, MediaStreams)> {
+        Self::new_from_bytes_with_options(
+            data,
+            state,
+            StreamingOptions::default(),
+            HwDecoderPref::default(),
+        )
+    }
+
+    /// Creates a new media player stream from media held in memory using [StreamingOptions]
+    /// and a [HwDecoderPref], see [MediaDecoder::new_from_bytes]
+    pub fn new_from_bytes_with_options(
+        data: Arc<[u8]>,
+        state: SharedPlaybackState,
+        options: StreamingOptions,
+        hw_pref: HwDecoderPref,
+    ) -> Result<(Self, MediaStreams)> {
+        Self::spawn_decoder("memory", Some(data), state, options, hw_pref)
+    }
+
+    /// Read the duration, streams and metadata of the media without starting a decoder
+    /// thread, e.g. for file browsers or building playlists
+    pub fn probe_only(path: &str) -> Result<DecoderInfo> {
+        let path = file_uri_to_path(path, cfg!(windows)).unwrap_or_else(|| path.to_string());
+        #[cfg(feature = "ffmpeg")]
+        {
+            ffmpeg::FfmpegDecoder::probe_only(&path)
+        }
+        #[cfg(not(feature = "ffmpeg"))]
+        {
+            bail!("Probing {} is not supported without ffmpeg", path)
+        }
+    }
+
+    fn spawn_decoder(
+        input: &str,
+        bytes: Option<Arc<[u8]>>,
+        state: SharedPlaybackState,
+        options: StreamingOptions,
+        hw_pref: HwDecoderPref,
+    ) -> Result<(Self, MediaStreams)> {
         let (tx_m, rx_m) = sync_channel(1);
-        let (tx_v, rx_v) = sync_channel(10);
+        let (tx_v, rx_v) = sync_channel(options.video_capacity(StreamingOptions::DEFAULT_FPS));
         let (tx_a, rx_a) = sync_channel(1_000);
         let (tx_s, rx_s) = sync_channel(10);
+        let (tx_vr, rx_vr) = sync_channel(1);
 
         let thread_data = MediaDecoderThreadData {
-            path: input.to_string(),
+            path: file_uri_to_path(input, cfg!(windows)).unwrap_or_else(|| input.to_string()),
+            bytes,
+            options,
+            hw_pref,
             playback: state,
+            stop_flag: Arc::new(AtomicBool::new(false)),
             tx_m,
             tx_v,
             tx_a,
             tx_s,
+            tx_vr,
         };
         let mut internal = Self::create_decoder(thread_data.clone())?;
         let thread = internal.start()?;
@@ -176,16 +519,202 @@ impl MediaDecoder {
                 video: rx_v,
                 audio: rx_a,
                 subtitle: rx_s,
+                video_resized: rx_vr,
             },
         ))
     }
 
+    /// Stop the decoder thread, it exits before decoding the next packet or while waiting
+    /// for room in a full channel
+    pub fn cancel(&mut self) {
+        self.data.stop_flag.store(true, Ordering::Relaxed);
+    }
+
     #[allow(unused_variables)]
     fn create_decoder(data: MediaDecoderThreadData) -> Result<Box<dyn MediaDecoderImpl>> {
         #[cfg(feature = "ffmpeg")]
-        return Ok(Box::new(ffmpeg::FfmpegDecoder::new(data)));
+        {
+            let hw_pref = data.hw_pref;
+            return Ok(Box::new(
+                ffmpeg::FfmpegDecoder::new(data).with_hw_pref(hw_pref),
+            ));
+        }
         #[cfg(feature = "avfoundation")]
         return Ok(Box::new(avfoundation::AvFoundationDecoder::new(data)));
         bail!("No decoder impl available!")
     }
 }
+
+/// Convert a `file://` URI into a local path, percent-decoding it. On Windows the
+/// drive letter is kept (`file:///C:/a.mp4` is `C:\a.mp4`). [None] for other inputs
+fn file_uri_to_path(input: &str, windows: bool) -> Option<String> {
+    let rest = input
+        .get(..7)
+        .filter(|s| s.eq_ignore_ascii_case("file://"))
+        .map(|_| &input[7..])?;
+    let rest = rest.strip_prefix("localhost").unwrap_or(rest);
+    let path = percent_decode(rest);
+    if windows {
+        let path = match path.as_bytes() {
+            [b'/', drive, b':', ..] if drive.is_ascii_alphabetic() => &path[1..],
+            _ => &path,
+        };
+        Some(path.replace('/', "\\"))
+    } else {
+        Some(path)
+    }
+}
+
+/// Decode `%XX` escapes, invalid escapes are kept as they are
+fn percent_decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && let Some(b) = input
+                .get(i + 1..i + 3)
+                .and_then(|h| u8::from_str_radix(h, 16).ok())
+        {
+            out.push(b);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+impl Drop for MediaDecoder {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn media_metadata() {
+        let tags = HashMap::from([
+            ("title".to_string(), "Song".to_string()),
+            ("album_artist".to_string(), "Band".to_string()),
+            ("date".to_string(), "2019-05-01".to_string()),
+            ("comment".to_string(), " ".to_string()),
+        ]);
+        let md = MediaMetadata::from_tags(&tags);
+        assert_eq!(md.title.as_deref(), Some("Song"));
+        assert_eq!(md.artist.as_deref(), Some("Band"));
+        assert_eq!(md.year, Some(2019));
+        assert_eq!(md.comment, None);
+        assert_eq!(md.to_string(), "Band - Song [2019]");
+        assert!(MediaMetadata::from_tags(&HashMap::new()).is_empty());
+    }
+
+    #[test]
+    fn file_uri() {
+        assert_eq!(
+            file_uri_to_path("file:///home/user/my%20video.mp4", false).as_deref(),
+            Some("/home/user/my video.mp4")
+        );
+        assert_eq!(
+            file_uri_to_path("file:///C:/video.mp4", true).as_deref(),
+            Some("C:\\video.mp4")
+        );
+        assert_eq!(
+            file_uri_to_path("FILE://localhost/tmp/a%2", false).as_deref(),
+            Some("/tmp/a%2")
+        );
+        assert_eq!(file_uri_to_path("/tmp/a.mp4", false), None);
+        assert_eq!(file_uri_to_path("https://example.com/a.mp4", false), None);
+    }
+
+    #[test]
+    fn stream_info_display() {
+        let mut info = StreamInfo {
+            r#type: StreamType::Audio,
+            index: 1,
+            codec: "aac".to_string(),
+            format: "fltp".to_string(),
+            channels: 2,
+            sample_rate: 48_000,
+            width: 0,
+            height: 0,
+            fps: 0.0,
+            language: None,
+            bitrate: 0,
+            sar: None,
+        };
+        assert_eq!(info.to_string(), "A #1: aac 2ch fltp@48000 unk");
+        info.bitrate = 128_000;
+        assert_eq!(info.to_string(), "A #1: aac 2ch fltp@48000 unk 128kbps");
+    }
+
+    #[test]
+    fn display_aspect_ratio() {
+        let mut info = StreamInfo {
+            r#type: StreamType::Video,
+            index: 0,
+            codec: "mpeg2video".to_string(),
+            format: "yuv420p".to_string(),
+            channels: 0,
+            sample_rate: 0,
+            width: 720,
+            height: 576,
+            fps: 25.0,
+            language: None,
+            bitrate: 0,
+            sar: None,
+        };
+        assert_eq!(info.display_aspect_ratio(), 1.25);
+        info.sar = Some((16, 15));
+        assert_eq!(info.display_aspect_ratio(), 768.0 / 576.0);
+    }
+
+    #[test]
+    fn streaming_options() {
+        assert!(StreamingOptions::is_realtime("rtsp://camera.local/live"));
+        assert!(StreamingOptions::is_realtime("RTMP://example.com/app/key"));
+        assert!(!StreamingOptions::is_realtime(
+            "https://example.com/video.mp4"
+        ));
+        assert!(!StreamingOptions::is_realtime("/home/user/rtsp.mp4"));
+
+        let opts = StreamingOptions::default();
+        assert_eq!(
+            opts.input_url("rtsp://camera.local/live"),
+            "rtsp://camera.local/live?tcp"
+        );
+        assert_eq!(
+            opts.input_url("rtsp://camera.local/live?channel=1"),
+            "rtsp://camera.local/live?channel=1&tcp"
+        );
+        assert_eq!(
+            opts.input_url("rtmp://example.com/app/key"),
+            "rtmp://example.com/app/key"
+        );
+
+        assert_eq!(opts.video_capacity(StreamingOptions::DEFAULT_FPS), 10);
+        assert_eq!(opts.video_capacity(120.0), 40);
+        let opts = StreamingOptions {
+            preload_seconds: 0.0,
+            ..Default::default()
+        };
+        assert_eq!(opts.video_capacity(60.0), 1);
+    }
+
+    #[test]
+    fn video_filters() {
+        let f = VideoFilters::default();
+        assert!(f.is_default());
+        let f = VideoFilters {
+            brightness: 0.1,
+            contrast: 1.5,
+            saturation: 0.0,
+        };
+        assert!(!f.is_default());
+        assert_eq!(f.to_string(), "eq=brightness=0.1:contrast=1.5:saturation=0");
+    }
+}
@@ -3,11 +3,11 @@ use egui::{Align2, Color32, Pos2};
 use nom::branch::alt;
 use nom::bytes::complete::{is_not, tag, take_till, take_until, take_while_m_n};
 use nom::character::complete::{char, digit0, digit1};
-use nom::combinator::{map, map_res, opt, rest};
+use nom::combinator::{map, map_res, opt};
 use nom::error::context;
 use nom::multi::{many0, separated_list0};
 use nom::number::complete::double;
-use nom::sequence::{delimited, pair, preceded, tuple};
+use nom::sequence::{delimited, preceded, tuple};
 use nom::{IResult, Parser};
 
 use super::{FadeEffect, Subtitle};
@@ -16,6 +16,8 @@ enum SubtitleField<'a> {
     Fade(FadeEffect),
     Alignment(Align2),
     PrimaryFill(Color32),
+    SecondaryFill(Color32),
+    OutlineFill(Color32),
     Position(Pos2),
     Undefined(&'a str),
 }
@@ -96,24 +98,38 @@ fn pos(i: &str) -> IResult<&str, SubtitleField> {
 }
 
 // color parsing credit: example on https://github.com/rust-bakery/nom/tree/main
-fn from_hex(i: &str) -> Result<u8> {
-    Ok(u8::from_str_radix(i, 16)?)
-}
 fn is_hex_digit(c: char) -> bool {
     c.is_ascii_hexdigit()
 }
-fn hex_primary(i: &str) -> IResult<&str, u8> {
-    map_res(take_while_m_n(2, 2, is_hex_digit), from_hex).parse(i)
+/// Convert an ASS `&HBBGGRR&` color value into a [Color32], the alpha byte is ignored
+fn bgr_to_color32(bgr: u32) -> Color32 {
+    let [r, g, b, _] = bgr.to_le_bytes();
+    Color32::from_rgb(r, g, b)
 }
 fn hex_to_color32(i: &str) -> IResult<&str, Color32> {
-    let (i, (blue, green, red)) = tuple((hex_primary, hex_primary, hex_primary))(i)?;
-    Ok((i, Color32::from_rgb(red, green, blue)))
+    map(
+        map_res(take_while_m_n(1, 8, is_hex_digit), |s| {
+            u32::from_str_radix(s, 16)
+        }),
+        bgr_to_color32,
+    )
+    .parse(i)
 }
 fn c(i: &str) -> IResult<&str, SubtitleField> {
     delimited(
-        alt((tag(r"\c&H"), tag(r"\1c&H"))),
-        map(hex_to_color32, SubtitleField::PrimaryFill),
-        tag("&"),
+        char('\\'),
+        alt((
+            preceded(
+                alt((tag("c&H"), tag("1c&H"))),
+                map(hex_to_color32, SubtitleField::PrimaryFill),
+            ),
+            preceded(
+                tag("2c&H"),
+                map(hex_to_color32, SubtitleField::SecondaryFill),
+            ),
+            preceded(tag("3c&H"), map(hex_to_color32, SubtitleField::OutlineFill)),
+        )),
+        opt(char('&')),
     )
     .parse(i)
 }
@@ -124,33 +140,47 @@ fn undefined(i: &str) -> IResult<&str, SubtitleField> {
     )
     .parse(i)
 }
-fn parse_style(i: &str) -> IResult<&str, Subtitle> {
-    let (i, subtitle_style_components) = delimited(
+/// Override block, e.g. `{\an8\c&H0000FF&}`
+fn override_block(i: &str) -> IResult<&str, Vec<SubtitleField<'_>>> {
+    delimited(
         char('{'),
         many0(alt((t, fad, an, pos, c, undefined))),
         tuple((take_until("}"), char('}'))),
     )
-    .parse(i)?;
-
-    let mut subtitle = Subtitle::default();
+    .parse(i)
+}
 
-    for component in subtitle_style_components {
+fn apply_style(subtitle: &mut Subtitle, components: Vec<SubtitleField>) {
+    for component in components {
         match component {
             SubtitleField::Fade(fade) => subtitle.fade = fade,
             SubtitleField::Alignment(alignment) => subtitle.alignment = alignment,
             SubtitleField::PrimaryFill(primary_fill) => subtitle.primary_fill = primary_fill,
+            SubtitleField::SecondaryFill(fill) => subtitle._secondary_fill = fill,
+            SubtitleField::OutlineFill(fill) => subtitle.outline_fill = Some(fill),
             SubtitleField::Position(position) => subtitle.position = Some(position),
             SubtitleField::Undefined(_) => (),
         }
     }
-    Ok((i, subtitle))
 }
 
+/// Apply every override block in the text, later blocks replace earlier values
 fn text_field(i: &str) -> IResult<&str, Subtitle> {
-    let (i, (subtitle, subtitle_text)) =
-        preceded(opt_comma, pair(opt(parse_style), rest)).parse(i)?;
-    let mut subtitle = subtitle.unwrap_or_default();
-    subtitle.text = subtitle_text.replace(r"\N", "\n");
+    let (mut i, _) = opt_comma(i)?;
+    let mut subtitle = Subtitle::default();
+    let mut text = String::with_capacity(i.len());
+    while !i.is_empty() {
+        if let Ok((r, components)) = override_block(i) {
+            apply_style(&mut subtitle, components);
+            i = r;
+        } else {
+            let first = i.chars().next().map_or(1, char::len_utf8);
+            let end = i[first..].find('{').map(|x| x + first).unwrap_or(i.len());
+            text.push_str(&i[..end]);
+            i = &i[end..];
+        }
+    }
+    subtitle.text = text.replace(r"\N", "\n");
     Ok((i, subtitle))
 }
 
@@ -190,3 +220,95 @@ pub(crate) fn parse_ass_subtitle(i: &str) -> Result<Subtitle> {
 
     Ok(subtitle)
 }
+
+/// Parse all `Dialogue:` entries of an `.ass`/`.ssa` file
+pub(crate) fn parse_ass_file(input: &str) -> Result<Vec<Subtitle>> {
+    let mut ret = Vec::new();
+    for line in input.lines() {
+        let Some(dialogue) = line.trim().strip_prefix("Dialogue:") else {
+            continue;
+        };
+        // Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text
+        let fields = dialogue.trim().splitn(10, ',').collect::<Vec<_>>();
+        if fields.len() != 10 {
+            continue;
+        }
+        let (Ok((_, start)), Ok((_, end))) = (
+            super::parse_timestamp(fields[1].trim()),
+            super::parse_timestamp(fields[2].trim()),
+        ) else {
+            continue;
+        };
+        // same layout as ffmpeg's ASS packets: ReadOrder, Layer, Style, Name, MarginL, MarginR, MarginV, Effect, Text
+        let packet = format!(
+            "0,{},{},{},{},{},{},{},{}",
+            fields[0], fields[3], fields[4], fields[5], fields[6], fields[7], fields[8], fields[9]
+        );
+        let mut sub = parse_ass_subtitle(&packet)?;
+        sub.pts = start;
+        sub.duration = end - start;
+        ret.push(sub);
+    }
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn color_byte_order() {
+        assert_eq!(bgr_to_color32(0x0000FF), Color32::from_rgb(255, 0, 0));
+        assert_eq!(bgr_to_color32(0x00FF00), Color32::from_rgb(0, 255, 0));
+        assert_eq!(bgr_to_color32(0xFF0000), Color32::from_rgb(0, 0, 255));
+        assert_eq!(
+            bgr_to_color32(0x80336699),
+            Color32::from_rgb(0x99, 0x66, 0x33)
+        );
+        assert_eq!(
+            hex_to_color32("FF&").unwrap().1,
+            Color32::from_rgb(255, 0, 0)
+        );
+    }
+
+    #[test]
+    fn parse_colors() {
+        let sub = parse_ass_subtitle(r"0,0,Default,,0,0,0,,{\c&HFF0000&}Blue").unwrap();
+        assert_eq!(sub.text, "Blue");
+        assert_eq!(sub.primary_fill, Color32::from_rgb(0, 0, 255));
+        assert_eq!(sub.outline_fill, None);
+
+        let sub = parse_ass_subtitle(
+            r"0,0,Default,,0,0,0,,{\an8\1c&H00FF00&\2c&H0000FF&}{\3c&H000000&}Hello{\i1}\Nworld",
+        )
+        .unwrap();
+        assert_eq!(sub.text, "Hello\nworld");
+        assert_eq!(sub.alignment, Align2::CENTER_TOP);
+        assert_eq!(sub.primary_fill, Color32::from_rgb(0, 255, 0));
+        assert_eq!(sub._secondary_fill, Color32::from_rgb(255, 0, 0));
+        assert_eq!(sub.outline_fill, Some(Color32::BLACK));
+    }
+
+    #[test]
+    fn parse_non_ascii() {
+        let sub = parse_ass_subtitle(r"0,0,Default,,0,0,0,,日本{\i1}語").unwrap();
+        assert_eq!(sub.text, "日本語");
+
+        let sub = parse_ass_subtitle(r"0,0,Default,,0,0,0,,{\an8}é{\b1}x").unwrap();
+        assert_eq!(sub.text, "éx");
+        assert_eq!(sub.alignment, Align2::CENTER_TOP);
+    }
+
+    #[test]
+    fn parse_position() {
+        let mut sub = parse_ass_subtitle(r"0,0,Default,,0,0,0,,{\an8\pos(640,180)}Top").unwrap();
+        assert_eq!(sub.text, "Top");
+        assert_eq!(sub.position, Some(Pos2::new(640.0, 180.0)));
+
+        let rect = egui::Rect::from_min_size(Pos2::new(10.0, 0.0), egui::vec2(640.0, 360.0));
+        // unknown resolution uses the default placement
+        assert_eq!(sub.anchor_in(rect), None);
+        sub.resolution = egui::vec2(1280.0, 720.0);
+        assert_eq!(sub.anchor_in(rect), Some(Pos2::new(330.0, 90.0)));
+    }
+}
@@ -1,11 +1,21 @@
-use crate::ffmpeg_sys_the_third::AVCodecID;
-use crate::subtitle::ass::parse_ass_subtitle;
-use crate::subtitle::srt::parse_srt_subtitle;
+use crate::subtitle::ass::{parse_ass_file, parse_ass_subtitle};
+use crate::subtitle::srt::{parse_srt_file, parse_srt_subtitle};
+use crate::subtitle::webvtt::{parse_webvtt_file, parse_webvtt_subtitle};
+use anyhow::{bail, Result};
 use egui::text::LayoutJob;
-use egui::{vec2, Align2, Color32, FontId, Margin, Pos2, Response, Stroke, TextFormat, Ui, Widget};
+use egui::{
+    vec2, Align2, Color32, FontId, Margin, Pos2, Rect, Response, Sense, Stroke, TextFormat, Ui,
+    Vec2, Widget,
+};
+use ffmpeg_rs_raw::ffmpeg_sys_the_third::AVCodecID;
+use nom::character::complete::{char, digit1, one_of};
+use nom::combinator::{map, map_res};
+use nom::{IResult, Parser};
+use std::path::Path;
 
 mod ass;
 mod srt;
+mod webvtt;
 
 #[derive(Debug)]
 pub struct Subtitle {
@@ -13,17 +23,54 @@ pub struct Subtitle {
     fade: FadeEffect,
     alignment: Align2,
     primary_fill: Color32,
+    /// Karaoke fill, not rendered yet
+    _secondary_fill: Color32,
+    /// Text outline, only drawn when set
+    outline_fill: Option<Color32>,
+    /// Absolute position from `\pos`, in pixels of [Subtitle::resolution]
     position: Option<Pos2>,
+    /// Resolution of the video the position refers to, zero when unknown
+    resolution: Vec2,
     font_size: f32,
     margin: Margin,
     bold: bool,
     italic: bool,
     underline: bool,
     strikethrough: bool,
+    /// Runs of text with their own style, rendered instead of [Subtitle::text] when not empty
+    segments: Vec<TextSegment>,
+    /// Moves the subtitle away from its default position, set by dragging it
+    pub(crate) offset: Vec2,
+    /// Show at the top of the frame instead of the bottom, used for the secondary track
+    pub(crate) at_top: bool,
     pub(crate) pts: f64,
     pub(crate) duration: f64,
 }
 
+/// Run of subtitle text with inline styling
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TextSegment {
+    text: String,
+    bold: bool,
+    italic: bool,
+    underline: bool,
+    strikethrough: bool,
+}
+
+/// Global overrides applied to every subtitle, e.g. for accessibility.
+/// Fields which are [None] keep the style from the subtitle itself.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SubtitleStyle {
+    /// Font size in points
+    pub font_size: Option<f32>,
+    /// Text color
+    pub color: Option<Color32>,
+    /// Distance from the edges of the video
+    pub margin: Option<Margin>,
+    /// Draw all text bold
+    pub bold: Option<bool>,
+}
+
 #[derive(Debug, Default)]
 pub struct FadeEffect {
     _fade_in_ms: i64,
@@ -44,24 +91,38 @@ impl Default for Subtitle {
             italic: false,
             underline: false,
             strikethrough: false,
-            pts: 0,
+            segments: Vec::new(),
+            offset: Vec2::ZERO,
+            at_top: false,
+            pts: 0.0,
             alignment: Align2::CENTER_CENTER,
             primary_fill: Color32::WHITE,
+            _secondary_fill: Color32::RED,
+            outline_fill: None,
             position: None,
-            duration: 0,
+            resolution: Vec2::ZERO,
+            duration: 0.0,
         }
     }
 }
 
 impl Subtitle {
-    pub(crate) fn new(text: String, pts: i64, duration: i64, codec: AVCodecID) -> Self {
+    pub(crate) fn new(
+        text: String,
+        pts: f64,
+        duration: f64,
+        codec: AVCodecID,
+        video_resolution: Vec2,
+    ) -> Self {
         if let Some(mut sub) = match codec {
             AVCodecID::AV_CODEC_ID_ASS => parse_ass_subtitle(&text).ok(),
             AVCodecID::AV_CODEC_ID_SUBRIP => parse_srt_subtitle(&text).ok(),
+            AVCodecID::AV_CODEC_ID_WEBVTT => parse_webvtt_subtitle(&text).ok(),
             _ => None,
         } {
             sub.pts = pts;
             sub.duration = duration;
+            sub.resolution = video_resolution;
             return sub;
         }
 
@@ -69,17 +130,101 @@ impl Subtitle {
             text,
             pts,
             duration,
+            resolution: video_resolution,
             ..Default::default()
         }
     }
 }
 
+/// Load all timed entries from a `.srt`, `.vtt` or `.ass`/`.ssa` subtitle file
+pub(crate) fn load_subtitle_file(path: &str) -> Result<Vec<Subtitle>> {
+    let text = std::fs::read_to_string(path)?;
+    let ext = Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+    match ext.as_deref() {
+        Some("srt") => parse_srt_file(&text),
+        Some("ass") | Some("ssa") => parse_ass_file(&text),
+        Some("vtt") => parse_webvtt_file(&text),
+        _ => bail!("Unsupported subtitle file: {}", path),
+    }
+}
+
+fn number(i: &str) -> IResult<&str, f64> {
+    map_res(digit1, str::parse::<f64>).parse(i)
+}
+
+/// Parse a `H:MM:SS.fff` / `HH:MM:SS,fff` timestamp into seconds
+pub(crate) fn parse_timestamp(i: &str) -> IResult<&str, f64> {
+    map(
+        (
+            number,
+            char(':'),
+            number,
+            char(':'),
+            number,
+            one_of(",."),
+            digit1,
+        ),
+        |(h, _, m, _, s, _, frac): (f64, char, f64, char, f64, char, &str)| {
+            let frac = frac.parse::<f64>().unwrap_or(0.0) / 10f64.powi(frac.len() as i32);
+            h * 3600.0 + m * 60.0 + s + frac
+        },
+    )
+    .parse(i)
+}
+
 impl FadeEffect {
     fn _is_zero(&self) -> bool {
         self._fade_in_ms == 0 && self._fade_out_ms == 0
     }
 }
 
+impl Subtitle {
+    /// Override the parsed style with any fields set in `style`
+    pub(crate) fn apply_style(&mut self, style: &SubtitleStyle) {
+        if let Some(size) = style.font_size {
+            self.font_size = size;
+        }
+        if let Some(color) = style.color {
+            self.primary_fill = color;
+        }
+        if let Some(margin) = style.margin {
+            self.margin = margin;
+        }
+        if let Some(bold) = style.bold {
+            self.bold = bold;
+            for seg in self.segments.iter_mut() {
+                seg.bold = bold;
+            }
+        }
+    }
+
+    /// Point in `rect` where the subtitle is anchored by its alignment, when it has
+    /// an absolute position. The position is scaled from the video resolution to `rect`
+    fn anchor_in(&self, rect: Rect) -> Option<Pos2> {
+        let position = self.position?;
+        if self.resolution.x <= 0.0 || self.resolution.y <= 0.0 {
+            return None;
+        }
+        Some(rect.min + position.to_vec2() / self.resolution * rect.size())
+    }
+
+    fn text_format(&self, italic: bool, underline: bool, strikethrough: bool) -> TextFormat {
+        let line = Stroke::new((self.font_size * 0.05).min(1.0), self.primary_fill);
+        TextFormat {
+            font_id: FontId::proportional(self.font_size),
+            color: self.primary_fill,
+            valign: self.alignment.x(),
+            italics: italic,
+            strikethrough: if strikethrough { line } else { Stroke::NONE },
+            underline: if underline { line } else { Stroke::NONE },
+            ..Default::default()
+        }
+    }
+}
+
 impl Widget for &Subtitle {
     fn ui(self, ui: &mut Ui) -> Response {
         let rect = ui.available_rect_before_wrap();
@@ -87,36 +232,56 @@ impl Widget for &Subtitle {
         let mut job = LayoutJob::default();
         job.halign = self.alignment.y();
 
-        let format = TextFormat {
-            font_id: FontId::proportional(self.font_size),
-            color: self.primary_fill,
-            valign: self.alignment.x(),
-            italics: self.italic,
-            strikethrough: if self.strikethrough {
-                Stroke::new((self.font_size * 0.05).min(1.0), self.primary_fill)
-            } else {
-                Stroke::NONE
-            },
-            underline: if self.underline {
-                Stroke::new((self.font_size * 0.05).min(1.0), self.primary_fill)
-            } else {
-                Stroke::NONE
-            },
-            ..Default::default()
-        };
-        job.append(&self.text, 0.0, format);
+        if self.segments.is_empty() {
+            job.append(
+                &self.text,
+                0.0,
+                self.text_format(self.italic, self.underline, self.strikethrough),
+            );
+        } else {
+            for seg in &self.segments {
+                job.append(
+                    &seg.text,
+                    0.0,
+                    self.text_format(seg.italic, seg.underline, seg.strikethrough),
+                );
+            }
+        }
         let painter = ui.painter();
         let galley = painter.layout_job(job);
 
-        let pos = rect.min
-            + vec2(
-                rect.width() / 2.0,
-                rect.height() - 30.0 - galley.rect.height(),
-            );
+        let pos = if let Some(anchor) = self.anchor_in(rect) {
+            let text_rect = self.alignment.anchor_size(anchor, galley.rect.size());
+            text_rect.min - galley.rect.min.to_vec2()
+        } else {
+            let y = if self.at_top {
+                30.0 + self.margin.top as f32
+            } else {
+                rect.height() - 30.0 - self.margin.bottom as f32 - galley.rect.height()
+            };
+            rect.min
+                + vec2(
+                    rect.width() / 2.0 + (self.margin.left as f32 - self.margin.right as f32) / 2.0,
+                    y,
+                )
+        } + self.offset;
+        if let Some(outline) = self.outline_fill {
+            let w = (self.font_size * 0.05).max(1.0);
+            for offset in [vec2(-w, 0.0), vec2(w, 0.0), vec2(0.0, -w), vec2(0.0, w)] {
+                painter.galley_with_override_text_color(pos + offset, galley.clone(), outline);
+            }
+        }
+        if self.bold {
+            // no bold font is loaded, draw a second copy shifted by a pixel instead
+            painter.galley(pos + vec2(1.0, 0.0), galley.clone(), Color32::TRANSPARENT);
+        }
+        let text_rect = galley.rect.translate(pos.to_vec2());
         painter.galley(pos, galley.clone(), Color32::TRANSPARENT);
 
-        // TODO(v0l): stroke text
-
-        ui.response()
+        ui.interact(
+            text_rect,
+            ui.id().with(("subtitle", self.at_top)),
+            Sense::drag(),
+        )
     }
 }
@@ -1,100 +1,176 @@
-use nom::branch::alt;
-use nom::bytes::complete::{tag, take_until};
-use nom::combinator::{all_consuming, map, rest};
-use nom::error::Error;
-use nom::sequence::delimited;
+use super::TextSegment;
+use nom::bytes::complete::{tag, tag_no_case, take_until};
+use nom::character::complete::{char, one_of, space0};
+use nom::combinator::{map, opt};
+use nom::sequence::{delimited, separated_pair};
 use nom::IResult;
 use nom::Parser;
 use std::default::Default;
+use std::ops::Range;
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum TagKind {
-    Unknown,
     Bold,
     Italic,
     Underline,
     Strikethrough,
 }
 
+/// Style flags of a run of text, from all the tags it is nested in
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TagStyle {
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub strikethrough: bool,
+}
+
 pub struct Tagged<'a> {
-    pub kind: TagKind,
+    pub style: TagStyle,
     pub content: &'a str,
 }
 
-fn i(input: &str) -> IResult<&str, Tagged> {
+/// Open (`<b>`) or close (`</b>`) style tag
+fn style_tag(input: &str) -> IResult<&str, (bool, TagKind)> {
     delimited(
-        tag("<i>"),
-        map(take_until("</i>"), |t| Tagged {
-            kind: TagKind::Italic,
-            content: t,
-        }),
-        tag("</i>"),
+        char('<'),
+        (
+            map(opt(char('/')), |c| c.is_some()),
+            map(one_of("biusBIUS"), |c| match c.to_ascii_lowercase() {
+                'b' => TagKind::Bold,
+                'i' => TagKind::Italic,
+                'u' => TagKind::Underline,
+                _ => TagKind::Strikethrough,
+            }),
+        ),
+        char('>'),
     )
     .parse(input)
 }
 
-fn b(input: &str) -> IResult<&str, Tagged> {
+/// `<font ...>` tags, which are not supported and skipped
+fn font_tag(input: &str) -> IResult<&str, &str> {
     delimited(
-        tag("<b>"),
-        map(take_until("</b>"), |t| Tagged {
-            kind: TagKind::Bold,
-            content: t,
-        }),
-        tag("</b>"),
+        (char('<'), opt(char('/')), tag_no_case("font")),
+        take_until(">"),
+        char('>'),
     )
     .parse(input)
 }
 
-fn u(input: &str) -> IResult<&str, Tagged> {
-    delimited(
-        tag("<u>"),
-        map(take_until("</u>"), |t| Tagged {
-            kind: TagKind::Underline,
-            content: t,
-        }),
-        tag("</u>"),
-    )
-    .parse(input)
+/// Split the input into runs of text with the style of the tags they are nested in.
+/// Also returns the combined style of every tag used in the input.
+fn tagged(input: &str) -> (Vec<Tagged<'_>>, TagStyle) {
+    // open tag count per [TagKind]
+    let mut depth = [0u32; 4];
+    let mut used = TagStyle::default();
+    let mut ret: Vec<(TagStyle, Range<usize>)> = Vec::new();
+    let mut pos = 0;
+    while pos < input.len() {
+        let rest = &input[pos..];
+        if let Ok((r, (close, kind))) = style_tag(rest) {
+            let d = &mut depth[kind as usize];
+            if close {
+                *d = d.saturating_sub(1);
+            } else {
+                *d += 1;
+                match kind {
+                    TagKind::Bold => used.bold = true,
+                    TagKind::Italic => used.italic = true,
+                    TagKind::Underline => used.underline = true,
+                    TagKind::Strikethrough => used.strikethrough = true,
+                }
+            }
+            pos = input.len() - r.len();
+            continue;
+        }
+        if let Ok((r, _)) = font_tag(rest) {
+            pos = input.len() - r.len();
+            continue;
+        }
+
+        // plain text up to the next possible tag
+        let first = rest.chars().next().map_or(1, char::len_utf8);
+        let end = pos
+            + rest[first..]
+                .find('<')
+                .map(|i| i + first)
+                .unwrap_or(rest.len());
+        let style = TagStyle {
+            bold: depth[TagKind::Bold as usize] > 0,
+            italic: depth[TagKind::Italic as usize] > 0,
+            underline: depth[TagKind::Underline as usize] > 0,
+            strikethrough: depth[TagKind::Strikethrough as usize] > 0,
+        };
+        // merge with the previous run when no tag was between them
+        match ret.last_mut() {
+            Some((last_style, range)) if *last_style == style && range.end == pos => {
+                range.end = end;
+            }
+            _ => ret.push((style, pos..end)),
+        }
+        pos = end;
+    }
+    let ret = ret
+        .into_iter()
+        .map(|(style, range)| Tagged {
+            style,
+            content: &input[range],
+        })
+        .collect();
+    (ret, used)
 }
 
-fn s(input: &str) -> IResult<&str, Tagged> {
-    delimited(
-        tag("<s>"),
-        map(take_until("</s>"), |t| Tagged {
-            kind: TagKind::Strikethrough,
-            content: t,
-        }),
-        tag("</s>"),
-    )
-    .parse(input)
+pub(crate) fn parse_srt_subtitle(input: &str) -> Result<super::Subtitle, anyhow::Error> {
+    let (runs, used) = tagged(input);
+    Ok(super::Subtitle {
+        text: runs.iter().map(|r| r.content).collect(),
+        bold: used.bold,
+        italic: used.italic,
+        underline: used.underline,
+        strikethrough: used.strikethrough,
+        segments: runs
+            .iter()
+            .map(|r| TextSegment {
+                text: r.content.to_owned(),
+                bold: r.style.bold,
+                italic: r.style.italic,
+                underline: r.style.underline,
+                strikethrough: r.style.strikethrough,
+            })
+            .collect(),
+        ..super::Subtitle::default()
+    })
 }
 
-fn any(input: &str) -> IResult<&str, Tagged<'_>, Error<&str>> {
-    all_consuming(alt((
-        i,
-        b,
-        u,
-        s,
-        map(rest, |r| Tagged {
-            kind: TagKind::Unknown,
-            content: r,
-        }),
-    )))
+fn timing(input: &str) -> IResult<&str, (f64, f64)> {
+    separated_pair(
+        super::parse_timestamp,
+        (space0, tag("-->"), space0),
+        super::parse_timestamp,
+    )
     .parse(input)
 }
 
-pub(crate) fn parse_srt_subtitle(input: &str) -> Result<super::Subtitle, anyhow::Error> {
-    any(input)
-        .map(|(_, srt)| {
-            Ok(super::Subtitle {
-                text: srt.content.to_owned(),
-                bold: matches!(srt.kind, TagKind::Bold),
-                italic: matches!(srt.kind, TagKind::Italic),
-                underline: matches!(srt.kind, TagKind::Underline),
-                strikethrough: matches!(srt.kind, TagKind::Strikethrough),
-                ..super::Subtitle::default()
-            })
-        })
-        .map_err(|e| anyhow::Error::msg(e.to_string()))?
+/// Parse all entries of a `.srt` file
+pub(crate) fn parse_srt_file(input: &str) -> Result<Vec<super::Subtitle>, anyhow::Error> {
+    let input = input.replace("\r\n", "\n");
+    let mut ret = Vec::new();
+    for block in input.split("\n\n") {
+        let mut lines = block.trim().lines().skip_while(|l| !l.contains("-->"));
+        let Some(Ok((_, (start, end)))) = lines.next().map(timing) else {
+            continue;
+        };
+        let text = lines.collect::<Vec<_>>().join("\n");
+        let mut sub = parse_srt_subtitle(&text).unwrap_or_else(|_| super::Subtitle {
+            text,
+            ..super::Subtitle::default()
+        });
+        sub.pts = start;
+        sub.duration = end - start;
+        ret.push(sub);
+    }
+    Ok(ret)
 }
 
 #[cfg(test)]
@@ -159,6 +235,72 @@ mod tests {
     #[test]
     fn parse_extra_chars() {
         let input = "<s></s> Some other text here";
-        assert!(parse_srt_subtitle(input).is_err())
+        let i = parse_srt_subtitle(input).unwrap();
+        assert_eq!(i.text, " Some other text here");
+        assert_eq!(i.segments.len(), 1);
+        assert_eq!(i.segments[0].strikethrough, false);
+    }
+
+    #[test]
+    fn parse_nested() {
+        let input = "<b><i>word</i></b> and <i>line 1\nline 2</i>";
+        let i = parse_srt_subtitle(input).unwrap();
+        assert_eq!(i.text, "word and line 1\nline 2");
+        assert_eq!(i.bold, true);
+        assert_eq!(i.italic, true);
+        assert_eq!(i.segments.len(), 3);
+        assert_eq!(i.segments[0].text, "word");
+        assert_eq!(i.segments[0].bold, true);
+        assert_eq!(i.segments[0].italic, true);
+        assert_eq!(i.segments[1].text, " and ");
+        assert_eq!(i.segments[1].bold, false);
+        assert_eq!(i.segments[1].italic, false);
+        assert_eq!(i.segments[2].text, "line 1\nline 2");
+        assert_eq!(i.segments[2].bold, false);
+        assert_eq!(i.segments[2].italic, true);
+    }
+
+    #[test]
+    fn parse_sequential() {
+        let input = "<u>one</u><s>two</s> <font color=\"red\">a < b</font>";
+        let i = parse_srt_subtitle(input).unwrap();
+        assert_eq!(i.text, "onetwo a < b");
+        assert_eq!(i.segments.len(), 4);
+        assert_eq!(i.segments[0].underline, true);
+        assert_eq!(i.segments[0].strikethrough, false);
+        assert_eq!(i.segments[1].text, "two");
+        assert_eq!(i.segments[1].underline, false);
+        assert_eq!(i.segments[1].strikethrough, true);
+        assert_eq!(i.segments[2].text, " ");
+        assert_eq!(i.segments[3].text, "a < b");
+        assert_eq!(i.segments[3].strikethrough, false);
+    }
+
+    #[test]
+    fn parse_non_ascii() {
+        let input = "é<b>x</b>日本<i>語</i>";
+        let i = parse_srt_subtitle(input).unwrap();
+        assert_eq!(i.text, "éx日本語");
+        assert_eq!(i.segments.len(), 4);
+        assert_eq!(i.segments[0].text, "é");
+        assert_eq!(i.segments[1].text, "x");
+        assert_eq!(i.segments[1].bold, true);
+        assert_eq!(i.segments[2].text, "日本");
+        assert_eq!(i.segments[3].text, "語");
+        assert_eq!(i.segments[3].italic, true);
+    }
+
+    #[test]
+    fn parse_file() {
+        let input = "1\r\n00:00:01,600 --> 00:00:04,200\r\n<i>First line</i>\r\n\r\n2\r\n00:01:02,000 --> 00:01:03,500\r\nSecond\r\nline\r\n";
+        let subs = parse_srt_file(input).unwrap();
+        assert_eq!(subs.len(), 2);
+        assert_eq!(subs[0].text, "First line");
+        assert_eq!(subs[0].italic, true);
+        assert_eq!(subs[0].pts, 1.6);
+        assert_eq!((subs[0].duration * 1000.0).round(), 2600.0);
+        assert_eq!(subs[1].text, "Second\nline");
+        assert_eq!(subs[1].pts, 62.0);
+        assert_eq!(subs[1].duration, 1.5);
     }
 }
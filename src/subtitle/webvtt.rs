@@ -0,0 +1,196 @@
+use anyhow::Result;
+use egui::Color32;
+use nom::branch::alt;
+use nom::bytes::complete::{tag, take_until, take_while1};
+use nom::character::complete::{char, digit1, space0};
+use nom::combinator::{map, opt};
+use nom::multi::many0;
+use nom::sequence::{delimited, preceded, separated_pair};
+use nom::{IResult, Parser};
+
+use super::srt::parse_srt_subtitle;
+use super::Subtitle;
+
+/// `MM:SS.mmm` timestamp, the hours are optional in WebVTT
+fn short_timestamp(i: &str) -> IResult<&str, f64> {
+    map(
+        (super::number, char(':'), super::number, char('.'), digit1),
+        |(m, _, s, _, frac): (f64, char, f64, char, &str)| {
+            let frac = frac.parse::<f64>().unwrap_or(0.0) / 10f64.powi(frac.len() as i32);
+            m * 60.0 + s + frac
+        },
+    )
+    .parse(i)
+}
+
+fn timestamp(i: &str) -> IResult<&str, f64> {
+    alt((super::parse_timestamp, short_timestamp)).parse(i)
+}
+
+/// Cue timing line, cue settings after the end time are ignored
+fn timing(i: &str) -> IResult<&str, (f64, f64)> {
+    separated_pair(timestamp, (space0, tag("-->"), space0), timestamp).parse(i)
+}
+
+/// `<c.class1.class2>` class span, returns the class names
+fn class_tag(i: &str) -> IResult<&str, Vec<&str>> {
+    delimited(
+        tag("<c"),
+        many0(preceded(
+            char('.'),
+            take_while1(|c: char| c != '.' && c != '>'),
+        )),
+        char('>'),
+    )
+    .parse(i)
+}
+
+/// Tags which are not rendered: `</c>`, voice `<v Name>`, language `<lang en>`,
+/// ruby `<ruby>` and karaoke timestamps `<00:01.000>`
+fn ignored_tag(i: &str) -> IResult<&str, &str> {
+    delimited(
+        (char('<'), opt(char('/'))),
+        alt((
+            map(
+                (
+                    alt((tag("c"), tag("v"), tag("lang"), tag("ruby"), tag("rt"))),
+                    take_until(">"),
+                ),
+                |(t, _)| t,
+            ),
+            map(timestamp, |_| ""),
+        )),
+        char('>'),
+    )
+    .parse(i)
+}
+
+/// Default WebVTT color classes
+fn class_color(class: &str) -> Option<Color32> {
+    Some(match class {
+        "white" => Color32::WHITE,
+        "lime" => Color32::from_rgb(0, 255, 0),
+        "cyan" => Color32::from_rgb(0, 255, 255),
+        "red" => Color32::RED,
+        "yellow" => Color32::YELLOW,
+        "magenta" => Color32::from_rgb(255, 0, 255),
+        "blue" => Color32::BLUE,
+        "black" => Color32::BLACK,
+        _ => return None,
+    })
+}
+
+/// Replace the character references of cue text, `&amp;` last so `&amp;lt;` stays `&lt;`
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+pub(crate) fn parse_webvtt_subtitle(text: &str) -> Result<Subtitle> {
+    let mut timing_pts = None;
+    let mut payload = Vec::new();
+    for line in text.lines() {
+        if let Ok((_, t)) = timing(line.trim()) {
+            timing_pts = Some(t);
+            // anything before the timing line is the cue identifier
+            payload.clear();
+            continue;
+        }
+        payload.push(line);
+    }
+    let payload = payload.join("\n");
+
+    // strip WebVTT specific tags, keeping the first color class
+    let mut color = None;
+    let mut stripped = String::with_capacity(payload.len());
+    let mut i = payload.as_str();
+    while !i.is_empty() {
+        if let Ok((r, classes)) = class_tag(i) {
+            color = color.or_else(|| classes.iter().find_map(|c| class_color(c)));
+            i = r;
+        } else if let Ok((r, _)) = ignored_tag(i) {
+            i = r;
+        } else {
+            let c = i.chars().next().unwrap_or_default();
+            stripped.push(c);
+            i = &i[c.len_utf8()..];
+        }
+    }
+
+    // <b>, <i>, <u> are the same as SRT, escaped text is only replaced after the tags
+    // were parsed so `&lt;b&gt;` is shown instead of making the text bold
+    let mut sub = parse_srt_subtitle(&stripped)?;
+    sub.text = unescape(&sub.text);
+    for seg in sub.segments.iter_mut() {
+        seg.text = unescape(&seg.text);
+    }
+    if let Some(color) = color {
+        sub.primary_fill = color;
+    }
+    if let Some((start, end)) = timing_pts {
+        sub.pts = start;
+        sub.duration = end - start;
+    }
+    Ok(sub)
+}
+
+/// Parse all cues of a `.vtt` file
+pub(crate) fn parse_webvtt_file(input: &str) -> Result<Vec<Subtitle>> {
+    let input = input.replace("\r\n", "\n");
+    let mut ret = Vec::new();
+    for block in input.split("\n\n") {
+        if !block.contains("-->") {
+            // header, NOTE, STYLE and REGION blocks
+            continue;
+        }
+        ret.push(parse_webvtt_subtitle(block.trim())?);
+    }
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_cue() {
+        let input = "intro\n00:01.500 --> 00:00:04.000 align:start\n<c.yellow.bg_black>Hello</c> <b>world</b>";
+        let sub = parse_webvtt_subtitle(input).unwrap();
+        assert_eq!(sub.text, "Hello world");
+        assert_eq!(sub.primary_fill, Color32::YELLOW);
+        assert_eq!(sub.bold, true);
+        assert_eq!(sub.pts, 1.5);
+        assert_eq!(sub.duration, 2.5);
+    }
+
+    #[test]
+    fn parse_payload() {
+        let input = "<v Bob><i>Hi</i> <00:00:01.000>there &amp; bye";
+        let sub = parse_webvtt_subtitle(input).unwrap();
+        assert_eq!(sub.text, "Hi there & bye");
+        assert_eq!(sub.italic, true);
+        assert_eq!(sub.primary_fill, Color32::WHITE);
+    }
+
+    #[test]
+    fn parse_escaped_tags() {
+        let input = "&lt;b&gt;not bold&lt;/b&gt; <i>x &amp;lt; y</i>";
+        let sub = parse_webvtt_subtitle(input).unwrap();
+        assert_eq!(sub.text, "<b>not bold</b> x &lt; y");
+        assert_eq!(sub.bold, false);
+        assert_eq!(sub.italic, true);
+    }
+
+    #[test]
+    fn parse_file() {
+        let input = "WEBVTT\n\nNOTE comment\n\n1\n00:00:01.000 --> 00:00:02.000\nFirst\n\n00:00:03.000 --> 00:00:05.000\n<u>Second</u>\nline\n";
+        let subs = parse_webvtt_file(input).unwrap();
+        assert_eq!(subs.len(), 2);
+        assert_eq!(subs[0].text, "First");
+        assert_eq!(subs[0].pts, 1.0);
+        assert_eq!(subs[1].text, "Second\nline");
+        assert_eq!(subs[1].underline, true);
+        assert_eq!(subs[1].duration, 2.0);
+    }
+}
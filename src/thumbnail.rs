@@ -0,0 +1,360 @@
+use crate::stream::{DecoderInfo, MediaDecoder, MediaStreams, StreamingOptions, VideoFrame};
+use crate::{NoAudioDevice, SharedPlaybackState};
+use anyhow::{Context, Result};
+use egui::{ColorImage, TextureHandle};
+use log::{debug, error, info};
+use std::collections::BTreeMap;
+use std::fmt::{Debug, Formatter};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, RwLock, Weak};
+use std::time::Duration;
+
+/// Width of generated thumbnails, height follows the video aspect ratio
+const THUMBNAIL_WIDTH: usize = 160;
+
+/// Most thumbnails kept for one media, the interval grows for long media
+const MAX_THUMBNAILS: usize = 200;
+
+type ThumbnailMap = BTreeMap<u64, TextureHandle>;
+
+/// Cache of video thumbnails taken at fixed intervals, used for seekbar previews.
+/// Thumbnails are decoded on a background thread which exits once every handle to the cache is dropped.
+#[derive(Clone)]
+pub struct ThumbnailCache {
+    /// Time between thumbnails in seconds
+    interval: f64,
+    /// Thumbnails by PTS (milliseconds)
+    thumbnails: Arc<RwLock<ThumbnailMap>>,
+}
+
+impl Debug for ThumbnailCache {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ThumbnailCache")
+            .field("interval", &self.interval)
+            .field(
+                "thumbnails",
+                &self.thumbnails.read().map(|t| t.len()).unwrap_or(0),
+            )
+            .finish()
+    }
+}
+
+impl ThumbnailCache {
+    /// Start generating thumbnails every `interval` seconds for a media of `duration` seconds.
+    /// The interval is raised so no more than [MAX_THUMBNAILS] are generated
+    pub fn new(ctx: &egui::Context, path: &str, interval: f64, duration: f64) -> Result<Self> {
+        let interval = Self::capped_interval(interval, duration);
+        let thumbnails = Arc::new(RwLock::new(BTreeMap::new()));
+        let weak = Arc::downgrade(&thumbnails);
+        let ctx = ctx.clone();
+        let path = path.to_string();
+        std::thread::Builder::new()
+            .name("thumbnail-cache".to_string())
+            .spawn(move || {
+                if let Err(e) = Self::run(ctx, path, interval, duration, weak) {
+                    error!("Thumbnail cache stopped: {}", e);
+                }
+            })?;
+        Ok(Self {
+            interval,
+            thumbnails,
+        })
+    }
+
+    fn run(
+        ctx: egui::Context,
+        path: String,
+        interval: f64,
+        duration: f64,
+        thumbnails: Weak<RwLock<ThumbnailMap>>,
+    ) -> Result<()> {
+        let (state, _decoder, streams) = open_video_only(&path)?;
+
+        let mut pts = 0.0;
+        while pts < duration {
+            let frame = frame_at(&state, &streams.video, pts)?;
+
+            // stop once the cache is no longer used
+            let Some(thumbnails) = thumbnails.upgrade() else {
+                return Ok(());
+            };
+            let [w, h] = frame.data.size;
+            let width = THUMBNAIL_WIDTH.min(w);
+            let height = (h * width / w.max(1)).max(1);
+            let key = (pts * 1000.0) as u64;
+            let texture = ctx.load_texture(
+                format!("thumbnail_{}", key),
+                scale_image(&frame.data, width, height),
+                Default::default(),
+            );
+            if let Ok(mut t) = thumbnails.write() {
+                t.insert(key, texture);
+            }
+            pts += interval;
+        }
+        info!("Thumbnail cache complete for {}", path);
+        Ok(())
+    }
+
+    fn capped_interval(interval: f64, duration: f64) -> f64 {
+        interval.max(0.1).max(duration / MAX_THUMBNAILS as f64)
+    }
+
+    /// Time between thumbnails in seconds
+    pub fn interval(&self) -> f64 {
+        self.interval
+    }
+
+    /// Get the thumbnail nearest to `pts`, returns the thumbnail PTS (seconds) and texture
+    pub fn get(&self, pts: f64) -> Option<(f64, TextureHandle)> {
+        let key = (pts.max(0.0) * 1000.0) as u64;
+        let thumbnails = self.thumbnails.read().ok()?;
+        let before = thumbnails.range(..=key).next_back();
+        let after = thumbnails.range(key..).next();
+        let (k, t) = match (before, after) {
+            (Some(b), Some(a)) => {
+                if key - b.0 <= a.0 - key {
+                    b
+                } else {
+                    a
+                }
+            }
+            (Some(b), None) => b,
+            (None, Some(a)) => a,
+            (None, None) => return None,
+        };
+        Some((*k as f64 / 1000.0, t.clone()))
+    }
+}
+
+/// Decodes video frames at arbitrary positions with a second [MediaDecoder], for previews
+/// while hovering the seekbar. Only the latest requested position is decoded, the
+/// decoder thread exits once every handle is dropped.
+#[derive(Clone)]
+pub struct SeekPreviewDecoder {
+    /// Latest requested PTS (milliseconds), -1 when nothing is requested
+    target: Arc<AtomicI64>,
+    /// PTS (milliseconds) of the previous request, repeated requests are ignored
+    last_request: Arc<AtomicI64>,
+    /// Last decoded frame and the PTS (seconds) it was requested at
+    frame: Arc<RwLock<Option<(f64, TextureHandle)>>>,
+}
+
+impl Debug for SeekPreviewDecoder {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SeekPreviewDecoder")
+            .field("target", &self.target.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+impl SeekPreviewDecoder {
+    /// Start the decoder thread for the media at `path`, nothing is decoded until requested
+    pub fn new(ctx: &egui::Context, path: &str) -> Result<Self> {
+        let target = Arc::new(AtomicI64::new(-1));
+        let frame = Arc::new(RwLock::new(None));
+        let weak = Arc::downgrade(&frame);
+        let ctx = ctx.clone();
+        let path = path.to_string();
+        let thread_target = target.clone();
+        std::thread::Builder::new()
+            .name("seek-preview".to_string())
+            .spawn(move || {
+                if let Err(e) = Self::run(ctx, path, thread_target, weak) {
+                    error!("Seek preview decoder stopped: {}", e);
+                }
+            })?;
+        Ok(Self {
+            target,
+            last_request: Arc::new(AtomicI64::new(-1)),
+            frame,
+        })
+    }
+
+    fn run(
+        ctx: egui::Context,
+        path: String,
+        target: Arc<AtomicI64>,
+        frame: Weak<RwLock<Option<(f64, TextureHandle)>>>,
+    ) -> Result<()> {
+        let mut decoder = None;
+        let mut texture: Option<TextureHandle> = None;
+        loop {
+            // stop once the preview is no longer used
+            let Some(slot) = frame.upgrade() else {
+                return Ok(());
+            };
+            let Ok(key) = u64::try_from(target.swap(-1, Ordering::Relaxed)) else {
+                drop(slot);
+                std::thread::sleep(Duration::from_millis(10));
+                continue;
+            };
+            let pts = key as f64 / 1000.0;
+            let (state, _, streams) = match &mut decoder {
+                Some(d) => d,
+                None => decoder.insert(open_video_only(&path)?),
+            };
+            let decoded = match frame_at(state, &streams.video, pts) {
+                Ok(f) => f,
+                Err(e) => {
+                    // the decoder thread exits at the end of the stream,
+                    // a new one is started for the next request
+                    debug!("No preview frame at {:.3}s: {}", pts, e);
+                    decoder = None;
+                    continue;
+                }
+            };
+            let [w, h] = decoded.data.size;
+            let width = THUMBNAIL_WIDTH.min(w);
+            let height = (h * width / w.max(1)).max(1);
+            let image = scale_image(&decoded.data, width, height);
+            let handle = match &mut texture {
+                Some(t) => {
+                    t.set(image, Default::default());
+                    t.clone()
+                }
+                None => texture
+                    .insert(ctx.load_texture("seek_preview", image, Default::default()))
+                    .clone(),
+            };
+            if let Ok(mut f) = slot.write() {
+                f.replace((decoded.pts, handle));
+            }
+            ctx.request_repaint();
+        }
+    }
+
+    /// Ask for the frame at `pts` (seconds), replacing any earlier request
+    pub fn request(&self, pts: f64) {
+        let key = (pts.max(0.0) * 1000.0) as i64;
+        if self.last_request.swap(key, Ordering::Relaxed) != key {
+            self.target.store(key, Ordering::Relaxed);
+        }
+    }
+
+    /// Last decoded frame, returns its PTS (seconds) and the texture
+    pub fn frame(&self) -> Option<(f64, TextureHandle)> {
+        self.frame.read().ok()?.clone()
+    }
+}
+
+/// Extracts single video frames from a media file, for file pickers or library grids
+/// which need a preview without creating a [`crate::Player`].
+/// Each call to [`ThumbnailExtractor::get_frame_at`] starts a decoder which exits after one frame.
+pub struct ThumbnailExtractor {
+    path: String,
+    info: DecoderInfo,
+}
+
+impl ThumbnailExtractor {
+    /// Probe the media at `path`, fails if it cannot be opened
+    pub fn new(path: &str) -> Result<Self> {
+        let (_decoder, streams) = Self::open(path, SharedPlaybackState::new())?;
+        let info = streams.metadata.recv().context("Failed to probe media")?;
+        Ok(Self {
+            path: path.to_string(),
+            info,
+        })
+    }
+
+    /// Stream info of the media
+    pub fn info(&self) -> &DecoderInfo {
+        &self.info
+    }
+
+    /// Decode the video frame shown at `seconds`
+    pub fn get_frame_at(&mut self, seconds: f64) -> Result<ColorImage> {
+        let state = SharedPlaybackState::new();
+        let (_decoder, streams) = Self::open(&self.path, state.clone())?;
+        let _audio = NoAudioDevice::new(streams.audio);
+        let frame = frame_at(&state, &streams.video, seconds.max(0.0))
+            .context("No video frame at this position")?;
+        Ok(frame.data)
+    }
+
+    fn open(path: &str, state: SharedPlaybackState) -> Result<(MediaDecoder, MediaStreams)> {
+        // only one frame is needed, the decoder thread exits once the channels are dropped
+        let options = StreamingOptions {
+            preload_seconds: 0.0,
+            ..Default::default()
+        };
+        MediaDecoder::new_with_options(path, state, options)
+    }
+}
+
+/// Start a decoder which only decodes video, used to pick single frames
+fn open_video_only(path: &str) -> Result<(SharedPlaybackState, MediaDecoder, MediaStreams)> {
+    let state = SharedPlaybackState::new();
+    // only one frame is needed at a time, the video channel is never resized
+    let options = StreamingOptions {
+        preload_seconds: 0.0,
+        video_only: true,
+        ..Default::default()
+    };
+    let (decoder, streams) = MediaDecoder::new_with_options(path, state.clone(), options)?;
+    Ok((state, decoder, streams))
+}
+
+/// Seek to `pts` and wait for the first frame which covers it
+fn frame_at(
+    state: &SharedPlaybackState,
+    video: &Receiver<VideoFrame>,
+    pts: f64,
+) -> Result<VideoFrame> {
+    state.set_seek(pts);
+    // frames decoded before the seek may still be arriving
+    let generation = state.seek_generation();
+    loop {
+        let frame = video.recv()?;
+        if frame.generation >= generation && frame.pts + frame.duration >= pts {
+            return Ok(frame);
+        }
+    }
+}
+
+/// Nearest-neighbour scale of an image to a new size
+pub(crate) fn scale_image(src: &ColorImage, width: usize, height: usize) -> ColorImage {
+    let [sw, sh] = src.size;
+    let mut pixels = Vec::with_capacity(width * height);
+    for y in 0..height {
+        let sy = y * sh / height;
+        for x in 0..width {
+            let sx = x * sw / width;
+            pixels.push(src.pixels[sy * sw + sx]);
+        }
+    }
+    ColorImage::new([width, height], pixels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use egui::Color32;
+
+    #[test]
+    fn scale() {
+        let src = ColorImage::new(
+            [2, 2],
+            vec![Color32::RED, Color32::GREEN, Color32::BLUE, Color32::WHITE],
+        );
+        let up = scale_image(&src, 4, 4);
+        assert_eq!(up.size, [4, 4]);
+        assert_eq!(up.pixels[0], Color32::RED);
+        assert_eq!(up.pixels[3], Color32::GREEN);
+        assert_eq!(up.pixels[12], Color32::BLUE);
+        assert_eq!(up.pixels[15], Color32::WHITE);
+
+        let down = scale_image(&up, 1, 1);
+        assert_eq!(down.pixels, vec![Color32::RED]);
+    }
+
+    #[test]
+    fn capped_interval() {
+        assert_eq!(ThumbnailCache::capped_interval(5.0, 60.0), 5.0);
+        assert_eq!(ThumbnailCache::capped_interval(0.0, 10.0), 0.1);
+        // 2h of media is capped to MAX_THUMBNAILS
+        let interval = ThumbnailCache::capped_interval(5.0, 7200.0);
+        assert_eq!((7200.0 / interval).ceil() as usize, MAX_THUMBNAILS);
+    }
+}